@@ -17,13 +17,24 @@ pub struct Infra {
 }
 
 /// Apply the infrastructure to our store, synchronizing it with our remote resources.
-pub async fn infrastructure<'b, 'a: 'b>(
-    account_id: String,
-    store: &'b mut Store<Aws>,
-) -> anyhow::Result<Infra> {
+pub async fn infrastructure<'b, 'a: 'b>(store: &'b mut Store<Aws>) -> anyhow::Result<Infra> {
+    let identity = aws::CallerIdentity::sync_current(store, "caller-identity").await?;
+
+    // handles of the resources, so their names can't be misspelled
+    let table_id = store.resource("crud-table");
+    let policy_id = store.resource("lambda-apigateway-policy");
+    let role_id = store.resource("lambda-apigateway-role");
+    let log_group_id = store.resource("lambda-log-group");
+    let lambda_id = store.resource("lambda-function");
+    let gateway_id = store.resource("gateway");
+    let stage_id = store.resource("stage");
+    let perm_id = store.resource("apigateway-lambda-invoke-perm");
+    let integration_id = store.resource("integration");
+    let route_id = store.resource("catchall-route");
+
     let dydb_table = store
         .sync(
-            "crud-table",
+            &table_id,
             aws::dynamodb::Table {
                 table_name: "crud-table".into(),
                 key_schema: vec![aws::dynamodb::KeySchemaElement::partition_key(
@@ -39,37 +50,31 @@ pub async fn infrastructure<'b, 'a: 'b>(
 
     let lambda_policy = store
         .sync(
-            "lambda-apigateway-policy",
+            &policy_id,
             aws::iam::Policy {
-                document: serde_json::json!({
-                    "Version": "2012-10-17",
-                    "Statement": [
-                        {
-                            "Sid": "Stmt1428341300017",
-                            "Action": [
+                document: aws::iam::PolicyDocument::new()
+                    .statement(
+                        aws::iam::Statement::allow()
+                            .actions([
                                 "dynamodb:DeleteItem",
                                 "dynamodb:GetItem",
                                 "dynamodb:PutItem",
                                 "dynamodb:Query",
                                 "dynamodb:Scan",
-                                "dynamodb:UpdateItem"
-                            ],
-                            "Effect": "Allow",
-                            "Resource": "*"
-                        },
-                        {
-                            "Sid": "",
-                            "Resource": "*",
-                            "Action": [
+                                "dynamodb:UpdateItem",
+                            ])
+                            .resource(dydb_table.arn.clone()),
+                    )
+                    .statement(
+                        aws::iam::Statement::allow()
+                            .actions([
                                 "logs:CreateLogGroup",
                                 "logs:CreateLogStream",
-                                "logs:PutLogEvents"
-                            ],
-                            "Effect": "Allow"
-                        }
-                    ]
-                })
-                .into(),
+                                "logs:PutLogEvents",
+                            ])
+                            .resource("*"),
+                    )
+                    .into(),
                 ..Default::default()
             },
         )
@@ -77,42 +82,45 @@ pub async fn infrastructure<'b, 'a: 'b>(
 
     let lambda_role = store
         .sync(
-            "lambda-apigateway-role",
+            &role_id,
             aws::iam::Role {
-                document: serde_json::json!({
-                    "Version": "2012-10-17",
-                    "Statement": [
-                        {
-                            "Effect": "Allow",
-                            "Action": [
-                                "sts:AssumeRole"
-                            ],
-                            "Principal": {
-                                "Service": [
-                                    "lambda.amazonaws.com"
-                                ]
-                            }
-                        }
-                    ]
-                })
-                .into(),
+                document: aws::iam::PolicyDocument::new()
+                    .statement(
+                        aws::iam::Statement::allow()
+                            .action("sts:AssumeRole")
+                            .principal(aws::iam::Principal::service("lambda.amazonaws.com")),
+                    )
+                    .into(),
                 attached_policy_arn: Some(lambda_policy.arn.clone()).into(),
                 ..Default::default()
             },
         )
         .await?;
 
+    // keep the lambda's logs for a week, instead of forever
+    store
+        .sync(
+            &log_group_id,
+            aws::lambda::LambdaLogGroup {
+                function_name: "teleform-example-lambda".into(),
+                retention_in_days: Some(7).into(),
+            },
+        )
+        .await?;
+
     let zip_file_path: Local<String> = "target/lambda/example-lambda/bootstrap.zip".into();
     let lambda = store
         .sync(
-            "lambda-function",
+            &lambda_id,
             aws::lambda::Lambda {
                 name: "teleform-example-lambda".into(),
                 role_arn: lambda_role.arn.clone().into(),
                 handler: "bootstrap".into(),
-                zip_file_hash: tele::cli::sha256_digest(zip_file_path.as_ref())?
-                    .map(Remote::Remote)
-                    .unwrap_or(Remote::Unknown),
+                zip_file_hash: if std::path::Path::new(zip_file_path.as_str()).exists() {
+                    Remote::Remote(store.hashed_file(zip_file_path.as_str())?.hash)
+                } else {
+                    Remote::Unknown
+                },
                 zip_file_path,
                 architecture: Some("arm64".into()).into(),
                 ..Default::default()
@@ -122,26 +130,28 @@ pub async fn infrastructure<'b, 'a: 'b>(
 
     let apigateway = store
         .sync(
-            "gateway",
+            &gateway_id,
             aws::apigatewayv2::ApiGatewayV2 {
                 ..Default::default()
             },
         )
         .await?;
 
-    let stage = store
+    let mut stage = store
         .sync(
-            "stage",
+            &stage_id,
             aws::apigatewayv2::Stage {
                 api_id: apigateway.api_id.clone(),
                 stage_name: "$default".into(),
                 auto_deploy: true.into(),
+                ..Default::default()
             }
         )
         .await?;
 
     // Add permission for the http gateway to call the lambda
-    let region = store.cfg.as_ref().region().context("unknown region")?;
+    let region = identity.region.maybe_ref().context("unknown region")?;
+    let account_id = identity.account_id.maybe_ref().context("unknown account id")?;
     let source_arn = apigateway
         .api_id
         .maybe_ref()
@@ -149,7 +159,7 @@ pub async fn infrastructure<'b, 'a: 'b>(
         .unwrap_or_default();
     let apigateway_lambda_perm = store
         .sync(
-            "apigateway-lambda-invoke-perm",
+            &perm_id,
             aws::lambda::LambdaAddedPermission {
                 function_arn: (|| -> Option<Remote<String>> {
                     let arn = lambda.arn.maybe_ref()?;
@@ -167,7 +177,7 @@ pub async fn infrastructure<'b, 'a: 'b>(
 
     let integration = store
         .sync(
-            "integration",
+            &integration_id,
             aws::apigatewayv2::Integration {
                 api_id: apigateway.api_id.clone().into(),
                 integration_uri: (|| -> Option<Remote<String>> {
@@ -183,7 +193,7 @@ pub async fn infrastructure<'b, 'a: 'b>(
 
     let catchall_route = store
         .sync(
-            "catchall-route",
+            &route_id,
             aws::apigatewayv2::Route {
                 api_id: apigateway.api_id.clone(),
                 route_key: "ANY /{proxy+}".into(),
@@ -198,6 +208,21 @@ pub async fn infrastructure<'b, 'a: 'b>(
         )
         .await?;
 
+    // record what depends on what, so that pruning deletes dependents first
+    store.depends_on(&policy_id, &[&table_id])?;
+    store.depends_on(&role_id, &[&policy_id])?;
+    store.depends_on(&lambda_id, &[&role_id])?;
+    store.depends_on(&stage_id, &[&gateway_id])?;
+    store.depends_on(&perm_id, &[&gateway_id, &lambda_id])?;
+    store.depends_on(&integration_id, &[&gateway_id, &lambda_id])?;
+    store.depends_on(&route_id, &[&gateway_id, &integration_id])?;
+
+    // the route is deployed asynchronously, so wait for it before anything
+    // calls the API
+    if stage.api_id.maybe_ref().is_some() {
+        stage.await_deployment(&store.cfg, stage_id.as_str()).await?;
+    }
+
     Ok(Infra {
         lambda_policy,
         lambda_role,
@@ -226,10 +251,6 @@ struct Cli {
     /// otherwise the changes will only be printed.
     #[clap(long)]
     delete: bool,
-
-    /// Your AWS account id.
-    #[clap(long)]
-    account_id: String,
 }
 
 #[::tokio::main]
@@ -238,7 +259,6 @@ async fn main() -> anyhow::Result<()> {
         verbosity,
         apply,
         delete,
-        account_id,
     } = Cli::parse();
 
     let level = match verbosity {
@@ -258,17 +278,16 @@ async fn main() -> anyhow::Result<()> {
 
     let workspace_dir = tele::cli::find_workspace_dir()?;
     let store_path = workspace_dir.join("default_store.json");
-    let backup_store_path = store_path.with_extension("bak.json");
     log::debug!("using store file: {}", store_path.display());
 
-    let aws_provider = Aws(aws_config::from_env().load().await);
-    let mut store = tele::cli::create_store(&store_path, &backup_store_path, aws_provider, apply)?;
+    let aws_provider = Aws::new(aws_config::from_env().load().await);
+    let mut store = tele::cli::create_store(&store_path, aws_provider, apply)?;
 
     let maybe_infra = if delete {
         log::warn!("deleting previous infrastructure!");
         None
     } else {
-        let infra = infrastructure(account_id, &mut store).await?;
+        let infra = infrastructure(&mut store).await?;
         log::info!(
             "Play with your app at {}",
             infra
@@ -310,8 +329,6 @@ async fn main() -> anyhow::Result<()> {
                 serde_json::to_string_pretty(&infra).unwrap(),
             )?;
         }
-        // Remove the backup
-        std::fs::remove_file(backup_store_path)?;
     }
 
     Ok(())
@@ -130,6 +130,13 @@ struct ImplDetails {
     update: Option<syn::Ident>,
     update_finalize: Option<syn::Ident>,
     delete: Option<syn::Ident>,
+    validate: Option<syn::Ident>,
+    read: Option<syn::Ident>,
+    read_many: Option<syn::Ident>,
+    exists: Option<syn::Ident>,
+    describe_change: Option<syn::Ident>,
+    references: Option<syn::Ident>,
+    schema_version: Option<syn::LitInt>,
 }
 
 fn get_impl_details(attrs: &[Attribute]) -> syn::Result<ImplDetails> {
@@ -161,10 +168,40 @@ fn get_impl_details(attrs: &[Attribute]) -> syn::Result<ImplDetails> {
                     let value = meta.value()?;
                     let ident: syn::Ident = value.parse()?;
                     details.delete = Some(ident);
+                } else if meta.path.is_ident("validate") {
+                    let value = meta.value()?;
+                    let ident: syn::Ident = value.parse()?;
+                    details.validate = Some(ident);
+                } else if meta.path.is_ident("read") {
+                    let value = meta.value()?;
+                    let ident: syn::Ident = value.parse()?;
+                    details.read = Some(ident);
+                } else if meta.path.is_ident("read_many") {
+                    let value = meta.value()?;
+                    let ident: syn::Ident = value.parse()?;
+                    details.read_many = Some(ident);
+                } else if meta.path.is_ident("exists") {
+                    let value = meta.value()?;
+                    let ident: syn::Ident = value.parse()?;
+                    details.exists = Some(ident);
+                } else if meta.path.is_ident("describe_change") {
+                    let value = meta.value()?;
+                    let ident: syn::Ident = value.parse()?;
+                    details.describe_change = Some(ident);
+                } else if meta.path.is_ident("references") {
+                    let value = meta.value()?;
+                    let ident: syn::Ident = value.parse()?;
+                    details.references = Some(ident);
+                } else if meta.path.is_ident("schema_version") {
+                    let value = meta.value()?;
+                    let version: syn::LitInt = value.parse()?;
+                    version.base10_parse::<u32>()?;
+                    details.schema_version = Some(version);
                 } else {
                     return Err(meta.error(format!(
                         "unknown attribute {:?} - must be one of 'helper', \
-                         'create', 'update' or 'delete'",
+                         'create', 'update', 'delete', 'validate', 'read', 'read_many', \
+                         'exists', 'describe_change', 'references' or 'schema_version'",
                         meta.path
                             .get_ident()
                             .map(|id| id.to_string())
@@ -192,10 +229,18 @@ pub fn derive_telesync(input: proc_macro::TokenStream) -> proc_macro::TokenStrea
         .create
         .map(|create| {
             quote! {
-                #create(self, apply, helper, name)
+                fn create<'a>(
+                    &'a mut self,
+                    apply: bool,
+                    helper: &'a Self::Provider,
+                    name: &'a str,
+                ) -> std::pin::Pin<Box<dyn std::future::Future<Output = anyhow::Result<()>> + Send + 'a>>
+                {
+                    Box::pin(#create(self, apply, helper, name))
+                }
             }
         })
-        .unwrap_or_else(|| quote! { compile_error!("missing tele_create_with attribute")});
+        .unwrap_or_default();
     let create_finalize = details
         .create_finalize
         .map(|f| {
@@ -208,10 +253,19 @@ pub fn derive_telesync(input: proc_macro::TokenStream) -> proc_macro::TokenStrea
         .update
         .map(|update| {
             quote! {
-                #update(self, apply, helper, name, previous)
+                fn update<'a>(
+                    &'a mut self,
+                    apply: bool,
+                    helper: &'a Self::Provider,
+                    name: &'a str,
+                    previous: &'a Self,
+                ) -> std::pin::Pin<Box<dyn std::future::Future<Output = anyhow::Result<()>> + Send + 'a>>
+                {
+                    Box::pin(#update(self, apply, helper, name, previous))
+                }
             }
         })
-        .unwrap_or_else(|| quote! {compile_error!("missing tele_update_with attribute")});
+        .unwrap_or_default();
     let update_finalize = details
         .update_finalize
         .map(|f| {
@@ -224,10 +278,100 @@ pub fn derive_telesync(input: proc_macro::TokenStream) -> proc_macro::TokenStrea
         .delete
         .map(|delete| {
             quote! {
-                #delete(self, apply, helper, name)
+                fn delete<'a>(
+                    &'a self,
+                    apply: bool,
+                    helper: &'a Self::Provider,
+                    name: &'a str,
+                ) -> std::pin::Pin<Box<dyn std::future::Future<Output = anyhow::Result<()>> + Send + 'a>>
+                {
+                    Box::pin(#delete(self, apply, helper, name))
+                }
+            }
+        })
+        .unwrap_or_default();
+    let validate = details
+        .validate
+        .map(|validate| {
+            quote! {
+                fn validate(&self, name: &str) -> anyhow::Result<()> {
+                    #validate(self, name)
+                }
+            }
+        })
+        .unwrap_or_default();
+    let describe_change = details
+        .describe_change
+        .map(|describe_change| {
+            quote! {
+                fn describe_change(&self, previous: &Self) -> Option<String> {
+                    #describe_change(self, previous)
+                }
+            }
+        })
+        .unwrap_or_default();
+    let references = details
+        .references
+        .map(|references| {
+            quote! {
+                fn referenced_values(&self) -> Vec<serde_json::Value> {
+                    #references(self)
+                }
+            }
+        })
+        .unwrap_or_default();
+    let read = details
+        .read
+        .map(|read| {
+            quote! {
+                fn read<'a>(
+                    &'a mut self,
+                    helper: &'a Self::Provider,
+                    name: &'a str,
+                ) -> std::pin::Pin<Box<dyn std::future::Future<Output = anyhow::Result<()>> + Send + 'a>>
+                {
+                    Box::pin(#read(self, helper, name))
+                }
+            }
+        })
+        .unwrap_or_default();
+    let read_many = details
+        .read_many
+        .map(|read_many| {
+            quote! {
+                fn read_many<'a>(
+                    resources: &'a mut std::collections::BTreeMap<String, Self>,
+                    helper: &'a Self::Provider,
+                ) -> std::pin::Pin<Box<dyn std::future::Future<Output = anyhow::Result<()>> + Send + 'a>>
+                {
+                    Box::pin(#read_many(resources, helper))
+                }
+            }
+        })
+        .unwrap_or_default();
+    let exists = details
+        .exists
+        .map(|exists| {
+            quote! {
+                fn exists<'a>(
+                    &'a self,
+                    helper: &'a Self::Provider,
+                    name: &'a str,
+                ) -> std::pin::Pin<Box<dyn std::future::Future<Output = anyhow::Result<bool>> + Send + 'a>>
+                {
+                    Box::pin(#exists(self, helper, name))
+                }
+            }
+        })
+        .unwrap_or_default();
+    let schema_version = details
+        .schema_version
+        .map(|version| {
+            quote! {
+                const SCHEMA_VERSION: Option<u32> = Some(#version);
             }
         })
-        .unwrap_or_else(|| quote! {compile_error!("missing tele_delete_with attribute")});
+        .unwrap_or_default();
     let Composite {
         function_body: composite,
         where_constraints,
@@ -247,6 +391,8 @@ pub fn derive_telesync(input: proc_macro::TokenStream) -> proc_macro::TokenStrea
         {
             type Provider = #helper;
 
+            #schema_version
+
             fn composite(self, other: Self) -> Self {
                 #composite
             }
@@ -259,54 +405,41 @@ pub fn derive_telesync(input: proc_macro::TokenStream) -> proc_macro::TokenStrea
                 #should_update
             }
 
-            fn create<'a>(
-                &'a mut self,
-                apply: bool,
-                helper: &'a Self::Provider,
-                name: &'a str,
-            ) -> std::pin::Pin<Box<dyn std::future::Future<Output = anyhow::Result<()>> + 'a>>
-            {
-                Box::pin(#create)
-            }
+            #describe_change
+
+            #references
+
+            #validate
+
+            #create
 
             fn create_finalize<'a>(
                 &'a mut self,
                 apply: bool,
                 helper: &'a Self::Provider,
                 name: &'a str,
-            ) -> std::pin::Pin<Box<dyn std::future::Future<Output = anyhow::Result<()>> + 'a>> {
+            ) -> std::pin::Pin<Box<dyn std::future::Future<Output = anyhow::Result<()>> + Send + 'a>> {
                 Box::pin(#create_finalize)
             }
 
-            fn update<'a>(
-                &'a mut self,
-                apply: bool,
-                helper: &'a Self::Provider,
-                name: &'a str,
-                previous: &'a Self,
-            ) -> std::pin::Pin<Box<dyn std::future::Future<Output = anyhow::Result<()>> + 'a>>
-            {
-                Box::pin(#update)
-            }
+            #update
 
             fn update_finalize<'a>(
                 &'a mut self,
                 apply: bool,
                 helper: &'a Self::Provider,
                 name: &'a str,
-            ) -> std::pin::Pin<Box<dyn std::future::Future<Output = anyhow::Result<()>> + 'a>> {
+            ) -> std::pin::Pin<Box<dyn std::future::Future<Output = anyhow::Result<()>> + Send + 'a>> {
                 Box::pin(#update_finalize)
             }
 
-            fn delete<'a>(
-                &'a self,
-                apply: bool,
-                helper: &'a Self::Provider,
-                name: &'a str,
-            ) -> std::pin::Pin<Box<dyn std::future::Future<Output = anyhow::Result<()>> + 'a>>
-            {
-                Box::pin(#delete)
-            }
+            #delete
+
+            #read
+
+            #read_many
+
+            #exists
         }
     };
     output.into()
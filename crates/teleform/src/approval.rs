@@ -0,0 +1,115 @@
+//! Approval of actions during apply.
+//!
+//! By default destructive actions can be approved interactively on the
+//! terminal (see [`crate::Store::set_interactive`]). Library consumers can
+//! instead approve every action with their own callback, eg to build approval
+//! around chat buttons or a web UI (see [`crate::Store::set_approval`]).
+use std::{
+    future::Future,
+    io::IsTerminal,
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Context;
+
+/// The answer to a request for approval.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Approval {
+    /// Go ahead with the action.
+    Approve,
+    /// Skip the action, leaving the resource as it is.
+    Skip,
+    /// Abort the apply with an error.
+    Abort,
+}
+
+/// An action waiting for approval, just before it runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingAction {
+    /// Name of the resource.
+    pub name: String,
+    /// What will be done - "create", "update", "recreate" or "delete".
+    pub action: &'static str,
+}
+
+impl PendingAction {
+    /// Returns whether the action deletes a resource.
+    pub fn is_destructive(&self) -> bool {
+        matches!(self.action, "recreate" | "delete")
+    }
+}
+
+pub(crate) type ApprovalFuture = Pin<Box<dyn Future<Output = anyhow::Result<Approval>> + Send>>;
+
+type ApprovalFn = dyn FnMut(PendingAction) -> ApprovalFuture + Send + Sync;
+
+/// A callback that approves actions, shared by a store and its stacks.
+#[derive(Clone)]
+pub(crate) struct Callback(pub(crate) Arc<Mutex<Box<ApprovalFn>>>);
+
+impl Callback {
+    /// Ask the callback to approve the given action.
+    pub(crate) fn call(&self, pending: PendingAction) -> ApprovalFuture {
+        let mut f = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        (*f)(pending)
+    }
+}
+
+impl std::fmt::Debug for Callback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Callback")
+    }
+}
+
+/// Interactive approval state.
+#[derive(Debug, Default)]
+pub(crate) struct Interactive {
+    pub(crate) enabled: bool,
+    approve_all: bool,
+}
+
+impl Interactive {
+    /// Returns a fresh prompt that is enabled if this one is.
+    pub(crate) fn like(&self) -> Self {
+        Interactive {
+            enabled: self.enabled,
+            approve_all: false,
+        }
+    }
+
+    /// Prompt the operator on stdin to approve a destructive action.
+    ///
+    /// Answers are `y`(es), `n`(o), `a`(ll - approve this and every following
+    /// action) or `q`(uit).
+    pub(crate) fn prompt(&mut self, question: &str) -> anyhow::Result<Approval> {
+        if !self.enabled || self.approve_all {
+            return Ok(Approval::Approve);
+        }
+        loop {
+            eprint!("{question} [y/n/a/q] ");
+            let mut answer = String::new();
+            let read = std::io::stdin()
+                .read_line(&mut answer)
+                .context("could not read approval")?;
+            if read == 0 {
+                return Ok(Approval::Abort);
+            }
+            match answer.trim() {
+                "y" | "yes" => return Ok(Approval::Approve),
+                "n" | "no" => return Ok(Approval::Skip),
+                "a" | "all" => {
+                    self.approve_all = true;
+                    return Ok(Approval::Approve);
+                }
+                "q" | "quit" => return Ok(Approval::Abort),
+                other => eprintln!("unexpected answer '{other}'"),
+            }
+        }
+    }
+}
+
+/// Returns whether stdin is interactive.
+pub(crate) fn stdin_is_terminal() -> bool {
+    std::io::stdin().is_terminal()
+}
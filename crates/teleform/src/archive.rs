@@ -0,0 +1,140 @@
+//! Exporting and importing a store's state as a single `.tar.gz` archive.
+//!
+//! Useful for handing state to a teammate, attaching it to an incident ticket
+//! or seeding a new backend. The archive holds the store file, its stacks,
+//! snapshots, audit log and hash cache, along with a manifest of their
+//! checksums that is verified on import.
+use std::{collections::BTreeMap, io::Read, path::Path};
+
+use anyhow::Context;
+
+use crate::{backend::stem_of, file, Store};
+
+/// Name of the manifest within an archive.
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// The manifest of an archive.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Manifest {
+    pub teleform_version: String,
+    /// File name of the archived store.
+    pub store: String,
+    /// SHA256 of each archived file, by path within the archive.
+    pub files: BTreeMap<String, String>,
+}
+
+impl<Config> Store<Config> {
+    /// Bundle this store's state into a `.tar.gz` archive at the given path.
+    pub fn export_archive(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let files = self.state_files()?;
+        let mut manifest = Manifest {
+            teleform_version: env!("CARGO_PKG_VERSION").to_string(),
+            store: self
+                .path
+                .file_name()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            files: BTreeMap::new(),
+        };
+        let encoder = flate2::write::GzEncoder::new(
+            std::fs::File::create(path)
+                .with_context(|| format!("could not create {}", path.display()))?,
+            flate2::Compression::default(),
+        );
+        let mut builder = tar::Builder::new(encoder);
+        for (name, file_path) in files.iter() {
+            let bytes = std::fs::read(file_path)?;
+            manifest
+                .files
+                .insert(name.clone(), crate::sha256_hex(&bytes));
+            let mut header = tar::Header::new_gnu();
+            header.set_size(bytes.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, bytes.as_slice())?;
+        }
+        let manifest = serde_json::to_vec_pretty(&manifest)?;
+        let mut header = tar::Header::new_gnu();
+        header.set_size(manifest.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, MANIFEST_FILE_NAME, manifest.as_slice())?;
+        builder.into_inner()?.finish()?;
+        log::info!("exported {} state files to {}", files.len(), path.display());
+        Ok(())
+    }
+
+    /// Replace this store's state with the state in the `.tar.gz` archive at
+    /// the given path.
+    ///
+    /// Every file is verified against the archive's manifest before anything
+    /// is written. Archived files are renamed to match this store's file name.
+    pub fn import_archive(&mut self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let decoder = flate2::read::GzDecoder::new(
+            std::fs::File::open(path)
+                .with_context(|| format!("could not open {}", path.display()))?,
+        );
+        let mut archive = tar::Archive::new(decoder);
+        let mut contents = BTreeMap::new();
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let name = entry.path()?.to_string_lossy().to_string();
+            let mut bytes = vec![];
+            entry.read_to_end(&mut bytes)?;
+            contents.insert(name, bytes);
+        }
+        let manifest: Manifest = serde_json::from_slice(
+            &contents
+                .remove(MANIFEST_FILE_NAME)
+                .context("archive has no manifest")?,
+        )
+        .context("could not read the archive's manifest")?;
+        for (name, hash) in manifest.files.iter() {
+            let bytes = contents
+                .get(name)
+                .with_context(|| format!("archive is missing {name}"))?;
+            anyhow::ensure!(
+                crate::sha256_hex(bytes) == *hash,
+                "checksum of {name} doesn't match the archive's manifest"
+            );
+        }
+        for name in contents.keys() {
+            anyhow::ensure!(
+                manifest.files.contains_key(name),
+                "{name} is not in the archive's manifest"
+            );
+            anyhow::ensure!(
+                !Path::new(name)
+                    .components()
+                    .any(|c| !matches!(c, std::path::Component::Normal(_))),
+                "refusing to import {name} - it is outside the store's directory"
+            );
+        }
+
+        let dir = self.path.parent().context("store has no directory")?;
+        let archived_stem = stem_of(Path::new(&manifest.store));
+        let stem = stem_of(&self.path);
+        for (name, bytes) in contents.iter() {
+            let name = match name.strip_prefix(&archived_stem) {
+                Some(rest) if rest.starts_with('.') => format!("{stem}{rest}"),
+                _ => name.clone(),
+            };
+            let file_path = dir.join(name);
+            if let Some(parent) = file_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&file_path, bytes)
+                .with_context(|| format!("could not write {}", file_path.display()))?;
+        }
+        let bytes = std::fs::read(&self.path)?;
+        self.rez = file::decode(&self.path, &bytes)?;
+        log::info!(
+            "imported {} state files from {}",
+            contents.len(),
+            path.display()
+        );
+        Ok(())
+    }
+}
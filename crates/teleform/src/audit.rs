@@ -0,0 +1,69 @@
+//! Structured audit log of every state mutation.
+//!
+//! Every successful action taken by an applying store appends one JSON line to
+//! `audit.log` in the store's directory, answering "who changed this and when"
+//! without digging through the git history of the store file.
+use std::io::Write;
+
+use crate::Action;
+
+/// One line of the audit log.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct AuditEntry {
+    /// Seconds since the UNIX epoch.
+    pub timestamp: u64,
+    /// Name of the resource.
+    pub resource: String,
+    /// Rust type name of the resource.
+    pub type_is: String,
+    pub action: Action,
+    /// The user that ran the action, taken from `$USER` (or `$USERNAME`).
+    pub who: String,
+    /// SHA256 of the resource's stored data before the action, or empty if it
+    /// wasn't stored yet.
+    pub old_hash: String,
+    /// SHA256 of the resource's data after the action.
+    pub new_hash: String,
+}
+
+impl AuditEntry {
+    pub(crate) fn new(
+        resource: &str,
+        type_is: &str,
+        action: Action,
+        old_hash: String,
+        new_hash: String,
+    ) -> Self {
+        AuditEntry {
+            timestamp: crate::now_secs(),
+            resource: resource.to_string(),
+            type_is: type_is.to_string(),
+            action,
+            who: std::env::var("USER")
+                .or_else(|_| std::env::var("USERNAME"))
+                .unwrap_or_else(|_| "unknown".to_string()),
+            old_hash,
+            new_hash,
+        }
+    }
+
+    /// Append this entry to the audit log at the given path.
+    pub(crate) fn append(&self, path: impl AsRef<std::path::Path>) -> anyhow::Result<()> {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        writeln!(file, "{}", serde_json::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+/// Read all entries of the audit log at the given path.
+pub fn read(path: impl AsRef<std::path::Path>) -> anyhow::Result<Vec<AuditEntry>> {
+    let contents = std::fs::read_to_string(path)?;
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| Ok(serde_json::from_str(line)?))
+        .collect()
+}
@@ -2,10 +2,9 @@
 use std::collections::HashSet;
 
 use anyhow::Context;
-use aws_config::SdkConfig;
 use aws_sdk_acm::types::{DomainStatus, RecordType};
 
-use crate::{self as tele, Local, Remote, TeleSync};
+use crate::{self as tele, aws::Aws, Local, Remote, TeleSync};
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
 pub enum ValidationMethod {
@@ -52,7 +51,7 @@ pub struct ValidationOption {
 
 /// AWS certificate.
 #[derive(TeleSync, Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
-#[tele(helper = SdkConfig)]
+#[tele(helper = Aws)]
 #[tele(create = create_cert, update = update_cert, delete = delete_cert)]
 pub struct Certificate {
     // Fully qualified domain name.
@@ -68,11 +67,11 @@ pub struct Certificate {
 async fn create_cert(
     cert: &mut Certificate,
     apply: bool,
-    cfg: &SdkConfig,
-    _name: &str,
+    cfg: &Aws,
+    name: &str,
 ) -> anyhow::Result<()> {
     if apply {
-        let client = aws_sdk_acm::Client::new(cfg);
+        let client = cfg.for_resource(name).acm();
         let validation: aws_sdk_acm::types::ValidationMethod =
             (*cert.validation_method.as_ref()).into();
         let subject_alt_names = cert.subject_alternative_names.as_ref().clone();
@@ -99,13 +98,12 @@ async fn create_cert(
 async fn update_cert(
     _cert: &mut Certificate,
     apply: bool,
-    cfg: &SdkConfig,
+    _cfg: &Aws,
     _name: &str,
     _previous: &Certificate,
 ) -> anyhow::Result<()> {
     if apply {
-        let _client = aws_sdk_acm::Client::new(cfg);
-        todo!()
+        return Err(tele::Error::not_implemented::<Certificate>("update").into());
     }
 
     Ok(())
@@ -114,22 +112,22 @@ async fn update_cert(
 async fn delete_cert(
     _cert: &Certificate,
     apply: bool,
-    cfg: &SdkConfig,
+    _cfg: &Aws,
     _name: &str,
 ) -> anyhow::Result<()> {
     if apply {
-        let _client = aws_sdk_acm::Client::new(cfg);
-        todo!()
+        return Err(tele::Error::not_implemented::<Certificate>("delete").into());
     }
     Ok(())
 }
 
 impl Certificate {
-    pub async fn get_validation_options(
-        &self,
-        cfg: &SdkConfig,
-    ) -> anyhow::Result<Vec<ValidationOption>> {
-        let client = aws_sdk_acm::Client::new(cfg);
+    /// Returns the validation options of the certificate.
+    ///
+    /// Pass the provider of the certificate's region, eg
+    /// `cfg.for_resource("cert")`.
+    pub async fn get_validation_options(&self, cfg: &Aws) -> anyhow::Result<Vec<ValidationOption>> {
+        let client = cfg.acm();
         let out = client
             .describe_certificate()
             .certificate_arn(self.arn.maybe_ref().context("missing cert arn")?)
@@ -166,3 +164,151 @@ impl Certificate {
         Ok(options)
     }
 }
+
+/// How many days before expiry a [`CertificateStatus`] warns by default.
+pub const DEFAULT_WARN_WITHIN_DAYS: u32 = 30;
+
+/// The status and expiry of a certificate.
+///
+/// A data source - syncing it looks up the certificate when applying, and
+/// deleting it does nothing. The status is unknown while planning, and while
+/// the certificate's ARN is unknown, eg before the certificate is created, so
+/// planning a new certificate along with its status doesn't fail.
+///
+/// A warning is logged when the certificate expires within
+/// `warn_within_days`, which for an Amazon issued certificate means its
+/// managed renewal is failing. Refresh it on every run to keep checking:
+///
+/// ```ignore
+/// let status = store
+///     .sync_by_name("cert-status", CertificateStatus::new(cert.arn.clone()))
+///     .await?;
+/// store.refresh::<CertificateStatus>("cert-status").await?;
+/// ```
+#[derive(TeleSync, Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[tele(helper = Aws)]
+#[tele(create = read_status, update = update_status, delete = forget_status)]
+#[tele(read = read_status_of)]
+pub struct CertificateStatus {
+    pub certificate_arn: Remote<String>,
+    pub warn_within_days: Local<u32>,
+    // eg "ISSUED" or "PENDING_VALIDATION".
+    pub status: Remote<String>,
+    // Expiry, in seconds since the unix epoch.
+    pub not_after: Remote<i64>,
+}
+
+impl Default for CertificateStatus {
+    fn default() -> Self {
+        CertificateStatus {
+            certificate_arn: Remote::default(),
+            warn_within_days: DEFAULT_WARN_WITHIN_DAYS.into(),
+            status: Remote::default(),
+            not_after: Remote::default(),
+        }
+    }
+}
+
+impl CertificateStatus {
+    pub fn new(certificate_arn: impl Into<Remote<String>>) -> Self {
+        CertificateStatus {
+            certificate_arn: certificate_arn.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Make the status unknown.
+    fn forget(&mut self) {
+        self.status = Remote::Unknown;
+        self.not_after = Remote::Unknown;
+    }
+
+    /// Returns the whole days until the certificate expires, if known.
+    pub fn days_until_expiry(&self) -> Option<i64> {
+        let not_after = *self.not_after.maybe_ref()?;
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()?
+            .as_secs() as i64;
+        Some((not_after - now).div_euclid(60 * 60 * 24))
+    }
+}
+
+async fn read_status(
+    status: &mut CertificateStatus,
+    apply: bool,
+    cfg: &Aws,
+    name: &str,
+) -> anyhow::Result<()> {
+    if apply {
+        read_status_of(status, cfg, name).await
+    } else {
+        status.forget();
+        Ok(())
+    }
+}
+
+async fn update_status(
+    status: &mut CertificateStatus,
+    apply: bool,
+    cfg: &Aws,
+    name: &str,
+    _previous: &CertificateStatus,
+) -> anyhow::Result<()> {
+    read_status(status, apply, cfg, name).await
+}
+
+/// Reads the status of the certificate, which is unknown until the
+/// certificate's ARN is.
+async fn read_status_of(
+    status: &mut CertificateStatus,
+    cfg: &Aws,
+    name: &str,
+) -> anyhow::Result<()> {
+    let Some(arn) = status.certificate_arn.maybe_ref() else {
+        log::debug!("the certificate ARN of {name} is unknown, so its status is too");
+        status.forget();
+        return Ok(());
+    };
+    let out = cfg
+        .for_resource(name)
+        .acm()
+        .describe_certificate()
+        .certificate_arn(arn)
+        .send()
+        .await?;
+    let detail = out.certificate.context("missing cert detail")?;
+    status.status = detail
+        .status
+        .context("missing status")?
+        .as_str()
+        .to_string()
+        .into();
+    status.not_after = detail
+        .not_after
+        .map(|t| Remote::Remote(t.secs()))
+        .unwrap_or_default();
+    if let Some(days) = status.days_until_expiry() {
+        if days <= i64::from(*status.warn_within_days) {
+            let managed = detail.r#type == Some(aws_sdk_acm::types::CertificateType::AmazonIssued);
+            log::warn!(
+                "certificate {arn} of {name} expires in {days} days{}",
+                if managed {
+                    " - check its managed renewal"
+                } else {
+                    ""
+                }
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn forget_status(
+    _: &CertificateStatus,
+    _apply: bool,
+    _cfg: &Aws,
+    _name: &str,
+) -> anyhow::Result<()> {
+    Ok(())
+}
@@ -1,9 +1,8 @@
 //! ApiGatewayV2 infrastructure.
 use anyhow::Context;
-use aws_config::SdkConfig;
 use aws_sdk_apigatewayv2::types as aws;
 
-use crate::{self as tele, Local, Remote, TeleEither, TeleSync};
+use crate::{self as tele, aws::Aws, Local, Remote, TeleEither, TeleSync};
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
 pub enum Protocol {
@@ -22,7 +21,7 @@ impl Protocol {
 }
 
 #[derive(TeleSync, Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
-#[tele(helper = SdkConfig)]
+#[tele(helper = Aws)]
 #[tele(create = create_api, update = update_api, delete = delete_api)]
 pub struct ApiGatewayV2 {
     pub target_lambda_arn: Option<Remote<String>>,
@@ -34,13 +33,13 @@ pub struct ApiGatewayV2 {
 async fn create_api(
     api: &mut ApiGatewayV2,
     apply: bool,
-    cfg: &SdkConfig,
+    cfg: &Aws,
     name: &str,
 ) -> anyhow::Result<()> {
     if apply {
         let protocol =
             aws_sdk_apigatewayv2::types::ProtocolType::from(api.protocol.as_ref().as_str());
-        let client = aws_sdk_apigatewayv2::Client::new(cfg);
+        let client = cfg.for_resource(name).apigatewayv2();
         let out = client
             .create_api()
             .name(name)
@@ -63,26 +62,20 @@ async fn create_api(
 async fn update_api(
     _: &mut ApiGatewayV2,
     apply: bool,
-    cfg: &SdkConfig,
-    _: &str,
+    _cfg: &Aws,
+    _name: &str,
     _: &ApiGatewayV2,
 ) -> anyhow::Result<()> {
     if apply {
-        let _client = aws_sdk_apigatewayv2::Client::new(cfg);
-        todo!()
+        return Err(tele::Error::not_implemented::<ApiGatewayV2>("update").into());
     }
 
     Ok(())
 }
 
-async fn delete_api(
-    api: &ApiGatewayV2,
-    apply: bool,
-    cfg: &SdkConfig,
-    _: &str,
-) -> anyhow::Result<()> {
+async fn delete_api(api: &ApiGatewayV2, apply: bool, cfg: &Aws, name: &str) -> anyhow::Result<()> {
     if apply {
-        let client = aws_sdk_apigatewayv2::Client::new(cfg);
+        let client = cfg.for_resource(name).apigatewayv2();
         client
             .delete_api()
             .api_id(
@@ -119,7 +112,7 @@ impl From<IntegrationType> for aws_sdk_apigatewayv2::types::IntegrationType {
 }
 
 #[derive(TeleSync, Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
-#[tele(helper = SdkConfig)]
+#[tele(helper = Aws)]
 #[tele(create = create_integration, update = update_integration, delete = delete_integration)]
 pub struct Integration {
     pub api_id: Remote<String>,
@@ -133,11 +126,11 @@ pub struct Integration {
 async fn create_integration(
     integration: &mut Integration,
     apply: bool,
-    cfg: &SdkConfig,
-    _: &str,
+    cfg: &Aws,
+    name: &str,
 ) -> anyhow::Result<()> {
     if apply {
-        let client = aws_sdk_apigatewayv2::Client::new(cfg);
+        let client = cfg.for_resource(name).apigatewayv2();
         let out =
             client
                 .create_integration()
@@ -162,12 +155,12 @@ async fn create_integration(
 async fn update_integration(
     integration: &mut Integration,
     apply: bool,
-    cfg: &SdkConfig,
-    _name: &str,
+    cfg: &Aws,
+    name: &str,
     _previous: &Integration,
 ) -> anyhow::Result<()> {
     if apply {
-        let client = aws_sdk_apigatewayv2::Client::new(cfg);
+        let client = cfg.for_resource(name).apigatewayv2();
         let _out =
             client
                 .update_integration()
@@ -197,11 +190,11 @@ async fn update_integration(
 async fn delete_integration(
     integration: &Integration,
     apply: bool,
-    cfg: &SdkConfig,
-    _name: &str,
+    cfg: &Aws,
+    name: &str,
 ) -> anyhow::Result<()> {
     if apply {
-        let client = aws_sdk_apigatewayv2::Client::new(cfg);
+        let client = cfg.for_resource(name).apigatewayv2();
         let _ = client
             .delete_integration()
             .integration_id(
@@ -223,7 +216,7 @@ async fn delete_integration(
 }
 
 #[derive(TeleSync, Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
-#[tele(helper = SdkConfig)]
+#[tele(helper = Aws)]
 #[tele(create = create_route, update = update_route, delete = delete_route)]
 pub struct Route {
     // Id of the ApiGatewayV2 gateway
@@ -236,14 +229,9 @@ pub struct Route {
     pub route_id: Remote<String>,
 }
 
-async fn create_route(
-    route: &mut Route,
-    apply: bool,
-    cfg: &SdkConfig,
-    _: &str,
-) -> anyhow::Result<()> {
+async fn create_route(route: &mut Route, apply: bool, cfg: &Aws, name: &str) -> anyhow::Result<()> {
     if apply {
-        let client = aws_sdk_apigatewayv2::Client::new(cfg);
+        let client = cfg.for_resource(name).apigatewayv2();
         let out = client
             .create_route()
             .api_id(
@@ -271,12 +259,12 @@ async fn create_route(
 async fn update_route(
     route: &mut Route,
     apply: bool,
-    cfg: &SdkConfig,
-    _name: &str,
+    cfg: &Aws,
+    name: &str,
     _previous: &Route,
 ) -> anyhow::Result<()> {
     if apply {
-        let client = aws_sdk_apigatewayv2::Client::new(cfg);
+        let client = cfg.for_resource(name).apigatewayv2();
         let out = client
             .update_route()
             .route_id(
@@ -308,14 +296,9 @@ async fn update_route(
     Ok(())
 }
 
-async fn delete_route(
-    route: &Route,
-    apply: bool,
-    cfg: &SdkConfig,
-    _name: &str,
-) -> anyhow::Result<()> {
+async fn delete_route(route: &Route, apply: bool, cfg: &Aws, name: &str) -> anyhow::Result<()> {
     if apply {
-        let client = aws_sdk_apigatewayv2::Client::new(cfg);
+        let client = cfg.for_resource(name).apigatewayv2();
         let _ = client
             .delete_route()
             .api_id(
@@ -337,23 +320,107 @@ async fn delete_route(
 }
 
 #[derive(TeleSync, Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
-#[tele(helper = SdkConfig)]
+#[tele(helper = Aws)]
 #[tele(create = create_stage, update = update_stage, delete = delete_stage)]
+#[tele(create_finalize = finalize_stage, update_finalize = finalize_stage, read = read_stage)]
 pub struct Stage {
     #[tele(should_recreate)]
     pub api_id: Remote<String>,
     pub stage_name: Local<String>,
     pub auto_deploy: Local<bool>,
+    // Wait for the stage's deployment to finish after creating or updating it.
+    #[serde(default)]
+    pub wait_for_deployment: Local<bool>,
+    // Known once the stage is deployed.
+    #[serde(default)]
+    pub deployment_id: Remote<String>,
+    // Status of the latest deployment, eg "DEPLOYED", "PENDING" or "FAILED".
+    #[serde(default)]
+    pub deployment_status: Remote<String>,
+}
+
+/// Returns the id and status of the stage's latest deployment, if it has one.
+async fn latest_deployment(
+    client: &aws_sdk_apigatewayv2::Client,
+    api_id: &str,
+    stage_name: &str,
+) -> anyhow::Result<Option<(String, aws::DeploymentStatus)>> {
+    let stage = client
+        .get_stage()
+        .api_id(api_id)
+        .stage_name(stage_name)
+        .send()
+        .await?;
+    let Some(deployment_id) = stage.deployment_id else {
+        return Ok(None);
+    };
+    let deployment = client
+        .get_deployment()
+        .api_id(api_id)
+        .deployment_id(&deployment_id)
+        .send()
+        .await?;
+    let status = deployment
+        .deployment_status
+        .context("missing deployment status")?;
+    anyhow::ensure!(
+        status != aws::DeploymentStatus::Failed,
+        "deployment {deployment_id} of stage {stage_name} failed: {}",
+        deployment
+            .deployment_status_message
+            .as_deref()
+            .unwrap_or("no reason given")
+    );
+    Ok(Some((deployment_id, status)))
+}
+
+impl Stage {
+    /// Wait until the stage's latest deployment is done, and record it in
+    /// `deployment_id` and `deployment_status`.
+    ///
+    /// Routes and integrations are deployed to auto-deployed stages
+    /// asynchronously, so call this after syncing them, before anything
+    /// calls the API. Errs if the deployment failed.
+    pub async fn await_deployment(&mut self, cfg: &Aws, name: &str) -> anyhow::Result<()> {
+        let client = cfg.for_resource(name).apigatewayv2();
+        let api_id = self
+            .api_id
+            .maybe_ref()
+            .context("cannot await deployment - missing api_id")?;
+        let stage_name = self.stage_name.as_str();
+        let client = &client;
+        cfg.wait()
+            .until(&format!("deployment of stage {name}"), move || async move {
+                Ok(!matches!(
+                    latest_deployment(client, api_id, stage_name).await?,
+                    Some((_, aws::DeploymentStatus::Pending))
+                ))
+            })
+            .await?;
+        self.record_deployment(client).await
+    }
+
+    async fn record_deployment(
+        &mut self,
+        client: &aws_sdk_apigatewayv2::Client,
+    ) -> anyhow::Result<()> {
+        let api_id = self
+            .api_id
+            .maybe_ref()
+            .context("cannot read deployment - missing api_id")?;
+        if let Some((deployment_id, status)) =
+            latest_deployment(client, api_id, self.stage_name.as_str()).await?
+        {
+            self.deployment_id = deployment_id.into();
+            self.deployment_status = status.as_str().to_string().into();
+        }
+        Ok(())
+    }
 }
 
-async fn create_stage(
-    stage: &mut Stage,
-    apply: bool,
-    cfg: &SdkConfig,
-    _name: &str,
-) -> anyhow::Result<()> {
+async fn create_stage(stage: &mut Stage, apply: bool, cfg: &Aws, name: &str) -> anyhow::Result<()> {
     if apply {
-        let client = aws_sdk_apigatewayv2::Client::new(cfg);
+        let client = cfg.for_resource(name).apigatewayv2();
         let _ = client
             .create_stage()
             .api_id(
@@ -373,12 +440,12 @@ async fn create_stage(
 async fn update_stage(
     stage: &mut Stage,
     apply: bool,
-    cfg: &SdkConfig,
-    _name: &str,
+    cfg: &Aws,
+    name: &str,
     _previous: &Stage,
 ) -> anyhow::Result<()> {
     if apply {
-        let client = aws_sdk_apigatewayv2::Client::new(cfg);
+        let client = cfg.for_resource(name).apigatewayv2();
         client
             .update_stage()
             .api_id(
@@ -396,14 +463,26 @@ async fn update_stage(
     Ok(())
 }
 
-async fn delete_stage(
-    stage: &Stage,
+async fn finalize_stage(
+    stage: &mut Stage,
     apply: bool,
-    cfg: &SdkConfig,
-    _name: &str,
+    cfg: &Aws,
+    name: &str,
 ) -> anyhow::Result<()> {
+    if apply && *stage.wait_for_deployment {
+        stage.await_deployment(cfg, name).await?;
+    }
+    Ok(())
+}
+
+async fn read_stage(stage: &mut Stage, cfg: &Aws, name: &str) -> anyhow::Result<()> {
+    let client = cfg.for_resource(name).apigatewayv2();
+    stage.record_deployment(&client).await
+}
+
+async fn delete_stage(stage: &Stage, apply: bool, cfg: &Aws, name: &str) -> anyhow::Result<()> {
     if apply {
-        let client = aws_sdk_apigatewayv2::Client::new(cfg);
+        let client = cfg.for_resource(name).apigatewayv2();
         let _ = client
             .delete_stage()
             .api_id(
@@ -475,7 +554,7 @@ impl From<DomainNameConfiguration> for aws::DomainNameConfiguration {
 }
 
 #[derive(TeleSync, Debug, Clone, serde::Serialize, serde::Deserialize)]
-#[tele(helper = SdkConfig)]
+#[tele(helper = Aws)]
 #[tele(create = create_domain_name, update = update_domain_name, delete = delete_domain_name)]
 pub struct DomainName {
     pub domain_name: Local<String>,
@@ -486,11 +565,11 @@ pub struct DomainName {
 async fn create_domain_name(
     domain_name: &mut DomainName,
     apply: bool,
-    cfg: &SdkConfig,
-    _name: &str,
+    cfg: &Aws,
+    name: &str,
 ) -> anyhow::Result<()> {
     if apply {
-        let client = aws_sdk_apigatewayv2::Client::new(cfg);
+        let client = cfg.for_resource(name).apigatewayv2();
         let out = client
             .create_domain_name()
             .domain_name(domain_name.domain_name.as_str())
@@ -518,13 +597,12 @@ async fn create_domain_name(
 async fn update_domain_name(
     _domain_name: &mut DomainName,
     apply: bool,
-    cfg: &SdkConfig,
+    _cfg: &Aws,
     _name: &str,
     _previous: &DomainName,
 ) -> anyhow::Result<()> {
     if apply {
-        let _client = aws_sdk_apigatewayv2::Client::new(cfg);
-        todo!()
+        return Err(tele::Error::not_implemented::<DomainName>("update").into());
     }
 
     Ok(())
@@ -533,11 +611,11 @@ async fn update_domain_name(
 async fn delete_domain_name(
     domain_name: &DomainName,
     apply: bool,
-    cfg: &SdkConfig,
-    _name: &str,
+    cfg: &Aws,
+    name: &str,
 ) -> anyhow::Result<()> {
     if apply {
-        let client = aws_sdk_apigatewayv2::Client::new(cfg);
+        let client = cfg.for_resource(name).apigatewayv2();
         client
             .delete_domain_name()
             .domain_name(domain_name.domain_name.as_str())
@@ -548,25 +626,37 @@ async fn delete_domain_name(
 }
 
 #[derive(TeleSync, Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
-#[tele(helper = SdkConfig)]
+#[tele(helper = Aws)]
 #[tele(create = create_mapping, update = update_mapping, delete = delete_mapping)]
 pub struct ApiMapping {
     // Likely depends on upstream remote values.
     pub api_id: Remote<String>,
+    #[tele(should_recreate)]
     pub domain_name: Local<String>,
-    //pub api_mapping_key: Local<String>,
+    // Path the API is mapped under, eg "v1" maps it to "<domain>/v1". Empty
+    // maps it to the root of the domain.
+    #[serde(default)]
+    pub api_mapping_key: Local<String>,
     pub stage: Local<String>,
     pub api_mapping_id: Remote<String>,
 }
 
+impl ApiMapping {
+    /// Returns the mapping key, if the API isn't mapped to the root of the
+    /// domain.
+    fn api_mapping_key(&self) -> Option<String> {
+        Some(self.api_mapping_key.as_str().to_string()).filter(|key| !key.is_empty())
+    }
+}
+
 async fn create_mapping(
     mapping: &mut ApiMapping,
     apply: bool,
-    cfg: &SdkConfig,
-    _name: &str,
+    cfg: &Aws,
+    name: &str,
 ) -> anyhow::Result<()> {
     if apply {
-        let client = aws_sdk_apigatewayv2::Client::new(cfg);
+        let client = cfg.for_resource(name).apigatewayv2();
         let out = client
             .create_api_mapping()
             .api_id(
@@ -575,7 +665,7 @@ async fn create_mapping(
                     .maybe_ref()
                     .context("cannot create mapping - missing api_id")?,
             )
-            //.api_mapping_key(mapping.api_mapping_key.as_str())
+            .set_api_mapping_key(mapping.api_mapping_key())
             .domain_name(mapping.domain_name.as_str())
             .stage(mapping.stage.as_str())
             .send()
@@ -589,28 +679,46 @@ async fn create_mapping(
 }
 
 async fn update_mapping(
-    _mapping: &mut ApiMapping,
+    mapping: &mut ApiMapping,
     apply: bool,
-    cfg: &SdkConfig,
-    _name: &str,
+    cfg: &Aws,
+    name: &str,
     _previous: &ApiMapping,
 ) -> anyhow::Result<()> {
     if apply {
-        let _client = aws_sdk_apigatewayv2::Client::new(cfg);
-        todo!()
+        let client = cfg.for_resource(name).apigatewayv2();
+        client
+            .update_api_mapping()
+            .api_id(
+                mapping
+                    .api_id
+                    .maybe_ref()
+                    .context("cannot update mapping - missing api_id")?,
+            )
+            .api_mapping_id(
+                mapping
+                    .api_mapping_id
+                    .maybe_ref()
+                    .context("cannot update mapping - missing api_mapping_id")?,
+            )
+            // always set the key, so an empty key maps the API to the root again
+            .api_mapping_key(mapping.api_mapping_key.as_str())
+            .domain_name(mapping.domain_name.as_str())
+            .stage(mapping.stage.as_str())
+            .send()
+            .await?;
     }
-
     Ok(())
 }
 
 async fn delete_mapping(
     mapping: &ApiMapping,
     apply: bool,
-    cfg: &SdkConfig,
-    _name: &str,
+    cfg: &Aws,
+    name: &str,
 ) -> anyhow::Result<()> {
     if apply {
-        let client = aws_sdk_apigatewayv2::Client::new(cfg);
+        let client = cfg.for_resource(name).apigatewayv2();
         let _out = client
             .delete_api_mapping()
             .api_mapping_id(
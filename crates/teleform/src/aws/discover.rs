@@ -0,0 +1,465 @@
+//! Discovering existing AWS resources, to adopt them into a store.
+//!
+//! Accounts that predate teleform already have lambdas, tables, buckets and
+//! APIs. [`discover`] lists the ones whose names start with a prefix, or that
+//! have a given tag, and [`Discovered::import`] stores them, so the next sync
+//! of each updates the existing resource instead of creating a new one:
+//!
+//! ```ignore
+//! let found = tele::aws::discover::discover(&aws, &Filter::name_prefix("shop-")).await?;
+//! let mut store = Store::new(true, aws, "store.json");
+//! found.import(&mut store)?;
+//! store.save(store.path())?;
+//! ```
+//!
+//! Resources are stored by their AWS names. Only what AWS reports is filled
+//! in - eg a lambda's zip file path and a bucket's ACL can't be discovered, so
+//! lambdas get an empty path and buckets are assumed to be "private". Declare
+//! the imported resources in the program before the next prune, since
+//! stored resources that are no longer declared are deleted by default (see
+//! [`crate::orphans`]).
+use std::collections::BTreeMap;
+
+use crate::{aws::Aws, Store};
+
+/// Which existing resources to discover.
+///
+/// A resource must match both the name prefix and the tag, when given.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    pub name_prefix: Option<String>,
+    /// Key and value of a tag.
+    pub tag: Option<(String, String)>,
+}
+
+impl Filter {
+    /// Resources whose names start with the given prefix.
+    pub fn name_prefix(prefix: impl Into<String>) -> Self {
+        Filter {
+            name_prefix: Some(prefix.into()),
+            tag: None,
+        }
+    }
+
+    /// Resources with the given tag.
+    pub fn tag(key: impl Into<String>, value: impl Into<String>) -> Self {
+        Filter {
+            name_prefix: None,
+            tag: Some((key.into(), value.into())),
+        }
+    }
+
+    fn matches_name(&self, name: &str) -> bool {
+        self.name_prefix
+            .as_deref()
+            .map(|prefix| name.starts_with(prefix))
+            .unwrap_or(true)
+    }
+
+    fn matches_tags<'a>(&self, mut tags: impl Iterator<Item = (&'a str, &'a str)>) -> bool {
+        let Some((key, value)) = self.tag.as_ref() else {
+            return true;
+        };
+        tags.any(|(k, v)| k == key && v == value)
+    }
+}
+
+/// Existing resources found by [`discover`], by name.
+#[derive(Debug, Default)]
+pub struct Discovered {
+    #[cfg(feature = "aws-lambda")]
+    pub lambdas: BTreeMap<String, crate::aws::lambda::Lambda>,
+    #[cfg(feature = "aws-dynamodb")]
+    pub tables: BTreeMap<String, crate::aws::dynamodb::Table>,
+    #[cfg(feature = "aws-s3")]
+    pub buckets: BTreeMap<String, crate::aws::s3::Bucket>,
+    #[cfg(feature = "aws-apigatewayv2")]
+    pub apis: BTreeMap<String, crate::aws::apigatewayv2::ApiGatewayV2>,
+}
+
+impl Discovered {
+    /// Returns the names of all discovered resources.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = vec![];
+        #[cfg(feature = "aws-lambda")]
+        names.extend(self.lambdas.keys().map(String::as_str));
+        #[cfg(feature = "aws-dynamodb")]
+        names.extend(self.tables.keys().map(String::as_str));
+        #[cfg(feature = "aws-s3")]
+        names.extend(self.buckets.keys().map(String::as_str));
+        #[cfg(feature = "aws-apigatewayv2")]
+        names.extend(self.apis.keys().map(String::as_str));
+        names
+    }
+
+    /// Store the discovered resources.
+    ///
+    /// Resources already in the store are skipped. Errs if resources of
+    /// different types have the same name.
+    pub fn import<Config: AsRef<Aws>>(self, store: &mut Store<Config>) -> anyhow::Result<()> {
+        let mut names = self.names();
+        names.sort();
+        let duplicates = names
+            .windows(2)
+            .filter(|pair| pair[0] == pair[1])
+            .map(|pair| pair[0].to_string())
+            .collect::<Vec<_>>();
+        anyhow::ensure!(
+            duplicates.is_empty(),
+            "discovered resources of different types with the same names: {duplicates:?}"
+        );
+        let is_new = |store: &Store<Config>, name: &str| {
+            if store.rez.contains_key(name) {
+                log::warn!("{name} is already stored - skipping it");
+                false
+            } else {
+                log::info!("importing {name}");
+                true
+            }
+        };
+        #[cfg(feature = "aws-lambda")]
+        for (name, lambda) in self.lambdas {
+            if is_new(store, &name) {
+                store.insert(name, lambda)?;
+            }
+        }
+        #[cfg(feature = "aws-dynamodb")]
+        for (name, table) in self.tables {
+            if is_new(store, &name) {
+                store.insert(name, table)?;
+            }
+        }
+        #[cfg(feature = "aws-s3")]
+        for (name, bucket) in self.buckets {
+            if is_new(store, &name) {
+                store.insert(name, bucket)?;
+            }
+        }
+        #[cfg(feature = "aws-apigatewayv2")]
+        for (name, api) in self.apis {
+            if is_new(store, &name) {
+                store.insert(name, api)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Discover the existing lambdas, tables, buckets and APIs that match the
+/// filter.
+pub async fn discover(cfg: &Aws, filter: &Filter) -> anyhow::Result<Discovered> {
+    let discovered = Discovered {
+        #[cfg(feature = "aws-lambda")]
+        lambdas: lambdas(cfg, filter).await?,
+        #[cfg(feature = "aws-dynamodb")]
+        tables: tables(cfg, filter).await?,
+        #[cfg(feature = "aws-s3")]
+        buckets: buckets(cfg, filter).await?,
+        #[cfg(feature = "aws-apigatewayv2")]
+        apis: apis(cfg, filter).await?,
+    };
+    Ok(discovered)
+}
+
+/// Discover the existing lambdas that match the filter.
+///
+/// The zip file path and hash of each lambda are left empty, so the first
+/// sync with the real zip file uploads its code.
+#[cfg(feature = "aws-lambda")]
+pub async fn lambdas(
+    cfg: &Aws,
+    filter: &Filter,
+) -> anyhow::Result<BTreeMap<String, crate::aws::lambda::Lambda>> {
+    use crate::{aws::lambda::Lambda, Remote};
+
+    let client = cfg.lambda();
+    let mut found = BTreeMap::new();
+    let mut marker = None;
+    loop {
+        let out = client
+            .list_functions()
+            .set_marker(marker.take())
+            .send()
+            .await?;
+        for function in out.functions().unwrap_or_default() {
+            let Some(name) = function.function_name() else {
+                continue;
+            };
+            if !filter.matches_name(name) {
+                continue;
+            }
+            let arn = function.function_arn().unwrap_or_default();
+            if filter.tag.is_some() {
+                let tags = client.list_tags().resource(arn).send().await?;
+                let tags = tags.tags().cloned().unwrap_or_default();
+                if !filter.matches_tags(tags.iter().map(|(k, v)| (k.as_str(), v.as_str()))) {
+                    continue;
+                }
+            }
+            let lambda = Lambda {
+                name: name.to_string().into(),
+                role_arn: function.role().unwrap_or_default().to_string().into(),
+                handler: function.handler().unwrap_or_default().to_string().into(),
+                zip_file_path: String::new().into(),
+                zip_file_hash: Remote::Unknown,
+                architecture: function
+                    .architectures()
+                    .and_then(|archs| archs.first())
+                    .map(|arch| arch.as_str().to_string())
+                    .into(),
+                environment: function
+                    .environment()
+                    .and_then(|env| env.variables())
+                    .map(|vars| {
+                        vars.iter()
+                            .map(|(k, v)| (k.clone(), Remote::from(v.clone())))
+                            .collect()
+                    }),
+                timeout: function.timeout().into(),
+                arn: arn.to_string().into(),
+                version: function.version().unwrap_or_default().to_string().into(),
+            };
+            found.insert(name.to_string(), lambda);
+        }
+        marker = out.next_marker().map(ToString::to_string);
+        if marker.is_none() {
+            break;
+        }
+    }
+    Ok(found)
+}
+
+/// Discover the existing DynamoDB tables that match the filter.
+#[cfg(feature = "aws-dynamodb")]
+pub async fn tables(
+    cfg: &Aws,
+    filter: &Filter,
+) -> anyhow::Result<BTreeMap<String, crate::aws::dynamodb::Table>> {
+    use crate::aws::dynamodb::{
+        AttributeType, BillingMode, KeySchemaElement, KeyType, Table, TableClass,
+    };
+    use anyhow::Context;
+    use aws_sdk_dynamodb::types as aws;
+
+    let client = cfg.dynamodb();
+    let mut found = BTreeMap::new();
+    let mut start = None;
+    loop {
+        let out = client
+            .list_tables()
+            .set_exclusive_start_table_name(start.take())
+            .send()
+            .await?;
+        for name in out.table_names().unwrap_or_default() {
+            if !filter.matches_name(name) {
+                continue;
+            }
+            let description = client
+                .describe_table()
+                .table_name(name)
+                .send()
+                .await?
+                .table
+                .with_context(|| format!("missing description of table {name}"))?;
+            let arn = description.table_arn().unwrap_or_default();
+            if filter.tag.is_some() {
+                let tags = client
+                    .list_tags_of_resource()
+                    .resource_arn(arn)
+                    .send()
+                    .await?;
+                let tags = tags.tags().unwrap_or_default();
+                if !filter.matches_tags(tags.iter().map(|tag| {
+                    (
+                        tag.key().unwrap_or_default(),
+                        tag.value().unwrap_or_default(),
+                    )
+                })) {
+                    continue;
+                }
+            }
+            let attribute_type = |attribute_name: &str| {
+                description
+                    .attribute_definitions()
+                    .unwrap_or_default()
+                    .iter()
+                    .find(|def| def.attribute_name() == Some(attribute_name))
+                    .and_then(|def| def.attribute_type())
+                    .map(|type_is| match type_is {
+                        aws::ScalarAttributeType::B => AttributeType::Binary,
+                        aws::ScalarAttributeType::N => AttributeType::Number,
+                        _ => AttributeType::String,
+                    })
+                    .unwrap_or(AttributeType::String)
+            };
+            let key_schema = description
+                .key_schema()
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|element| {
+                    let attribute_name = element.attribute_name()?;
+                    Some(KeySchemaElement {
+                        attribute_name: attribute_name.to_string(),
+                        key_type: match element.key_type()? {
+                            aws::KeyType::Range => KeyType::Range,
+                            _ => KeyType::Hash,
+                        },
+                        attribute_type: attribute_type(attribute_name),
+                    })
+                })
+                .collect::<Vec<_>>();
+            let billing_mode = match description
+                .billing_mode_summary()
+                .and_then(|summary| summary.billing_mode())
+            {
+                Some(aws::BillingMode::PayPerRequest) => BillingMode::PayPerRequest,
+                _ => {
+                    let throughput = description.provisioned_throughput();
+                    BillingMode::Provisioned {
+                        read_capacity_units: throughput
+                            .and_then(|t| t.read_capacity_units())
+                            .unwrap_or_default(),
+                        write_capacity_units: throughput
+                            .and_then(|t| t.write_capacity_units())
+                            .unwrap_or_default(),
+                    }
+                }
+            };
+            let table_class = match description
+                .table_class_summary()
+                .and_then(|summary| summary.table_class())
+            {
+                Some(aws::TableClass::StandardInfrequentAccess) => {
+                    TableClass::StandardInfrequentAccess
+                }
+                _ => TableClass::Standard,
+            };
+            let table = Table {
+                table_name: name.clone().into(),
+                table_class: table_class.into(),
+                key_schema: key_schema.into(),
+                billing_mode: billing_mode.into(),
+                arn: arn.to_string().into(),
+                id: description
+                    .table_id()
+                    .unwrap_or_default()
+                    .to_string()
+                    .into(),
+            };
+            found.insert(name.clone(), table);
+        }
+        start = out.last_evaluated_table_name().map(ToString::to_string);
+        if start.is_none() {
+            break;
+        }
+    }
+    Ok(found)
+}
+
+/// Discover the existing S3 buckets that match the filter.
+///
+/// Bucket ACLs aren't discovered, so every bucket is assumed to be "private".
+#[cfg(feature = "aws-s3")]
+pub async fn buckets(
+    cfg: &Aws,
+    filter: &Filter,
+) -> anyhow::Result<BTreeMap<String, crate::aws::s3::Bucket>> {
+    use crate::aws::s3::Bucket;
+
+    let client = cfg.s3();
+    let mut found = BTreeMap::new();
+    let out = client.list_buckets().send().await?;
+    for name in out
+        .buckets()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|bucket| bucket.name())
+    {
+        if !filter.matches_name(name) {
+            continue;
+        }
+        if filter.tag.is_some() {
+            // buckets without tags (or in other regions) err
+            let tags = match client.get_bucket_tagging().bucket(name).send().await {
+                Ok(out) => out.tag_set().unwrap_or_default().to_vec(),
+                Err(e) => {
+                    log::debug!("could not get the tags of bucket {name}: {e}");
+                    vec![]
+                }
+            };
+            if !filter.matches_tags(tags.iter().map(|tag| {
+                (
+                    tag.key().unwrap_or_default(),
+                    tag.value().unwrap_or_default(),
+                )
+            })) {
+                continue;
+            }
+        }
+        let versioning = client
+            .get_bucket_versioning()
+            .bucket(name)
+            .send()
+            .await?
+            .status()
+            == Some(&aws_sdk_s3::types::BucketVersioningStatus::Enabled);
+        let bucket = Bucket {
+            acl: "private".to_string().into(),
+            bucket_name: name.to_string().into(),
+            versioning: versioning.into(),
+            encryption: None.into(),
+            public_access_block: None.into(),
+        };
+        found.insert(name.to_string(), bucket);
+    }
+    Ok(found)
+}
+
+/// Discover the existing API Gateway V2 APIs that match the filter.
+///
+/// APIs are created with their resource names, so each is stored by its name.
+#[cfg(feature = "aws-apigatewayv2")]
+pub async fn apis(
+    cfg: &Aws,
+    filter: &Filter,
+) -> anyhow::Result<BTreeMap<String, crate::aws::apigatewayv2::ApiGatewayV2>> {
+    use crate::aws::apigatewayv2::{ApiGatewayV2, Protocol};
+
+    let client = cfg.apigatewayv2();
+    let mut found = BTreeMap::new();
+    let mut next_token = None;
+    loop {
+        let out = client
+            .get_apis()
+            .set_next_token(next_token.take())
+            .send()
+            .await?;
+        for api in out.items().unwrap_or_default() {
+            let Some(name) = api.name() else {
+                continue;
+            };
+            let tags = api.tags().cloned().unwrap_or_default();
+            if !filter.matches_name(name)
+                || !filter.matches_tags(tags.iter().map(|(k, v)| (k.as_str(), v.as_str())))
+            {
+                continue;
+            }
+            let protocol = match api.protocol_type() {
+                Some(aws_sdk_apigatewayv2::types::ProtocolType::Websocket) => Protocol::Websocket,
+                _ => Protocol::Http,
+            };
+            let api = ApiGatewayV2 {
+                target_lambda_arn: None,
+                protocol: protocol.into(),
+                api_id: api.api_id().unwrap_or_default().to_string().into(),
+                api_endpoint: api.api_endpoint().unwrap_or_default().to_string().into(),
+            };
+            found.insert(name.to_string(), api);
+        }
+        next_token = out.next_token().map(ToString::to_string);
+        if next_token.is_none() {
+            break;
+        }
+    }
+    Ok(found)
+}
@@ -1,8 +1,8 @@
 //! AWS DynamoDB infrastructure.
-use crate::{self as tele, Local, Remote, TeleSync};
+use crate::{self as tele, aws::Aws, Local, Remote, TeleSync};
 use anyhow::Context;
-use aws_config::SdkConfig;
 use aws_sdk_dynamodb::types as aws;
+use std::collections::{BTreeMap, HashSet};
 
 #[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
 pub enum KeyType {
@@ -140,12 +140,14 @@ impl From<BillingMode> for Option<aws::ProvisionedThroughput> {
 }
 
 #[derive(TeleSync, Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
-#[tele(helper = SdkConfig)]
+#[tele(helper = Aws)]
 #[tele(
     create = create_table,
     create_finalize = create_finalize_table,
     update = update_table,
-    delete = delete_table
+    delete = delete_table,
+    exists = table_exists,
+    read_many = tables_exist
 )]
 pub struct Table {
     pub table_name: Local<String>,
@@ -159,14 +161,9 @@ pub struct Table {
     pub id: Remote<String>,
 }
 
-async fn create_table(
-    table: &mut Table,
-    apply: bool,
-    cfg: &SdkConfig,
-    _name: &str,
-) -> anyhow::Result<()> {
+async fn create_table(table: &mut Table, apply: bool, cfg: &Aws, name: &str) -> anyhow::Result<()> {
     if apply {
-        let client = aws_sdk_dynamodb::Client::new(cfg);
+        let client = cfg.for_resource(name).dynamodb();
         let out = client
             .create_table()
             .table_name(table.table_name.as_str())
@@ -209,36 +206,32 @@ async fn create_table(
 pub async fn create_finalize_table(
     table: &mut Table,
     apply: bool,
-    cfg: &SdkConfig,
-    _name: &str,
+    cfg: &Aws,
+    name: &str,
 ) -> anyhow::Result<()> {
     if apply {
-        // timeout after 5 minutes
-        let timeout_secs = 60 * 5;
-        let start = std::time::Instant::now();
-        log::info!("awaiting table creation finialization");
-        loop {
-            let client = aws_sdk_dynamodb::Client::new(cfg);
-            let out = client
-                .describe_table()
-                .table_name(&table.table_name.0)
-                .send()
-                .await?;
-            let table_info = out.table.context("missing table description")?;
-            if table_info.table_status == Some(aws::TableStatus::Active) {
-                log::info!("...finalized");
-                return Ok(());
-            }
-            anyhow::ensure!(
-                table_info.table_status == Some(aws::TableStatus::Creating),
-                "table finalization failed, table status: {:?}",
-                table_info.table_status
-            );
-            if (std::time::Instant::now() - start).as_secs() >= timeout_secs {
-                anyhow::bail!("finalization timed out after {timeout_secs} seconds");
-            }
-            tokio::time::sleep(std::time::Duration::from_secs(3)).await;
-        }
+        let client = cfg.for_resource(name).dynamodb();
+        let table_name = table.table_name.0.as_str();
+        let client = &client;
+        cfg.wait()
+            .until("table creation finalization", move || async move {
+                let out = client
+                    .describe_table()
+                    .table_name(table_name)
+                    .send()
+                    .await?;
+                let table_info = out.table.context("missing table description")?;
+                anyhow::ensure!(
+                    matches!(
+                        table_info.table_status,
+                        Some(aws::TableStatus::Active | aws::TableStatus::Creating)
+                    ),
+                    "table finalization failed, table status: {:?}",
+                    table_info.table_status
+                );
+                Ok(table_info.table_status == Some(aws::TableStatus::Active))
+            })
+            .await
     } else {
         Ok(())
     }
@@ -247,26 +240,68 @@ pub async fn create_finalize_table(
 async fn update_table(
     _table: &mut Table,
     apply: bool,
-    cfg: &SdkConfig,
+    _cfg: &Aws,
     _name: &str,
     _previous: &Table,
 ) -> anyhow::Result<()> {
     if apply {
-        let _client = aws_sdk_dynamodb::Client::new(cfg);
-        todo!()
+        return Err(tele::Error::not_implemented::<Table>("update").into());
     }
 
     Ok(())
 }
 
-async fn delete_table(
-    table: &Table,
-    apply: bool,
-    cfg: &SdkConfig,
-    _name: &str,
-) -> anyhow::Result<()> {
+async fn table_exists(table: &Table, cfg: &Aws, name: &str) -> anyhow::Result<bool> {
+    let client = cfg.for_resource(name).dynamodb();
+    match client
+        .describe_table()
+        .table_name(table.table_name.as_str())
+        .send()
+        .await
+    {
+        Ok(_) => Ok(true),
+        Err(e) => {
+            let e = e.into_service_error();
+            if e.is_resource_not_found_exception() {
+                Ok(false)
+            } else {
+                Err(e.into())
+            }
+        }
+    }
+}
+
+/// Drop the tables that no longer exist, with one sweep of `list_tables` per
+/// region. Tables can't be read, so the rest are left as they are.
+async fn tables_exist(tables: &mut BTreeMap<String, Table>, cfg: &Aws) -> anyhow::Result<()> {
+    for (aws, names) in cfg.group_by_overrides(tables.keys()) {
+        let client = aws.dynamodb();
+        let mut existing = HashSet::new();
+        let mut start = None;
+        loop {
+            let out = client
+                .list_tables()
+                .set_exclusive_start_table_name(start.take())
+                .send()
+                .await?;
+            existing.extend(out.table_names.unwrap_or_default());
+            start = out.last_evaluated_table_name;
+            if start.is_none() {
+                break;
+            }
+        }
+        for name in names {
+            if !existing.contains(tables[&name].table_name.as_str()) {
+                tables.remove(&name);
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn delete_table(table: &Table, apply: bool, cfg: &Aws, name: &str) -> anyhow::Result<()> {
     if apply {
-        let client = aws_sdk_dynamodb::Client::new(cfg);
+        let client = cfg.for_resource(name).dynamodb();
         let _ = client
             .delete_table()
             .table_name(table.table_name.as_ref())
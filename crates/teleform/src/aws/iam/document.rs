@@ -0,0 +1,242 @@
+//! Typed IAM policy documents.
+//!
+//! Builds the JSON AWS expects for a [`Policy`](super::Policy) or the trust
+//! policy of a [`Role`](super::Role), with the ARNs of other resources as
+//! [`Remote`] values:
+//!
+//! ```ignore
+//! let document = PolicyDocument::new()
+//!     .statement(
+//!         Statement::allow()
+//!             .actions(["dynamodb:GetItem", "dynamodb:PutItem"])
+//!             .resource(table.arn.clone()),
+//!     )
+//!     .statement(
+//!         Statement::allow()
+//!             .actions(["logs:CreateLogStream", "logs:PutLogEvents"])
+//!             .resource("*"),
+//!     );
+//! let policy = store
+//!     .sync_by_name("policy", Policy { document: document.clone().into(), ..Default::default() })
+//!     .await?;
+//! store.depends_on_by_name("policy", document.referenced_resources(&store))?;
+//! ```
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{Local, Remote, Store};
+
+/// The policy language version of every document.
+pub const VERSION: &str = "2012-10-17";
+
+/// An IAM policy document.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PolicyDocument {
+    pub version: String,
+    pub statement: Vec<Statement>,
+}
+
+impl Default for PolicyDocument {
+    fn default() -> Self {
+        PolicyDocument {
+            version: VERSION.to_string(),
+            statement: vec![],
+        }
+    }
+}
+
+impl PolicyDocument {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a statement.
+    pub fn statement(mut self, statement: Statement) -> Self {
+        self.statement.push(statement);
+        self
+    }
+
+    /// Returns the ARNs referenced by the document's statements, including
+    /// unknown ones.
+    pub fn arns(&self) -> Vec<&Remote<String>> {
+        self.statement
+            .iter()
+            .flat_map(|statement| {
+                statement.resource.iter().chain(
+                    statement
+                        .principal
+                        .iter()
+                        .flat_map(|principal| principal.0.values().flatten()),
+                )
+            })
+            .collect()
+    }
+
+    /// Returns the names of the stored resources whose ARNs are referenced by
+    /// the document, eg to record them with [`Store::depends_on`].
+    pub fn referenced_resources<Config>(&self, store: &Store<Config>) -> Vec<String> {
+        let arns = self
+            .arns()
+            .into_iter()
+            .filter_map(|arn| arn.maybe_ref())
+            .map(|arn| serde_json::Value::String(arn.clone()))
+            .collect::<Vec<_>>();
+        store
+            .rez
+            .iter()
+            .filter(|(_, rez)| match &rez.data {
+                serde_json::Value::Object(fields) => {
+                    fields.values().any(|value| arns.contains(value))
+                }
+                _ => false,
+            })
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+}
+
+impl From<PolicyDocument> for serde_json::Value {
+    fn from(document: PolicyDocument) -> Self {
+        // UNWRAP: safe because documents only hold strings and JSON values
+        serde_json::to_value(document).unwrap()
+    }
+}
+
+impl From<PolicyDocument> for Local<serde_json::Value> {
+    fn from(document: PolicyDocument) -> Self {
+        Local(document.into())
+    }
+}
+
+/// Whether a statement allows or denies its actions.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Effect {
+    #[default]
+    Allow,
+    Deny,
+}
+
+/// The principals a statement applies to, by kind, eg "Service" or "AWS".
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Principal(pub BTreeMap<String, Vec<Remote<String>>>);
+
+impl Principal {
+    /// An AWS service, eg "lambda.amazonaws.com".
+    pub fn service(service: impl Into<String>) -> Self {
+        let service: String = service.into();
+        Principal::default().and("Service", service)
+    }
+
+    /// An AWS account, user or role, by ARN.
+    pub fn aws(arn: impl Into<Remote<String>>) -> Self {
+        Principal::default().and("AWS", arn)
+    }
+
+    /// Add a principal of the given kind.
+    pub fn and(mut self, kind: impl Into<String>, principal: impl Into<Remote<String>>) -> Self {
+        self.0
+            .entry(kind.into())
+            .or_default()
+            .push(principal.into());
+        self
+    }
+}
+
+/// A statement of a [`PolicyDocument`].
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Statement {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
+    pub effect: Effect,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub principal: Option<Principal>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub action: BTreeSet<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub resource: Vec<Remote<String>>,
+    /// Conditions by operator, eg "StringEquals", then by key, eg
+    /// "aws:SourceAccount".
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub condition: BTreeMap<String, BTreeMap<String, serde_json::Value>>,
+}
+
+impl Statement {
+    /// A statement that allows its actions.
+    pub fn allow() -> Self {
+        Statement::default()
+    }
+
+    /// A statement that denies its actions.
+    pub fn deny() -> Self {
+        Statement {
+            effect: Effect::Deny,
+            ..Default::default()
+        }
+    }
+
+    /// Set the statement id.
+    pub fn sid(mut self, sid: impl Into<String>) -> Self {
+        self.sid = Some(sid.into());
+        self
+    }
+
+    /// Add an action, eg "s3:GetObject".
+    pub fn action(mut self, action: impl Into<String>) -> Self {
+        self.action.insert(action.into());
+        self
+    }
+
+    /// Add actions.
+    pub fn actions(mut self, actions: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.action.extend(actions.into_iter().map(Into::into));
+        self
+    }
+
+    /// Add a resource by ARN, or "*" for all resources.
+    pub fn resource(mut self, arn: impl Into<Remote<String>>) -> Self {
+        self.resource.push(arn.into());
+        self
+    }
+
+    /// Set the principals the statement applies to.
+    pub fn principal(mut self, principal: Principal) -> Self {
+        self.principal = Some(principal);
+        self
+    }
+
+    /// Add a condition, eg `("StringEquals", "aws:SourceAccount", "123456789012")`.
+    pub fn condition(
+        mut self,
+        operator: impl Into<String>,
+        key: impl Into<String>,
+        value: impl Into<serde_json::Value>,
+    ) -> Self {
+        self.condition
+            .entry(operator.into())
+            .or_default()
+            .insert(key.into(), value.into());
+        self
+    }
+}
+
+/// Returns the JSON of a policy document.
+///
+/// Errs if the document has unknown values (ie nulls), eg the ARN of a
+/// resource that hasn't been created yet.
+pub(crate) fn to_json(name: &str, document: &serde_json::Value) -> anyhow::Result<String> {
+    fn has_null(value: &serde_json::Value) -> bool {
+        match value {
+            serde_json::Value::Null => true,
+            serde_json::Value::Array(values) => values.iter().any(has_null),
+            serde_json::Value::Object(fields) => fields.values().any(has_null),
+            _ => false,
+        }
+    }
+    anyhow::ensure!(
+        !has_null(document),
+        "the policy document of {name} has unknown values - sync the resources it refers to \
+         first"
+    );
+    Ok(serde_json::to_string(document)?)
+}
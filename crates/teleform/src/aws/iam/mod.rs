@@ -1,12 +1,16 @@
 //! IAM infrastructure.
 use anyhow::Context;
-use aws_config::SdkConfig;
 
-use crate::{self as tele, Local, Remote, TeleSync};
+use crate::{self as tele, aws::Aws, Local, Remote, TeleSync};
 
-/// AWS policy resource
+pub mod document;
+pub use document::{Effect, PolicyDocument, Principal, Statement};
+
+/// AWS policy resource.
+///
+/// Build its document with a [`PolicyDocument`], or write it by hand.
 #[derive(TeleSync, Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
-#[tele(helper = SdkConfig)]
+#[tele(helper = Aws)]
 #[tele(create = create_policy, update = update_policy, delete = delete_policy)]
 pub struct Policy {
     pub document: Local<serde_json::Value>,
@@ -17,15 +21,15 @@ pub struct Policy {
 async fn create_policy(
     policy: &mut Policy,
     apply: bool,
-    cfg: &SdkConfig,
+    cfg: &Aws,
     name: &str,
 ) -> anyhow::Result<()> {
     if apply {
-        let client = aws_sdk_iam::Client::new(cfg);
+        let client = cfg.for_resource(name).iam();
         let iam_policy = client
             .create_policy()
             .policy_name(name)
-            .policy_document(&serde_json::to_string(&policy.document)?)
+            .policy_document(&document::to_json(name, &policy.document)?)
             .send()
             .await?
             .policy
@@ -39,24 +43,65 @@ async fn create_policy(
     Ok(())
 }
 
+/// The maximum number of versions AWS keeps of a policy.
+const MAX_POLICY_VERSIONS: usize = 5;
+
+/// Make room for a new version of the policy with the given ARN, deleting its
+/// oldest non-default version if it already has the maximum number of versions.
+async fn prune_policy_versions(
+    client: &aws_sdk_iam::Client,
+    name: &str,
+    policy_arn: &str,
+) -> anyhow::Result<()> {
+    let mut versions = client
+        .list_policy_versions()
+        .policy_arn(policy_arn)
+        .send()
+        .await?
+        .versions
+        .unwrap_or_default();
+    if versions.len() >= MAX_POLICY_VERSIONS {
+        versions.sort_by_key(|v| v.create_date().map(|d| d.as_nanos()));
+        let oldest = versions
+            .iter()
+            .find(|v| !v.is_default_version())
+            .and_then(|v| v.version_id())
+            .context("no non-default policy version to delete")?
+            .to_string();
+        log::info!("deleting the oldest version {oldest} of policy {name}");
+        client
+            .delete_policy_version()
+            .policy_arn(policy_arn)
+            .version_id(&oldest)
+            .send()
+            .await?;
+        versions.retain(|v| v.version_id() != Some(oldest.as_str()));
+    }
+    log::info!(
+        "policy {name} will have {} of {MAX_POLICY_VERSIONS} versions",
+        versions.len() + 1
+    );
+    Ok(())
+}
+
 async fn update_policy(
     policy: &mut Policy,
     apply: bool,
-    cfg: &SdkConfig,
-    _name: &str,
+    cfg: &Aws,
+    name: &str,
     _: &Policy,
 ) -> anyhow::Result<()> {
     if apply {
-        let client = aws_sdk_iam::Client::new(cfg);
+        let client = cfg.for_resource(name).iam();
+        let policy_arn = policy
+            .arn
+            .maybe_ref()
+            .context("cannot update policy - missing arn")?;
+        prune_policy_versions(&client, name, policy_arn).await?;
         let out = client
             .create_policy_version()
-            .policy_arn(
-                policy
-                    .arn
-                    .maybe_ref()
-                    .context("cannot update policy - missing arn")?,
-            )
-            .policy_document(&serde_json::to_string(&policy.document)?)
+            .policy_arn(policy_arn)
+            .policy_document(&document::to_json(name, &policy.document)?)
             .set_as_default(true)
             .send()
             .await?;
@@ -70,15 +115,10 @@ async fn update_policy(
     Ok(())
 }
 
-async fn delete_policy(
-    policy: &Policy,
-    apply: bool,
-    cfg: &SdkConfig,
-    _: &str,
-) -> anyhow::Result<()> {
+async fn delete_policy(policy: &Policy, apply: bool, cfg: &Aws, name: &str) -> anyhow::Result<()> {
     if apply {
         if apply {
-            let client = aws_sdk_iam::Client::new(cfg);
+            let client = cfg.for_resource(name).iam();
             let _ = client
                 .delete_policy()
                 .policy_arn(policy.arn.maybe_ref().context("missing arn")?)
@@ -91,27 +131,22 @@ async fn delete_policy(
 
 /// AWS role resources.
 #[derive(TeleSync, Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
-#[tele(helper = aws_config::SdkConfig)]
+#[tele(helper = Aws)]
 #[tele(create = create_role, update = update_role, delete = delete_role)]
 pub struct Role {
-    #[tele(should_recreate)]
+    // The trust policy of the role, ie who may assume it.
     pub document: Local<serde_json::Value>,
     pub attached_policy_arn: Local<Option<Remote<String>>>,
     pub arn: Remote<String>,
 }
 
-async fn create_role(
-    role: &mut Role,
-    apply: bool,
-    cfg: &SdkConfig,
-    name: &str,
-) -> anyhow::Result<()> {
+async fn create_role(role: &mut Role, apply: bool, cfg: &Aws, name: &str) -> anyhow::Result<()> {
     if apply {
-        let client = aws_sdk_iam::Client::new(cfg);
+        let client = cfg.for_resource(name).iam();
         let iam_role = client
             .create_role()
             .role_name(name)
-            .assume_role_policy_document(serde_json::to_string(&role.document)?)
+            .assume_role_policy_document(document::to_json(name, &role.document)?)
             .send()
             .await?
             .role
@@ -129,10 +164,22 @@ async fn create_role(
 async fn update_role(
     role: &mut Role,
     apply: bool,
-    cfg: &SdkConfig,
+    cfg: &Aws,
     name: &str,
     previous: &Role,
 ) -> anyhow::Result<()> {
+    if role.document != previous.document {
+        log::info!("updating the trust policy of role {name}");
+        if apply {
+            let client = cfg.for_resource(name).iam();
+            client
+                .update_assume_role_policy()
+                .role_name(name)
+                .policy_document(document::to_json(name, &role.document)?)
+                .send()
+                .await?;
+        }
+    }
     if role.attached_policy_arn != previous.attached_policy_arn {
         if let Some(prev_policy) = previous.attached_policy_arn.as_ref() {
             log::info!("detaching previous policy {prev_policy} from role {name}");
@@ -164,7 +211,7 @@ async fn update_role(
     Ok(())
 }
 
-async fn delete_role(role: &Role, apply: bool, cfg: &SdkConfig, name: &str) -> anyhow::Result<()> {
+async fn delete_role(role: &Role, apply: bool, cfg: &Aws, name: &str) -> anyhow::Result<()> {
     if apply {
         if let Some(policy_arn) = role.attached_policy_arn.as_ref() {
             detach_policy(
@@ -176,18 +223,18 @@ async fn delete_role(role: &Role, apply: bool, cfg: &SdkConfig, name: &str) -> a
             )
             .await?;
         }
-        let client = aws_sdk_iam::Client::new(cfg);
+        let client = cfg.for_resource(name).iam();
         let _ = client.delete_role().role_name(name).send().await?;
     }
     Ok(())
 }
 
 //pub async fn create_role(
-//    cfg: &SdkConfig,
+//    cfg: &Aws,
 //    name: &str,
 //    document: &serde_json::Value,
 //) -> anyhow::Result<aws_sdk_iam::types::Role> {
-//    let client = aws_sdk_iam::Client::new(cfg);
+//    let client = cfg.iam();
 //    let role = client
 //        .create_role()
 //        .role_name(name)
@@ -200,18 +247,14 @@ async fn delete_role(role: &Role, apply: bool, cfg: &SdkConfig, name: &str) -> a
 //    Ok(role)
 //}
 
-//pub async fn delete_role(cfg: &SdkConfig, name: &str) -> anyhow::Result<()> {
-//    let client = aws_sdk_iam::Client::new(cfg);
+//pub async fn delete_role(cfg: &Aws, name: &str) -> anyhow::Result<()> {
+//    let client = cfg.iam();
 //    let _ = client.delete_role().role_name(name).send().await?;
 //    Ok(())
 //}
 
-pub async fn attach_policy(
-    cfg: &SdkConfig,
-    role_name: &str,
-    policy_arn: &str,
-) -> anyhow::Result<()> {
-    let client = aws_sdk_iam::Client::new(cfg);
+pub async fn attach_policy(cfg: &Aws, role_name: &str, policy_arn: &str) -> anyhow::Result<()> {
+    let client = cfg.for_resource(role_name).iam();
     let _ = client
         .attach_role_policy()
         .role_name(role_name)
@@ -222,12 +265,8 @@ pub async fn attach_policy(
     Ok(())
 }
 
-pub async fn detach_policy(
-    cfg: &SdkConfig,
-    role_name: &str,
-    policy_arn: &str,
-) -> anyhow::Result<()> {
-    let client = aws_sdk_iam::Client::new(cfg);
+pub async fn detach_policy(cfg: &Aws, role_name: &str, policy_arn: &str) -> anyhow::Result<()> {
+    let client = cfg.for_resource(role_name).iam();
     let _ = client
         .detach_role_policy()
         .role_name(role_name)
@@ -1,18 +1,21 @@
 //! AWS Lambda infrastructure.
 use anyhow::Context;
-use aws_config::SdkConfig;
-use aws_sdk_lambda::types::{self as aws, Architecture, LastUpdateStatus};
+use aws_sdk_lambda::{
+    operation::get_function_configuration::GetFunctionConfigurationOutput,
+    types::{self as aws, Architecture, LastUpdateStatus, State},
+};
 use std::{
     collections::{BTreeMap, HashMap},
-    io::Read,
     str::FromStr,
 };
 
-use crate::{self as tele, Local, Remote, TeleSync};
+use crate::{self as tele, aws::Aws, Local, Remote, TeleSync};
 
 #[derive(TeleSync, Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
-#[tele(helper = SdkConfig)]
+#[tele(helper = Aws)]
 #[tele(create = create_lambda, update = update_lambda, delete = delete_lambda)]
+#[tele(validate = validate_lambda, read = read_lambda, read_many = read_lambdas)]
+#[tele(describe_change = describe_lambda_change, references = lambda_references)]
 pub struct Lambda {
     #[tele(should_recreate)]
     pub name: Local<String>,
@@ -24,7 +27,9 @@ pub struct Lambda {
     pub zip_file_hash: Remote<String>,
     pub architecture: Local<Option<String>>,
     #[serde(default)]
-    pub environment: Local<Option<BTreeMap<String, String>>>,
+    // Environment variables, which may be remote values of other resources,
+    // eg the name of a table. Syncing records those resources as references.
+    pub environment: Option<BTreeMap<String, Remote<String>>>,
     #[serde(default)]
     // Time (in seconds) the function can run before being terminated.
     pub timeout: Local<Option<i32>>,
@@ -35,31 +40,148 @@ pub struct Lambda {
 }
 
 impl Lambda {
-    pub fn environment(&self) -> Option<aws::Environment> {
-        let env = self.environment.as_ref().as_ref()?;
-        Some(
+    /// Returns the environment of the lambda.
+    ///
+    /// Errs if any environment variable is still unknown.
+    pub fn environment(&self) -> anyhow::Result<Option<aws::Environment>> {
+        let Some(env) = self.environment.as_ref() else {
+            return Ok(None);
+        };
+        let mut variables = HashMap::new();
+        for (k, v) in env.iter() {
+            let v = v
+                .maybe_ref()
+                .with_context(|| format!("environment variable {k} is unknown"))?;
+            variables.insert(k.to_string(), v.to_string());
+        }
+        Ok(Some(
             aws::builders::EnvironmentBuilder::default()
-                .set_variables(Some(HashMap::from_iter(
-                    env.iter().map(|(k, v)| (k.to_string(), v.to_string())),
-                )))
+                .set_variables(Some(variables))
                 .build(),
-        )
+        ))
+    }
+}
+
+fn validate_lambda(lambda: &Lambda, _: &str) -> anyhow::Result<()> {
+    anyhow::ensure!(!lambda.name.is_empty(), "lambda name is empty");
+    anyhow::ensure!(
+        std::path::Path::new(lambda.zip_file_path.as_str()).is_file(),
+        "zip file {} does not exist",
+        lambda.zip_file_path.as_str()
+    );
+    Ok(())
+}
+
+/// Returns the known environment values, which may be remote values of
+/// other resources.
+fn lambda_references(lambda: &Lambda) -> Vec<serde_json::Value> {
+    lambda
+        .environment
+        .iter()
+        .flatten()
+        .filter_map(|(_, value)| value.maybe_ref())
+        .map(|value| serde_json::Value::String(value.clone()))
+        .collect()
+}
+
+/// Summarize code changes by the zip's size and hash, eg
+/// "code changed: 1.2 MB zip, hash 3fa2c1d0 → 9b71e4aa".
+fn describe_lambda_change(lambda: &Lambda, previous: &Lambda) -> Option<String> {
+    if lambda.zip_file_hash == previous.zip_file_hash {
+        return None;
+    }
+    let short_hash = |hash: &Remote<String>| {
+        hash.maybe_ref()
+            .map(|hash| hash.chars().take(8).collect::<String>())
+            .unwrap_or_else(|| "unknown".to_string())
+    };
+    let size = std::fs::metadata(lambda.zip_file_path.as_str())
+        .map(|metadata| format!("{:.1} MB", metadata.len() as f64 / 1_000_000.0))
+        .unwrap_or_else(|_| "missing".to_string());
+    let mut description = format!(
+        "code changed: {size} zip, hash {} → {}",
+        short_hash(&previous.zip_file_hash),
+        short_hash(&lambda.zip_file_hash)
+    );
+    let others = [
+        ("role_arn", lambda.role_arn != previous.role_arn),
+        ("handler", lambda.handler != previous.handler),
+        ("architecture", lambda.architecture != previous.architecture),
+        ("environment", lambda.environment != previous.environment),
+        ("timeout", lambda.timeout != previous.timeout),
+    ]
+    .into_iter()
+    .filter_map(|(field, changed)| changed.then_some(field))
+    .collect::<Vec<_>>();
+    if !others.is_empty() {
+        description.push_str(&format!("\nalso changed: {}", others.join(", ")));
     }
+    Some(description)
+}
+
+/// Poll the lambda's configuration until `is_done` returns true.
+async fn await_lambda(
+    cfg: &Aws,
+    client: &aws_sdk_lambda::Client,
+    lambda: &Lambda,
+    what: &str,
+    is_done: impl Fn(&GetFunctionConfigurationOutput) -> anyhow::Result<bool>,
+) -> anyhow::Result<()> {
+    let is_done = &is_done;
+    cfg.wait()
+        .until(what, move || async move {
+            let out = client
+                .get_function_configuration()
+                .function_name(lambda.name.as_str())
+                .send()
+                .await?;
+            is_done(&out)
+        })
+        .await
+}
+
+/// Wait for a newly created lambda to become active.
+async fn await_active(
+    cfg: &Aws,
+    client: &aws_sdk_lambda::Client,
+    lambda: &Lambda,
+) -> anyhow::Result<()> {
+    await_lambda(cfg, client, lambda, "lambda activation", |out| {
+        let state = out.state.as_ref().context("missing state")?;
+        anyhow::ensure!(
+            *state != State::Failed,
+            "lambda failed to activate: {}",
+            out.state_reason.as_deref().unwrap_or("unknown")
+        );
+        Ok(*state == State::Active)
+    })
+    .await
+}
+
+/// Wait for an update of a lambda to finish.
+async fn await_finalization(
+    cfg: &Aws,
+    client: &aws_sdk_lambda::Client,
+    lambda: &Lambda,
+) -> anyhow::Result<()> {
+    await_lambda(cfg, client, lambda, "update finalization", |out| {
+        let last_update_status = out.last_update_status.as_ref().context("missing status")?;
+        Ok(*last_update_status == LastUpdateStatus::Successful)
+    })
+    .await
 }
 
 async fn create_lambda(
     lambda: &mut Lambda,
     apply: bool,
-    cfg: &SdkConfig,
+    cfg: &Aws,
     name: &str,
 ) -> anyhow::Result<()> {
     if apply {
-        let client = aws_sdk_lambda::Client::new(cfg);
-        let file = std::fs::File::open(lambda.zip_file_path.as_ref())?;
-        let bytes: Vec<u8> = file.bytes().into_iter().try_fold(vec![], |mut acc, byte| {
-            acc.push(byte?);
-            anyhow::Ok(acc)
-        })?;
+        let client = cfg.for_resource(name).lambda();
+        let bytes = crate::rt::read(lambda.zip_file_path.as_ref())
+            .await
+            .with_context(|| format!("could not read {}", lambda.zip_file_path.as_str()))?;
         let arch = lambda
             .architecture
             .as_ref()
@@ -74,7 +196,7 @@ async fn create_lambda(
             .package_type(aws_sdk_lambda::types::PackageType::Zip)
             .runtime(aws_sdk_lambda::types::Runtime::Providedal2)
             .set_architectures(Some(vec![arch]))
-            .set_environment(lambda.environment())
+            .set_environment(lambda.environment()?)
             .set_timeout(lambda.timeout.as_ref().clone())
             .role(
                 lambda
@@ -93,6 +215,7 @@ async fn create_lambda(
             .await?;
         lambda.arn = out.function_arn.context("missing arn")?.into();
         lambda.version = out.version.context("missing version")?.into();
+        await_active(cfg, &client, lambda).await?;
         log::info!("...created lambda {name}");
     }
     Ok(())
@@ -101,35 +224,12 @@ async fn create_lambda(
 async fn update_lambda(
     lambda: &mut Lambda,
     apply: bool,
-    cfg: &SdkConfig,
+    cfg: &Aws,
     name: &str,
     previous: &Lambda,
 ) -> anyhow::Result<()> {
     if apply {
-        let client = aws_sdk_lambda::Client::new(cfg);
-
-        async fn await_finalization(client: &aws_sdk_lambda::Client, lambda: &Lambda) -> anyhow::Result<()> {
-            // timeout after 5 minutes
-            let timeout_secs = 60 * 5;
-            let start = std::time::Instant::now();
-            log::info!("awaiting update finialization");
-            loop {
-                let out = client
-                    .get_function_configuration()
-                    .function_name(lambda.name.as_str())
-                    .send()
-                    .await?;
-                let last_update_status = out.last_update_status.context("missing status")?;
-                if last_update_status == LastUpdateStatus::Successful {
-                    break;
-                }
-                if (std::time::Instant::now() - start).as_secs() >= timeout_secs {
-                    anyhow::bail!("finalization timed out after {timeout_secs} seconds");
-                }
-                tokio::time::sleep(std::time::Duration::from_secs(3)).await;
-            }
-            Ok(())
-        }
+        let client = cfg.for_resource(name).lambda();
 
         let mut needs_new_version = false;
         if lambda.zip_file_hash != previous.zip_file_hash {
@@ -147,11 +247,10 @@ async fn update_lambda(
                 .function_name(lambda.name.as_ref())
                 .set_architectures(Some(vec![arch]))
                 .zip_file({
-                    let file = std::fs::File::open(lambda.zip_file_path.as_ref())?;
-                    let bytes: Vec<u8> =
-                        file.bytes().into_iter().try_fold(vec![], |mut acc, byte| {
-                            acc.push(byte?);
-                            anyhow::Ok(acc)
+                    let bytes = crate::rt::read(lambda.zip_file_path.as_ref())
+                        .await
+                        .with_context(|| {
+                            format!("could not read {}", lambda.zip_file_path.as_str())
                         })?;
                     log::debug!("sending {} bytes of code/program", bytes.len());
                     aws_sdk_lambda::primitives::Blob::new(bytes)
@@ -169,7 +268,7 @@ async fn update_lambda(
                 last_update_status != LastUpdateStatus::Failed,
                 "update failed!"
             );
-            await_finalization(&client, lambda).await?;
+            await_finalization(cfg, &client, lambda).await?;
             log::info!("...done")
         }
 
@@ -183,7 +282,7 @@ async fn update_lambda(
             let out = client
                 .update_function_configuration()
                 .function_name(format!("{}:$LATEST", lambda.name.as_ref()))
-                .set_environment(lambda.environment())
+                .set_environment(lambda.environment()?)
                 .set_timeout(lambda.timeout.as_ref().clone())
                 .role(
                     lambda
@@ -205,7 +304,7 @@ async fn update_lambda(
                 last_update_status != LastUpdateStatus::Failed,
                 "update failed!"
             );
-            await_finalization(&client, lambda).await?;
+            await_finalization(cfg, &client, lambda).await?;
             log::info!("...done");
         }
 
@@ -225,14 +324,103 @@ async fn update_lambda(
     Ok(())
 }
 
+/// Update the lambda from its configuration in AWS.
+fn read_configuration(
+    lambda: &mut Lambda,
+    role: Option<String>,
+    handler: Option<String>,
+    timeout: Option<i32>,
+    environment: Option<aws::EnvironmentResponse>,
+    arn: Option<String>,
+) -> anyhow::Result<()> {
+    lambda.role_arn = role.context("missing role")?.into();
+    lambda.handler = handler.context("missing handler")?.into();
+    // AWS reports its own default timeout for lambdas declared without one,
+    // which would otherwise show up as drift.
+    if lambda.timeout.is_some() {
+        lambda.timeout = timeout.into();
+    }
+    lambda.environment = environment.and_then(|env| env.variables).map(|vars| {
+        vars.into_iter()
+            .map(|(k, v)| (k, Remote::from(v)))
+            .collect::<BTreeMap<_, _>>()
+    });
+    lambda.arn = arn.context("missing arn")?.into();
+    // The unqualified configuration is always "$LATEST", so keep the stored
+    // published version.
+    Ok(())
+}
+
+async fn read_lambda(lambda: &mut Lambda, cfg: &Aws, name: &str) -> anyhow::Result<()> {
+    let out = cfg
+        .for_resource(name)
+        .lambda()
+        .get_function_configuration()
+        .function_name(lambda.name.as_str())
+        .send()
+        .await?;
+    read_configuration(
+        lambda,
+        out.role,
+        out.handler,
+        out.timeout,
+        out.environment,
+        out.function_arn,
+    )
+}
+
+/// Read many lambdas with one sweep of `list_functions` per region.
+async fn read_lambdas(lambdas: &mut BTreeMap<String, Lambda>, cfg: &Aws) -> anyhow::Result<()> {
+    for (aws, names) in cfg.group_by_overrides(lambdas.keys()) {
+        let client = aws.lambda();
+        let mut functions = HashMap::new();
+        let mut marker = None;
+        loop {
+            let out = client
+                .list_functions()
+                .set_marker(marker.take())
+                .send()
+                .await?;
+            for function in out.functions.unwrap_or_default() {
+                if let Some(name) = function.function_name.clone() {
+                    functions.insert(name, function);
+                }
+            }
+            marker = out.next_marker;
+            if marker.is_none() {
+                break;
+            }
+        }
+        for name in names {
+            // UNWRAP: safe because the names are keys of the lambdas
+            let lambda = lambdas.get_mut(&name).unwrap();
+            match functions.remove(lambda.name.as_str()) {
+                Some(function) => read_configuration(
+                    lambda,
+                    function.role,
+                    function.handler,
+                    function.timeout,
+                    function.environment,
+                    function.function_arn,
+                )
+                .with_context(|| format!("could not read {name}"))?,
+                None => {
+                    lambdas.remove(&name);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 async fn delete_lambda(
     lambda: &Lambda,
     apply: bool,
-    cfg: &SdkConfig,
-    _: &str,
+    cfg: &Aws,
+    name: &str,
 ) -> anyhow::Result<()> {
     if apply {
-        let client = aws_sdk_lambda::Client::new(cfg);
+        let client = cfg.for_resource(name).lambda();
         let _ = client
             .delete_function()
             .function_name(lambda.name.as_ref())
@@ -243,7 +431,7 @@ async fn delete_lambda(
 }
 
 #[derive(TeleSync, Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
-#[tele(helper = SdkConfig)]
+#[tele(helper = Aws)]
 #[tele(create = create_added_perm, update = update_added_perm, delete = delete_added_perm)]
 pub struct LambdaAddedPermission {
     // The function ARN.
@@ -267,11 +455,11 @@ pub struct LambdaAddedPermission {
 async fn create_added_perm(
     perm: &mut LambdaAddedPermission,
     apply: bool,
-    cfg: &SdkConfig,
+    cfg: &Aws,
     name: &str,
 ) -> anyhow::Result<()> {
     if apply {
-        let client = aws_sdk_lambda::Client::new(cfg);
+        let client = cfg.for_resource(name).lambda();
         let out =
             client
                 .add_permission()
@@ -301,7 +489,7 @@ async fn create_added_perm(
 async fn update_added_perm(
     perm: &mut LambdaAddedPermission,
     apply: bool,
-    cfg: &SdkConfig,
+    cfg: &Aws,
     name: &str,
     previous: &LambdaAddedPermission,
 ) -> anyhow::Result<()> {
@@ -316,11 +504,11 @@ async fn update_added_perm(
 async fn delete_added_perm(
     perm: &LambdaAddedPermission,
     apply: bool,
-    cfg: &SdkConfig,
+    cfg: &Aws,
     name: &str,
 ) -> anyhow::Result<()> {
     if apply {
-        let client = aws_sdk_lambda::Client::new(cfg);
+        let client = cfg.for_resource(name).lambda();
         let _out = client
             .remove_permission()
             .function_name(
@@ -339,3 +527,140 @@ async fn delete_added_perm(
     }
     Ok(())
 }
+
+/// Retentions (in days) that CloudWatch supports.
+#[cfg(feature = "aws-cloudwatchlogs")]
+const RETENTION_DAYS: [i32; 22] = [
+    1, 3, 5, 7, 14, 30, 60, 90, 120, 150, 180, 365, 400, 545, 731, 1096, 1827, 2192, 2557, 2922,
+    3288, 3653,
+];
+
+/// The CloudWatch log group of a lambda, `/aws/lambda/<name>`.
+///
+/// Lambdas create their log group on first invocation, without a retention,
+/// so logs are kept forever. Sync this before the lambda is invoked to keep
+/// its logs for a limited time instead.
+#[cfg(feature = "aws-cloudwatchlogs")]
+#[derive(TeleSync, Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+#[tele(helper = Aws)]
+#[tele(create = create_log_group, update = update_log_group, delete = delete_log_group)]
+#[tele(validate = validate_log_group)]
+pub struct LambdaLogGroup {
+    // Name of the lambda.
+    #[tele(should_recreate)]
+    pub function_name: Local<String>,
+    // Days to keep log events, eg 7 or 30. `None` keeps them forever.
+    pub retention_in_days: Local<Option<i32>>,
+}
+
+#[cfg(feature = "aws-cloudwatchlogs")]
+impl LambdaLogGroup {
+    /// Returns the name of the log group.
+    pub fn log_group_name(&self) -> String {
+        format!("/aws/lambda/{}", self.function_name.as_str())
+    }
+}
+
+#[cfg(feature = "aws-cloudwatchlogs")]
+fn validate_log_group(group: &LambdaLogGroup, _: &str) -> anyhow::Result<()> {
+    anyhow::ensure!(!group.function_name.is_empty(), "function name is empty");
+    if let Some(days) = group.retention_in_days.as_ref() {
+        anyhow::ensure!(
+            RETENTION_DAYS.contains(days),
+            "CloudWatch doesn't support a retention of {days} days - use one of {RETENTION_DAYS:?}"
+        );
+    }
+    Ok(())
+}
+
+#[cfg(feature = "aws-cloudwatchlogs")]
+async fn put_retention(
+    group: &LambdaLogGroup,
+    client: &aws_sdk_cloudwatchlogs::Client,
+) -> anyhow::Result<()> {
+    match group.retention_in_days.as_ref() {
+        Some(days) => {
+            client
+                .put_retention_policy()
+                .log_group_name(group.log_group_name())
+                .retention_in_days(*days)
+                .send()
+                .await?;
+        }
+        None => {
+            client
+                .delete_retention_policy()
+                .log_group_name(group.log_group_name())
+                .send()
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "aws-cloudwatchlogs")]
+async fn create_log_group(
+    group: &mut LambdaLogGroup,
+    apply: bool,
+    cfg: &Aws,
+    name: &str,
+) -> anyhow::Result<()> {
+    if apply {
+        let client = cfg.for_resource(name).cloudwatchlogs();
+        if let Err(e) = client
+            .create_log_group()
+            .log_group_name(group.log_group_name())
+            .send()
+            .await
+        {
+            let e = e.into_service_error();
+            // the lambda was invoked before its log group was declared
+            if !e.is_resource_already_exists_exception() {
+                return Err(e.into());
+            }
+            log::info!("log group {} already exists", group.log_group_name());
+        }
+        put_retention(group, &client).await?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "aws-cloudwatchlogs")]
+async fn update_log_group(
+    group: &mut LambdaLogGroup,
+    apply: bool,
+    cfg: &Aws,
+    name: &str,
+    _previous: &LambdaLogGroup,
+) -> anyhow::Result<()> {
+    if apply {
+        let client = cfg.for_resource(name).cloudwatchlogs();
+        put_retention(group, &client).await?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "aws-cloudwatchlogs")]
+async fn delete_log_group(
+    group: &LambdaLogGroup,
+    apply: bool,
+    cfg: &Aws,
+    name: &str,
+) -> anyhow::Result<()> {
+    if apply {
+        let client = cfg.for_resource(name).cloudwatchlogs();
+        if let Err(e) = client
+            .delete_log_group()
+            .log_group_name(group.log_group_name())
+            .send()
+            .await
+        {
+            let e = e.into_service_error();
+            if !e.is_resource_not_found_exception() {
+                return Err(e.into());
+            }
+            log::info!("log group {} was already deleted", group.log_group_name());
+        }
+    }
+    Ok(())
+}
@@ -1,39 +1,626 @@
 //! Teleform for AWS.
+//!
+//! Each service's resources are behind their own cargo feature, eg
+//! `aws-lambda`, so programs only compile the SDKs of the services they use.
+//! The `aws-full` feature (on by default) enables all of them.
+
+use std::{
+    any::{Any, TypeId},
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 pub use aws_config::SdkConfig;
+use anyhow::Context;
+use aws_config::retry::{RetryConfig, RetryConfigBuilder, RetryMode};
+use aws_credential_types::{
+    provider::{ProvideCredentials, SharedCredentialsProvider},
+    Credentials,
+};
+use aws_types::region::Region;
+use crate::wait::Wait;
+#[cfg(feature = "aws-apigatewayv2")]
 pub mod apigatewayv2;
+#[cfg(feature = "aws-acm")]
 pub mod acm;
+pub mod discover;
+#[cfg(feature = "aws-dynamodb")]
 pub mod dynamodb;
+#[cfg(feature = "aws-iam")]
 pub mod iam;
+#[cfg(feature = "aws-lambda")]
 pub mod lambda;
+#[cfg(feature = "aws-route53")]
 pub mod route53;
+#[cfg(feature = "aws-s3")]
 pub mod s3;
+#[cfg(feature = "aws-sts")]
+pub mod sts;
+#[cfg(feature = "aws-sts")]
+pub use sts::CallerIdentity;
+
+type ClientKey = (TypeId, Option<String>);
+
+/// The AWS provider.
+///
+/// Wraps the AWS `SdkConfig` and caches service clients by service and
+/// region, so they can be shared across resources instead of being
+/// constructed for every action.
+///
+/// Resources use the provider's region unless they are given their own with
+/// [`Aws::set_region_of`], eg for CloudFront certificates, which must be in
+/// us-east-1. Other request settings of a single resource can be changed
+/// with [`Aws::set_overrides_of`].
+///
+/// Services can be pointed at other endpoints with [`Aws::with_endpoint_of`],
+/// and [`localstack`] returns a provider that points every service at a
+/// LocalStack emulator, eg to run integration tests of an IaC program in CI.
+pub struct Aws {
+    config: SdkConfig,
+    clients: Arc<Mutex<HashMap<ClientKey, Box<dyn Any + Send + Sync>>>>,
+    overrides: Arc<Mutex<BTreeMap<String, Overrides>>>,
+    /// Endpoint URLs by service, eg "s3".
+    endpoints: BTreeMap<String, String>,
+    wait: Wait,
+}
 
-/// A wrapper around the AWS `SdkConfig` that provides `AsRef<SdkConfig>`.
-pub struct Aws(pub SdkConfig);
+impl std::fmt::Debug for Aws {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Aws")
+            .field("region", &self.config.region())
+            .finish()
+    }
+}
+
+impl From<SdkConfig> for Aws {
+    fn from(config: SdkConfig) -> Self {
+        Aws::new(config)
+    }
+}
 
 impl AsRef<SdkConfig> for Aws {
     fn as_ref(&self) -> &SdkConfig {
-        &self.0
-    }
-}
-
-pub async fn prune<T: AsRef<SdkConfig>>(
-    store: &mut crate::Store<T>
-) -> anyhow::Result<()> {
-    store.prune::<route53::Record>().await?;
-    store.prune::<apigatewayv2::ApiMapping>().await?;
-    store.prune::<apigatewayv2::Route>().await?;
-    store.prune::<apigatewayv2::Stage>().await?;
-    store.prune::<apigatewayv2::DomainName>().await?;
-    store.prune::<apigatewayv2::Integration>().await?;
-    store.prune::<apigatewayv2::ApiGatewayV2>().await?;
-    store.prune::<dynamodb::Table>().await?;
-    store.prune::<lambda::LambdaAddedPermission>().await?;
-    store.prune::<lambda::Lambda>().await?;
-    store.prune::<s3::Object>().await?;
-    store.prune::<s3::Bucket>().await?;
-    store.prune::<iam::Role>().await?;
-    store.prune::<iam::Policy>().await?;
-    Ok(())
+        &self.config
+    }
+}
+
+impl AsRef<Aws> for Aws {
+    fn as_ref(&self) -> &Aws {
+        self
+    }
+}
+
+impl crate::Provider for Aws {
+    fn name(&self) -> String {
+        match self.config.region() {
+            Some(region) => format!("AWS ({region})"),
+            None => "AWS".to_string(),
+        }
+    }
+
+    /// Checks that a region is configured and that credentials can be loaded.
+    fn healthcheck(&self) -> crate::middleware::BoxFuture<'_> {
+        Box::pin(async move {
+            anyhow::ensure!(self.config.region().is_some(), "no region is configured");
+            let provider = self
+                .config
+                .credentials_provider()
+                .context("no credentials provider is configured")?;
+            provider
+                .provide_credentials()
+                .await
+                .context("could not load credentials")?;
+            Ok(())
+        })
+    }
+
+    /// Returns the region and endpoint URL overridden for the resource, see
+    /// [`Aws::set_overrides_of`].
+    fn settings_of(&self, name: &str) -> BTreeMap<String, String> {
+        // UNWRAP: safe because we never panic while holding the lock
+        let Some(overrides) = self.overrides.lock().unwrap().get(name).cloned() else {
+            return BTreeMap::new();
+        };
+        [
+            ("region", overrides.region),
+            ("endpoint_url", overrides.endpoint_url),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| Some((key.to_string(), value?)))
+        .collect()
+    }
+
+    /// Falls back to the stored region and endpoint URL of a resource whose
+    /// overrides weren't declared this run, so it's pruned and refreshed where
+    /// it lives.
+    fn restore_settings(&self, name: &str, settings: &BTreeMap<String, String>) {
+        if settings.is_empty() {
+            return;
+        }
+        // UNWRAP: safe because we never panic while holding the lock
+        self.overrides
+            .lock()
+            .unwrap()
+            .entry(name.to_string())
+            .or_insert_with(|| Overrides {
+                region: settings.get("region").cloned(),
+                endpoint_url: settings.get("endpoint_url").cloned(),
+                backoff: None,
+            });
+    }
+}
+
+macro_rules! clients {
+    ($($feature:literal: $service:ident => $sdk:ident),* $(,)?) => {
+        impl Aws {
+            $(
+                #[cfg(feature = $feature)]
+                #[doc = concat!("Returns the cached `", stringify!($sdk), "` client.")]
+                pub fn $service(&self) -> $sdk::Client {
+                    self.client(|config| {
+                        $sdk::Client::new(&self.service_config(stringify!($service), config))
+                    })
+                }
+            )*
+        }
+    };
+}
+
+clients! {
+    "aws-acm": acm => aws_sdk_acm,
+    "aws-apigatewayv2": apigatewayv2 => aws_sdk_apigatewayv2,
+    "aws-cloudwatchlogs": cloudwatchlogs => aws_sdk_cloudwatchlogs,
+    "aws-dynamodb": dynamodb => aws_sdk_dynamodb,
+    "aws-iam": iam => aws_sdk_iam,
+    "aws-lambda": lambda => aws_sdk_lambda,
+    "aws-rds": rds => aws_sdk_rds,
+    "aws-route53": route53 => aws_sdk_route53,
+    "aws-secretsmanager": secretsmanager => aws_sdk_secretsmanager,
+    "aws-sts": sts => aws_sdk_sts,
+}
+
+impl Aws {
+    /// Returns the cached `aws_sdk_s3` client.
+    ///
+    /// Buckets are addressed by path instead of by subdomain when S3 has
+    /// another endpoint, as emulators like LocalStack expect.
+    #[cfg(feature = "aws-s3")]
+    pub fn s3(&self) -> aws_sdk_s3::Client {
+        self.client(|config| {
+            let config = self.service_config("s3", config);
+            let path_style = config.endpoint_url().is_some();
+            aws_sdk_s3::Client::from_conf(
+                aws_sdk_s3::config::Builder::from(&config)
+                    .force_path_style(path_style)
+                    .build(),
+            )
+        })
+    }
+}
+
+/// Request settings of a single resource that differ from its provider's,
+/// see [`Aws::set_overrides_of`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Overrides {
+    /// The region of the resource.
+    pub region: Option<String>,
+    /// The endpoint URL of all of the resource's requests, eg of a VPC
+    /// endpoint.
+    ///
+    /// This takes precedence over the endpoints of services set with
+    /// [`Aws::with_endpoint_of`].
+    pub endpoint_url: Option<String>,
+    /// How the resource's throttled requests are retried.
+    pub backoff: Option<Backoff>,
+}
+
+impl Overrides {
+    /// Manage the resource in the given region.
+    pub fn region(mut self, region: impl Into<String>) -> Self {
+        self.region = Some(region.into());
+        self
+    }
+
+    /// Send the resource's requests to the given endpoint URL.
+    pub fn endpoint_url(mut self, url: impl Into<String>) -> Self {
+        self.endpoint_url = Some(url.into());
+        self
+    }
+
+    /// Retry the resource's throttled requests with the given backoff.
+    pub fn backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = Some(backoff);
+        self
+    }
+}
+
+/// How AWS requests are retried when they are throttled.
+///
+/// Throttling errors (eg `ThrottlingException` or `TooManyRequestsException`)
+/// and other transient errors are retried with exponential backoff and jitter,
+/// up to `max_attempts` attempts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    /// Maximum number of attempts, including the first.
+    pub max_attempts: u32,
+    /// Backoff before the first retry, doubled on each following retry.
+    pub initial_backoff: Duration,
+    /// Maximum backoff between retries.
+    pub max_backoff: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff {
+            max_attempts: 10,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(20),
+        }
+    }
+}
+
+impl Backoff {
+    fn retry_config(&self) -> RetryConfig {
+        RetryConfigBuilder::new()
+            .mode(RetryMode::Standard)
+            .max_attempts(self.max_attempts)
+            .initial_backoff(self.initial_backoff)
+            .max_backoff(self.max_backoff)
+            .build()
+    }
+}
+
+/// Returns a builder of a copy of the given config.
+fn to_builder(config: &SdkConfig) -> aws_types::sdk_config::Builder {
+    let mut builder = SdkConfig::builder();
+    builder
+        .set_region(config.region().cloned())
+        .set_endpoint_url(config.endpoint_url().map(String::from))
+        .set_retry_config(config.retry_config().cloned())
+        .set_timeout_config(config.timeout_config().cloned())
+        .set_sleep_impl(config.sleep_impl())
+        .set_credentials_cache(config.credentials_cache().cloned())
+        .set_credentials_provider(config.credentials_provider())
+        .set_app_name(config.app_name().cloned())
+        .set_http_connector(config.http_connector().cloned())
+        .set_use_fips(config.use_fips())
+        .set_use_dual_stack(config.use_dual_stack())
+        .set_time_source(config.time_source());
+    builder
+}
+
+impl Aws {
+    /// Create a provider from the given config, retrying throttled requests
+    /// with the default [`Backoff`].
+    pub fn new(config: SdkConfig) -> Self {
+        Aws {
+            config,
+            clients: Default::default(),
+            overrides: Default::default(),
+            endpoints: Default::default(),
+            wait: Wait::default(),
+        }
+        .with_backoff(Backoff::default())
+    }
+
+    /// Retry throttled requests with the given backoff.
+    pub fn with_backoff(self, backoff: Backoff) -> Self {
+        Aws {
+            config: to_builder(&self.config)
+                .retry_config(backoff.retry_config())
+                .build(),
+            // clients are built from the config, so they can't be reused
+            clients: Default::default(),
+            overrides: self.overrides,
+            endpoints: self.endpoints,
+            wait: self.wait,
+        }
+    }
+
+    /// Send the requests of the given service, eg "s3" or "lambda", to the
+    /// given endpoint URL instead of the service's AWS endpoint.
+    ///
+    /// Services are named as the methods that return their clients, eg
+    /// `Aws::s3`.
+    pub fn with_endpoint_of(mut self, service: impl Into<String>, url: impl Into<String>) -> Self {
+        self.endpoints.insert(service.into(), url.into());
+        // clients are built from the endpoints, so they can't be reused
+        self.clients = Default::default();
+        self
+    }
+
+    /// Returns the config of the given service's clients, with the service's
+    /// endpoint if it has one.
+    fn service_config(&self, service: &str, config: &SdkConfig) -> SdkConfig {
+        match self.endpoints.get(service) {
+            Some(url) => to_builder(config).endpoint_url(url).build(),
+            None => config.clone(),
+        }
+    }
+
+    /// Wait for resources to become ready with the given timeout and poll
+    /// interval.
+    pub fn with_wait(self, wait: Wait) -> Self {
+        Aws { wait, ..self }
+    }
+
+    /// Returns how long to wait for resources to become ready.
+    pub fn wait(&self) -> Wait {
+        self.wait
+    }
+
+    /// Returns a clone of this provider in the given region.
+    ///
+    /// The clone shares this provider's client cache and resource overrides.
+    pub fn with_region(&self, region: impl Into<String>) -> Aws {
+        Aws {
+            config: to_builder(&self.config)
+                .region(Region::new(region.into()))
+                .build(),
+            clients: self.clients.clone(),
+            overrides: self.overrides.clone(),
+            endpoints: self.endpoints.clone(),
+            wait: self.wait,
+        }
+    }
+
+    /// Use the given region for the resource with the given name, instead of
+    /// the provider's region.
+    ///
+    /// Declare this before the resource is synced. The region is stored with
+    /// the resource, so once it's no longer declared it is still pruned and
+    /// refreshed in its region.
+    pub fn set_region_of(&self, name: impl Into<String>, region: impl Into<String>) {
+        // UNWRAP: safe because we never panic while holding the lock
+        self.overrides
+            .lock()
+            .unwrap()
+            .entry(name.into())
+            .or_default()
+            .region = Some(region.into());
+    }
+
+    /// Use the given request settings for the resource with the given name,
+    /// instead of the provider's, eg to reach it through a VPC endpoint.
+    ///
+    /// This replaces any region set with [`Aws::set_region_of`]. Like it,
+    /// declare this before the resource is synced. The region and endpoint URL
+    /// are stored with the resource, but the backoff isn't.
+    pub fn set_overrides_of(&self, name: impl Into<String>, overrides: Overrides) {
+        // UNWRAP: safe because we never panic while holding the lock
+        self.overrides
+            .lock()
+            .unwrap()
+            .insert(name.into(), overrides);
+    }
+
+    /// Returns the provider for the resource with the given name, with the
+    /// resource's region and overrides.
+    pub fn for_resource(&self, name: &str) -> Aws {
+        // UNWRAP: safe because we never panic while holding the lock
+        let overrides = self
+            .overrides
+            .lock()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .unwrap_or_default();
+        let aws = match overrides.region {
+            Some(region) => self.with_region(region),
+            None => Aws {
+                config: self.config.clone(),
+                clients: self.clients.clone(),
+                overrides: self.overrides.clone(),
+                endpoints: self.endpoints.clone(),
+                wait: self.wait,
+            },
+        };
+        if overrides.endpoint_url.is_none() && overrides.backoff.is_none() {
+            return aws;
+        }
+        let mut builder = to_builder(&aws.config);
+        let mut endpoints = aws.endpoints;
+        if let Some(url) = overrides.endpoint_url {
+            builder.set_endpoint_url(Some(url));
+            endpoints.clear();
+        }
+        if let Some(backoff) = overrides.backoff {
+            builder.set_retry_config(Some(backoff.retry_config()));
+        }
+        Aws {
+            config: builder.build(),
+            // clients are built from the resource's own config, so they can't
+            // be shared with other resources
+            clients: Default::default(),
+            overrides: aws.overrides,
+            endpoints,
+            wait: aws.wait,
+        }
+    }
+
+    /// Returns the given resource names grouped by their overrides, with the
+    /// provider of each group, so resources reached the same way can be
+    /// listed with one request.
+    pub(crate) fn group_by_overrides<'a>(
+        &self,
+        names: impl IntoIterator<Item = &'a String>,
+    ) -> Vec<(Aws, Vec<String>)> {
+        let mut groups: Vec<(Overrides, Aws, Vec<String>)> = vec![];
+        for name in names {
+            // UNWRAP: safe because we never panic while holding the lock
+            let overrides = self
+                .overrides
+                .lock()
+                .unwrap()
+                .get(name)
+                .cloned()
+                .unwrap_or_default();
+            match groups.iter_mut().find(|(o, _, _)| *o == overrides) {
+                Some((_, _, names)) => names.push(name.clone()),
+                None => groups.push((overrides, self.for_resource(name), vec![name.clone()])),
+            }
+        }
+        groups
+            .into_iter()
+            .map(|(_, aws, names)| (aws, names))
+            .collect()
+    }
+
+    pub fn config(&self) -> &SdkConfig {
+        &self.config
+    }
+
+    /// Returns the cached client of type `C` for this provider's region,
+    /// creating it with `new` if need be.
+    ///
+    /// Clients are keyed by type (ie by service) and region.
+    pub fn client<C: Clone + Send + Sync + 'static>(
+        &self,
+        new: impl FnOnce(&SdkConfig) -> C,
+    ) -> C {
+        let key = (TypeId::of::<C>(), self.config.region().map(|r| r.to_string()));
+        // UNWRAP: safe because we never panic while holding the lock
+        let mut clients = self.clients.lock().unwrap();
+        let client = clients
+            .entry(key)
+            .or_insert_with(|| Box::new(new(&self.config)));
+        // UNWRAP: safe because clients are keyed by their type
+        client.downcast_ref::<C>().unwrap().clone()
+    }
+}
+
+/// Returns a config that assumes the given role, using the credentials of
+/// `base` to do so.
+///
+/// The assumed role's credentials are refreshed automatically as they expire,
+/// so one program can manage resources in several accounts, eg with
+/// `Aws::new(assume_role(&base, "arn:aws:iam::123456789012:role/deploy", "tele")?)`.
+/// Tag resources with the account they belong to with
+/// [`Store::set_account`](crate::Store::set_account).
+pub fn assume_role(
+    base: &SdkConfig,
+    role_arn: impl Into<String>,
+    session: impl Into<String>,
+) -> anyhow::Result<SdkConfig> {
+    let credentials = base
+        .credentials_provider()
+        .context("the base config has no credentials")?;
+    let mut builder = aws_config::sts::AssumeRoleProvider::builder(role_arn).session_name(session);
+    if let Some(region) = base.region() {
+        builder = builder.region(region.clone());
+    }
+    let provider = builder.build(credentials);
+    Ok(to_builder(base)
+        .credentials_provider(SharedCredentialsProvider::new(provider))
+        .build())
+}
+
+/// The region of [`localstack`] providers.
+pub const LOCALSTACK_REGION: &str = "us-east-1";
+
+/// Returns a provider for the LocalStack emulator (or another emulator of the
+/// AWS APIs) at the given endpoint, eg "http://localhost:4566".
+///
+/// The provider uses LocalStack's test credentials in [`LOCALSTACK_REGION`],
+/// so it never touches a live account:
+///
+/// ```ignore
+/// let aws = tele::aws::localstack("http://localhost:4566").await;
+/// let mut store = Store::new(true, aws, "test/store.json");
+/// ```
+pub async fn localstack(endpoint: impl Into<String>) -> Aws {
+    let credentials = Credentials::new("test", "test", None, None, "localstack");
+    let config = aws_config::from_env()
+        .region(Region::new(LOCALSTACK_REGION))
+        .endpoint_url(endpoint)
+        .credentials_provider(credentials)
+        .load()
+        .await;
+    Aws::new(config)
+}
+
+/// Register all AWS resource types with the store, so any AWS resource can be
+/// deleted with [`Store::destroy_by_id`](crate::Store::destroy_by_id).
+pub fn register<T: AsRef<Aws> + Send + Sync>(store: &mut crate::Store<T>) {
+    #[cfg(feature = "aws-route53")]
+    store.register::<route53::Record>();
+    #[cfg(feature = "aws-apigatewayv2")]
+    store.register::<apigatewayv2::ApiMapping>();
+    #[cfg(feature = "aws-apigatewayv2")]
+    store.register::<apigatewayv2::Route>();
+    #[cfg(feature = "aws-apigatewayv2")]
+    store.register::<apigatewayv2::Stage>();
+    #[cfg(feature = "aws-apigatewayv2")]
+    store.register::<apigatewayv2::DomainName>();
+    #[cfg(feature = "aws-apigatewayv2")]
+    store.register::<apigatewayv2::Integration>();
+    #[cfg(feature = "aws-apigatewayv2")]
+    store.register::<apigatewayv2::ApiGatewayV2>();
+    #[cfg(feature = "aws-dynamodb")]
+    store.register::<dynamodb::Table>();
+    #[cfg(feature = "aws-lambda")]
+    store.register::<lambda::LambdaAddedPermission>();
+    #[cfg(feature = "aws-lambda")]
+    store.register::<lambda::Lambda>();
+    #[cfg(all(feature = "aws-lambda", feature = "aws-cloudwatchlogs"))]
+    store.register::<lambda::LambdaLogGroup>();
+    #[cfg(feature = "aws-s3")]
+    store.register::<s3::Object>();
+    #[cfg(feature = "aws-s3")]
+    store.register::<s3::Bucket>();
+    #[cfg(feature = "aws-iam")]
+    store.register::<iam::Role>();
+    #[cfg(feature = "aws-iam")]
+    store.register::<iam::Policy>();
+    #[cfg(feature = "aws-sts")]
+    store.register::<sts::CallerIdentity>();
+    #[cfg(feature = "aws-acm")]
+    store.register::<acm::CertificateStatus>();
+}
+
+/// Prune all AWS resources that are no longer declared.
+///
+/// Resources are pruned with [`prune_order!`](crate::prune_order), dependents
+/// first, so that dependents recorded with
+/// [`Store::depends_on`](crate::Store::depends_on) are always deleted before
+/// the resources they depend on. Data sources (ie `CallerIdentity` and
+/// `CertificateStatus`) aren't pruned, as they don't own anything.
+pub async fn prune<T: AsRef<Aws>>(store: &mut crate::Store<T>) -> anyhow::Result<()> {
+    crate::prune_order!(
+        store,
+        [
+            #[cfg(feature = "aws-route53")]
+            route53::Record,
+            #[cfg(feature = "aws-apigatewayv2")]
+            apigatewayv2::ApiMapping,
+            #[cfg(feature = "aws-apigatewayv2")]
+            apigatewayv2::Route,
+            #[cfg(feature = "aws-apigatewayv2")]
+            apigatewayv2::Stage,
+            #[cfg(feature = "aws-apigatewayv2")]
+            apigatewayv2::DomainName,
+            #[cfg(feature = "aws-apigatewayv2")]
+            apigatewayv2::Integration,
+            #[cfg(feature = "aws-apigatewayv2")]
+            apigatewayv2::ApiGatewayV2,
+            #[cfg(feature = "aws-dynamodb")]
+            dynamodb::Table,
+            #[cfg(feature = "aws-lambda")]
+            lambda::LambdaAddedPermission,
+            #[cfg(feature = "aws-lambda")]
+            lambda::Lambda,
+            #[cfg(all(feature = "aws-lambda", feature = "aws-cloudwatchlogs"))]
+            lambda::LambdaLogGroup,
+            #[cfg(feature = "aws-s3")]
+            s3::Object,
+            #[cfg(feature = "aws-s3")]
+            s3::Bucket,
+            #[cfg(feature = "aws-iam")]
+            iam::Role,
+            #[cfg(feature = "aws-iam")]
+            iam::Policy,
+        ]
+    )
+    .await
 }
@@ -1,12 +1,11 @@
 //! Route53 infrastructure.
 //! AWS certificate manager infrastructure.
 use anyhow::Context;
-use aws_config::SdkConfig;
 use aws_sdk_route53::types::{
     self as aws, Change, ChangeAction, ChangeBatch, ChangeStatus, ResourceRecord, ResourceRecordSet,
 };
 
-use crate::{self as tele, Local, TeleEither, TeleSync, Remote};
+use crate::{self as tele, aws::Aws, Local, TeleEither, TeleSync, Remote};
 
 // TODO: create a derive macro for TeleEither.
 
@@ -29,6 +28,45 @@ impl TeleEither for AliasTarget {
     }
 }
 
+/// The hosted zone of every CloudFront distribution.
+pub const CLOUDFRONT_HOSTED_ZONE_ID: &str = "Z2FDTNDATAQYW2";
+
+impl AliasTarget {
+    /// Target the API Gateway domain name, using the hosted zone and DNS name
+    /// it was given when created.
+    #[cfg(feature = "aws-apigatewayv2")]
+    pub fn for_apigateway_domain(domain_name: &crate::aws::apigatewayv2::DomainName) -> Self {
+        let config = &domain_name.domain_name_configuration;
+        AliasTarget {
+            hosted_zone_id: config.hosted_zone_id.clone(),
+            dns_name: config.api_gateway_domain_name.clone(),
+            evaluate_target_health: false.into(),
+        }
+    }
+
+    /// Target the CloudFront distribution with the given domain name, eg
+    /// "d111111abcdef8.cloudfront.net".
+    ///
+    /// CloudFront doesn't support health checks, so target health isn't
+    /// evaluated.
+    pub fn for_cloudfront(domain_name: impl Into<Remote<String>>) -> Self {
+        AliasTarget {
+            hosted_zone_id: CLOUDFRONT_HOSTED_ZONE_ID.into(),
+            dns_name: domain_name.into(),
+            evaluate_target_health: false.into(),
+        }
+    }
+
+    /// Target another record in the same hosted zone.
+    pub fn for_record(record: &Record) -> Self {
+        AliasTarget {
+            hosted_zone_id: record.hosted_zone_id.as_str().into(),
+            dns_name: record.record_name.as_str().into(),
+            evaluate_target_health: true.into(),
+        }
+    }
+}
+
 impl From<AliasTarget> for aws::AliasTarget {
     fn from(a: AliasTarget) -> Self {
         aws::AliasTarget::builder()
@@ -40,7 +78,7 @@ impl From<AliasTarget> for aws::AliasTarget {
 }
 
 #[derive(TeleSync, Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
-#[tele(helper = SdkConfig)]
+#[tele(helper = Aws)]
 #[tele(create = create_record, update = update_record, delete = delete_record)]
 pub struct Record {
     pub hosted_zone_id: Local<String>,
@@ -55,11 +93,11 @@ pub struct Record {
 async fn create_record(
     record: &mut Record,
     apply: bool,
-    cfg: &SdkConfig,
-    _name: &str,
+    cfg: &Aws,
+    name: &str,
 ) -> anyhow::Result<()> {
     if apply {
-        let client = aws_sdk_route53::Client::new(cfg);
+        let client = cfg.for_resource(name).route53();
         let out = client
             .change_resource_record_sets()
             .hosted_zone_id(record.hosted_zone_id.as_str())
@@ -101,24 +139,16 @@ async fn create_record(
             )
             .send()
             .await?;
-        let mut info = out.change_info.context("missing change_info")?;
-        log::info!("awaiting record change");
-        let timeout_secs = 60;
-        let start = std::time::Instant::now();
-        while *info.status().context("missing change_info.status")? == ChangeStatus::Pending {
-            if (std::time::Instant::now() - start).as_secs() >= timeout_secs {
-                anyhow::bail!(
-                    "finalization of record creation timed out after {timeout_secs} seconds"
-                )
-            }
-            let out = client
-                .get_change()
-                .id(info.id.context("missing change_info.id")?)
-                .send()
-                .await?;
-            info = out.change_info.context("missing change_info")?;
-        }
-        log::info!("...records in sync");
+        let info = out.change_info.context("missing change_info")?;
+        let id = info.id.as_deref().context("missing change_info.id")?;
+        let client = &client;
+        cfg.wait()
+            .until("record change", move || async move {
+                let out = client.get_change().id(id).send().await?;
+                let info = out.change_info.context("missing change_info")?;
+                Ok(*info.status().context("missing change_info.status")? != ChangeStatus::Pending)
+            })
+            .await?;
     }
     Ok(())
 }
@@ -126,7 +156,7 @@ async fn create_record(
 async fn update_record(
     record: &mut Record,
     apply: bool,
-    cfg: &SdkConfig,
+    cfg: &Aws,
     name: &str,
     _previous: &Record,
 ) -> anyhow::Result<()> {
@@ -137,12 +167,11 @@ async fn update_record(
 async fn delete_record(
     _record: &Record,
     apply: bool,
-    cfg: &SdkConfig,
+    _cfg: &Aws,
     _name: &str,
 ) -> anyhow::Result<()> {
     if apply {
-        let _client = aws_sdk_route53::Client::new(cfg);
-        todo!()
+        return Err(tele::Error::not_implemented::<Record>("delete").into());
     }
     Ok(())
 }
@@ -1,22 +1,163 @@
 //! AWS S3 Bucket infrastructure.
 use anyhow::Context;
-use aws_config::SdkConfig;
-use aws_sdk_lambda::primitives::ByteStream;
+use aws_sdk_s3::primitives::ByteStream;
+use std::collections::BTreeMap;
 
-use crate::{self as tele, Local, TeleSync};
+use crate::{self as tele, aws::Aws, lint, Local, TeleSync};
 
 #[derive(TeleSync, Debug, Clone, serde::Serialize, serde::Deserialize)]
-#[tele(helper = SdkConfig)]
+#[tele(helper = Aws)]
 #[tele(create = create_bucket, update = update_bucket, delete = delete_bucket)]
 pub struct Bucket {
     pub acl: Local<String>,
     pub bucket_name: Local<String>,
+    #[serde(default)]
+    pub versioning: Local<bool>,
+    // Default encryption of new objects.
+    #[serde(default)]
+    pub encryption: Local<Option<Encryption>>,
+    #[serde(default)]
+    pub public_access_block: Local<Option<PublicAccessBlock>>,
+}
+
+/// Returns a lint that warns about buckets with public ACLs, eg
+/// "public-read".
+pub fn public_buckets() -> impl lint::Lint {
+    lint::for_type::<Bucket>("S3001", lint::Severity::Warning, |bucket| {
+        bucket
+            .acl
+            .starts_with("public-")
+            .then(|| format!("bucket ACL is {}", bucket.acl.as_str()))
+    })
+}
+
+/// Default encryption of a bucket's objects.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Encryption {
+    /// Encrypt with S3 managed keys.
+    SseS3,
+    /// Encrypt with KMS, using the given key or the AWS managed key if `None`.
+    SseKms { key_id: Option<String> },
+}
+
+/// Blocks public access to a bucket and its objects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct PublicAccessBlock {
+    pub block_public_acls: bool,
+    pub ignore_public_acls: bool,
+    pub block_public_policy: bool,
+    pub restrict_public_buckets: bool,
+}
+
+impl Default for PublicAccessBlock {
+    /// Block all public access.
+    fn default() -> Self {
+        PublicAccessBlock {
+            block_public_acls: true,
+            ignore_public_acls: true,
+            block_public_policy: true,
+            restrict_public_buckets: true,
+        }
+    }
+}
+
+/// Apply the versioning, encryption and public access block settings of a
+/// bucket, given the bucket's previous settings, if any.
+async fn put_bucket_settings(
+    client: &aws_sdk_s3::Client,
+    bucket: &Bucket,
+    previous: Option<&Bucket>,
+) -> anyhow::Result<()> {
+    use aws_sdk_s3::types as s3;
+
+    let bucket_name = bucket.bucket_name.as_str();
+    // versioning can be suspended but never turned off, so only touch it once
+    // it has been enabled
+    if *bucket.versioning || previous.is_some_and(|p| *p.versioning) {
+        let status = if *bucket.versioning {
+            s3::BucketVersioningStatus::Enabled
+        } else {
+            s3::BucketVersioningStatus::Suspended
+        };
+        client
+            .put_bucket_versioning()
+            .bucket(bucket_name)
+            .versioning_configuration(
+                s3::VersioningConfiguration::builder()
+                    .status(status)
+                    .build(),
+            )
+            .send()
+            .await?;
+    }
+
+    match bucket.encryption.as_ref() {
+        Some(encryption) => {
+            let default = match encryption {
+                Encryption::SseS3 => s3::ServerSideEncryptionByDefault::builder()
+                    .sse_algorithm(s3::ServerSideEncryption::Aes256),
+                Encryption::SseKms { key_id } => s3::ServerSideEncryptionByDefault::builder()
+                    .sse_algorithm(s3::ServerSideEncryption::AwsKms)
+                    .set_kms_master_key_id(key_id.clone()),
+            }
+            .build();
+            client
+                .put_bucket_encryption()
+                .bucket(bucket_name)
+                .server_side_encryption_configuration(
+                    s3::ServerSideEncryptionConfiguration::builder()
+                        .rules(
+                            s3::ServerSideEncryptionRule::builder()
+                                .apply_server_side_encryption_by_default(default)
+                                .build(),
+                        )
+                        .build(),
+                )
+                .send()
+                .await?;
+        }
+        None if previous.is_some_and(|p| p.encryption.is_some()) => {
+            client
+                .delete_bucket_encryption()
+                .bucket(bucket_name)
+                .send()
+                .await?;
+        }
+        None => {}
+    }
+
+    match bucket.public_access_block.as_ref() {
+        Some(block) => {
+            client
+                .put_public_access_block()
+                .bucket(bucket_name)
+                .public_access_block_configuration(
+                    s3::PublicAccessBlockConfiguration::builder()
+                        .block_public_acls(block.block_public_acls)
+                        .ignore_public_acls(block.ignore_public_acls)
+                        .block_public_policy(block.block_public_policy)
+                        .restrict_public_buckets(block.restrict_public_buckets)
+                        .build(),
+                )
+                .send()
+                .await?;
+        }
+        None if previous.is_some_and(|p| p.public_access_block.is_some()) => {
+            client
+                .delete_public_access_block()
+                .bucket(bucket_name)
+                .send()
+                .await?;
+        }
+        None => {}
+    }
+    Ok(())
 }
 
 async fn create_bucket(
     bucket: &mut Bucket,
     apply: bool,
-    cfg: &SdkConfig,
+    cfg: &Aws,
     name: &str,
 ) -> anyhow::Result<()> {
     if bucket.bucket_name.is_empty() {
@@ -25,13 +166,14 @@ async fn create_bucket(
     }
     if apply {
         let acl = aws_sdk_s3::types::BucketCannedAcl::from(bucket.acl.as_str());
-        let client = aws_sdk_s3::Client::new(cfg);
+        let client = cfg.for_resource(name).s3();
         let _bucket = client
             .create_bucket()
             .bucket(bucket.bucket_name.as_str())
             .acl(acl)
             .send()
             .await?;
+        put_bucket_settings(&client, bucket, None).await?;
     }
     Ok(())
 }
@@ -39,9 +181,9 @@ async fn create_bucket(
 async fn update_bucket(
     bucket: &mut Bucket,
     apply: bool,
-    cfg: &SdkConfig,
+    cfg: &Aws,
     name: &str,
-    _: &Bucket,
+    previous: &Bucket,
 ) -> anyhow::Result<()> {
     if bucket.bucket_name.is_empty() {
         log::warn!("bucket was created without a name - using the resource name");
@@ -49,39 +191,38 @@ async fn update_bucket(
     }
     if apply {
         let acl = aws_sdk_s3::types::BucketCannedAcl::from(bucket.acl.as_str());
-        let client = aws_sdk_s3::Client::new(cfg);
-        let _ = client.put_bucket_acl().acl(acl).send().await?;
+        let client = cfg.for_resource(name).s3();
+        if bucket.acl != previous.acl {
+            client
+                .put_bucket_acl()
+                .bucket(bucket.bucket_name.as_str())
+                .acl(acl)
+                .send()
+                .await?;
+        }
+        put_bucket_settings(&client, bucket, Some(previous)).await?;
     }
 
     Ok(())
 }
 
-async fn delete_bucket(
-    bucket: &Bucket,
-    apply: bool,
-    cfg: &SdkConfig,
-    name: &str,
-) -> anyhow::Result<()> {
+async fn delete_bucket(bucket: &Bucket, apply: bool, cfg: &Aws, name: &str) -> anyhow::Result<()> {
     let bucket_name = if bucket.bucket_name.is_empty() {
         name
     } else {
         bucket.bucket_name.as_str()
     };
     if apply {
-        let client = aws_sdk_s3::Client::new(cfg);
+        let client = cfg.for_resource(name).s3();
         client.delete_bucket().bucket(bucket_name).send().await?;
     }
     Ok(())
 }
 
-#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
-pub struct ObjectFile {
-    pub path: std::path::PathBuf,
-    pub hash: String,
-}
+pub type ObjectFile = tele::hash::HashedFile;
 
 #[derive(TeleSync, Debug, Clone, serde::Serialize, serde::Deserialize)]
-#[tele(helper = SdkConfig)]
+#[tele(helper = Aws)]
 #[tele(create = create_object, update = update_object, delete = delete_object)]
 pub struct Object {
     #[tele(should_recreate)]
@@ -92,13 +233,60 @@ pub struct Object {
     pub bucket: Local<String>,
     #[tele(should_recreate)]
     pub body: Local<ObjectFile>,
+    // Detected from the key's extension if `None`.
+    #[serde(default)]
+    pub content_type: Local<Option<String>>,
+    #[serde(default)]
+    pub cache_control: Local<Option<String>>,
+    #[serde(default)]
+    pub content_encoding: Local<Option<String>>,
+    // User-defined metadata.
+    #[serde(default)]
+    pub metadata: Local<BTreeMap<String, String>>,
+}
+
+impl Object {
+    /// Returns the content type of the object, detecting it from the key's
+    /// extension if it isn't set.
+    pub fn content_type(&self) -> String {
+        if let Some(content_type) = self.content_type.as_ref() {
+            return content_type.clone();
+        }
+        let extension = std::path::Path::new(self.key.as_str())
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match extension.as_str() {
+            "html" | "htm" => "text/html",
+            "css" => "text/css",
+            "js" | "mjs" => "text/javascript",
+            "json" | "map" => "application/json",
+            "txt" => "text/plain",
+            "xml" => "application/xml",
+            "svg" => "image/svg+xml",
+            "png" => "image/png",
+            "jpg" | "jpeg" => "image/jpeg",
+            "gif" => "image/gif",
+            "webp" => "image/webp",
+            "ico" => "image/x-icon",
+            "pdf" => "application/pdf",
+            "wasm" => "application/wasm",
+            "woff" => "font/woff",
+            "woff2" => "font/woff2",
+            "ttf" => "font/ttf",
+            "zip" => "application/zip",
+            "gz" => "application/gzip",
+            _ => "application/octet-stream",
+        }
+        .to_string()
+    }
 }
 
 async fn create_object(
     object: &mut Object,
     apply: bool,
-    cfg: &SdkConfig,
-    _: &str,
+    cfg: &Aws,
+    name: &str,
 ) -> anyhow::Result<()> {
     if apply {
         let acl = aws_sdk_s3::types::ObjectCannedAcl::from(object.acl.as_str());
@@ -110,13 +298,23 @@ async fn create_object(
                     object.body.path.display()
                 )
             })?;
-        let client = aws_sdk_s3::Client::new(cfg);
+        let client = cfg.for_resource(name).s3();
         client
             .put_object()
             .bucket(object.bucket.as_str())
             .acl(acl)
             .key(object.key.as_str())
             .body(body)
+            .content_type(object.content_type())
+            .set_cache_control(object.cache_control.as_ref().clone())
+            .set_content_encoding(object.content_encoding.as_ref().clone())
+            .set_metadata(Some(
+                object
+                    .metadata
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect(),
+            ))
             .send()
             .await?;
     }
@@ -124,27 +322,20 @@ async fn create_object(
 }
 
 async fn update_object(
-    _object: &mut Object,
+    object: &mut Object,
     apply: bool,
-    _cfg: &SdkConfig,
-    _name: &str,
+    cfg: &Aws,
+    name: &str,
     _previous: &Object,
 ) -> anyhow::Result<()> {
-    if apply {
-        unreachable!("object should be recreated");
-    }
-
-    Ok(())
+    // changes to the body, key or bucket recreate the object, so only the
+    // headers or metadata changed - upload the object again to replace them
+    create_object(object, apply, cfg, name).await
 }
 
-async fn delete_object(
-    object: &Object,
-    apply: bool,
-    cfg: &SdkConfig,
-    _name: &str,
-) -> anyhow::Result<()> {
+async fn delete_object(object: &Object, apply: bool, cfg: &Aws, name: &str) -> anyhow::Result<()> {
     if apply {
-        let client = aws_sdk_s3::Client::new(cfg);
+        let client = cfg.for_resource(name).s3();
         client
             .delete_object()
             .bucket(object.bucket.as_str())
@@ -154,3 +345,95 @@ async fn delete_object(
     }
     Ok(())
 }
+
+/// A [`StateBackend`](crate::backend::StateBackend) in an S3 bucket, keeping
+/// state files under the given key prefix.
+#[derive(Debug, Clone)]
+pub struct S3Backend {
+    pub client: aws_sdk_s3::Client,
+    pub bucket: String,
+    /// Prefix of the keys of state files, eg "teleform/prod/".
+    pub prefix: String,
+}
+
+impl S3Backend {
+    pub fn new(cfg: &Aws, bucket: impl Into<String>, prefix: impl Into<String>) -> Self {
+        S3Backend {
+            client: cfg.s3(),
+            bucket: bucket.into(),
+            prefix: prefix.into(),
+        }
+    }
+}
+
+impl crate::backend::StateBackend for S3Backend {
+    fn name(&self) -> String {
+        format!("s3://{}/{}", self.bucket, self.prefix)
+    }
+
+    fn get<'a>(&'a self, key: &'a str) -> crate::backend::BackendFuture<'a, Option<Vec<u8>>> {
+        Box::pin(async move {
+            let output = match self
+                .client
+                .get_object()
+                .bucket(&self.bucket)
+                .key(format!("{}{key}", self.prefix))
+                .send()
+                .await
+                .map_err(|e| e.into_service_error())
+            {
+                Ok(output) => output,
+                Err(e) if e.is_no_such_key() => return Ok(None),
+                Err(e) => return Err(e.into()),
+            };
+            let bytes = output.body.collect().await?.into_bytes();
+            Ok(Some(bytes.to_vec()))
+        })
+    }
+
+    fn put<'a>(&'a self, key: &'a str, bytes: Vec<u8>) -> crate::backend::BackendFuture<'a, ()> {
+        Box::pin(async move {
+            self.client
+                .put_object()
+                .bucket(&self.bucket)
+                .key(format!("{}{key}", self.prefix))
+                .body(ByteStream::from(bytes))
+                .send()
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn list(&self) -> crate::backend::BackendFuture<'_, Vec<String>> {
+        Box::pin(async move {
+            let mut keys = vec![];
+            let mut continuation_token = None;
+            loop {
+                let output = self
+                    .client
+                    .list_objects_v2()
+                    .bucket(&self.bucket)
+                    .prefix(&self.prefix)
+                    .set_continuation_token(continuation_token)
+                    .send()
+                    .await?;
+                keys.extend(
+                    output
+                        .contents()
+                        .unwrap_or_default()
+                        .iter()
+                        .filter_map(|object| object.key())
+                        .filter_map(|key| key.strip_prefix(self.prefix.as_str()))
+                        .map(ToString::to_string),
+                );
+                match output.next_continuation_token() {
+                    Some(token) if output.is_truncated() => {
+                        continuation_token = Some(token.to_string())
+                    }
+                    _ => break,
+                }
+            }
+            Ok(keys)
+        })
+    }
+}
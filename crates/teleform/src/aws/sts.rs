@@ -0,0 +1,90 @@
+//! AWS STS data sources.
+use anyhow::Context;
+
+use crate::{self as tele, aws::Aws, Remote, Store, TeleSync};
+
+/// The account, identity and region the provider acts as.
+///
+/// A data source - deleting it does nothing. Syncing it looks up the caller's
+/// identity (even when not applying) when it isn't stored yet, but later syncs
+/// keep the stored identity, so use [`CallerIdentity::sync_current`] to look
+/// it up every run:
+///
+/// ```ignore
+/// let identity = CallerIdentity::sync_current(&mut store, "identity").await?;
+/// let account_id = identity.account_id.maybe_ref().context("unknown account")?;
+/// ```
+#[derive(TeleSync, Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[tele(helper = Aws)]
+#[tele(create = read_identity, update = update_identity, delete = forget_identity)]
+#[tele(read = read_identity_of)]
+pub struct CallerIdentity {
+    pub account_id: Remote<String>,
+    // ARN of the user or role.
+    pub arn: Remote<String>,
+    pub user_id: Remote<String>,
+    pub region: Remote<String>,
+}
+
+impl CallerIdentity {
+    /// Sync the caller identity with the given name, then re-read it, so it
+    /// is current even if the provider's account or region changed since it
+    /// was stored.
+    pub async fn sync_current<C: AsRef<Aws>>(
+        store: &mut Store<C>,
+        name: &str,
+    ) -> anyhow::Result<Self> {
+        store.sync_by_name(name, CallerIdentity::default()).await?;
+        store.refresh::<CallerIdentity>(name).await?;
+        store
+            .stored(name)?
+            .with_context(|| format!("{name} is not stored"))
+    }
+}
+
+async fn read_identity(
+    identity: &mut CallerIdentity,
+    _apply: bool,
+    cfg: &Aws,
+    name: &str,
+) -> anyhow::Result<()> {
+    read_identity_of(identity, cfg, name).await
+}
+
+async fn update_identity(
+    identity: &mut CallerIdentity,
+    _apply: bool,
+    cfg: &Aws,
+    name: &str,
+    _previous: &CallerIdentity,
+) -> anyhow::Result<()> {
+    read_identity_of(identity, cfg, name).await
+}
+
+async fn read_identity_of(
+    identity: &mut CallerIdentity,
+    cfg: &Aws,
+    name: &str,
+) -> anyhow::Result<()> {
+    let cfg = cfg.for_resource(name);
+    let out = cfg.sts().get_caller_identity().send().await?;
+    identity.account_id = out.account.context("missing account")?.into();
+    identity.arn = out.arn.context("missing arn")?.into();
+    identity.user_id = out.user_id.context("missing user id")?.into();
+    identity.region = cfg
+        .config()
+        .region()
+        .context("no AWS region is configured")?
+        .to_string()
+        .into();
+    Ok(())
+}
+
+async fn forget_identity(
+    _: &CallerIdentity,
+    _apply: bool,
+    _cfg: &Aws,
+    _name: &str,
+) -> anyhow::Result<()> {
+    Ok(())
+}
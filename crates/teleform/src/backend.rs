@@ -0,0 +1,280 @@
+//! Remote backends that state is published to, eg an S3 bucket.
+//!
+//! Work locally, then publish the store's state with [`Store::push`] and
+//! fetch the latest published state with [`Store::pull`]. The files of the
+//! state (the store file, its stacks, snapshots, audit log and hash cache)
+//! are kept under the same paths in the backend as in the store's directory.
+//!
+//! The content hashes of the files at the last push or pull are recorded in
+//! a `.{store}.sync.json` file next to the store file, which is how conflicts
+//! are detected: a push fails if the backend changed since the last sync, and
+//! a pull fails if local state changed since the last sync.
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    path::{Path, PathBuf},
+    pin::Pin,
+};
+
+use anyhow::Context;
+
+use crate::{file, Store};
+
+/// The future returned by [`StateBackend`] methods.
+pub type BackendFuture<'a, T> = Pin<Box<dyn Future<Output = anyhow::Result<T>> + Send + 'a>>;
+
+/// A remote store of state files, by key.
+pub trait StateBackend: std::fmt::Debug + Send + Sync {
+    /// A human-readable name of the backend, used in messages.
+    fn name(&self) -> String;
+
+    /// Returns the contents of the file with the given key, if it exists.
+    fn get<'a>(&'a self, key: &'a str) -> BackendFuture<'a, Option<Vec<u8>>>;
+
+    /// Write the file with the given key.
+    fn put<'a>(&'a self, key: &'a str, bytes: Vec<u8>) -> BackendFuture<'a, ()>;
+
+    /// Returns the keys of all files.
+    fn list(&self) -> BackendFuture<'_, Vec<String>>;
+}
+
+/// A backend in a local (or mounted) directory.
+#[derive(Debug, Clone)]
+pub struct LocalBackend {
+    pub dir: PathBuf,
+}
+
+impl LocalBackend {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        LocalBackend { dir: dir.into() }
+    }
+}
+
+impl StateBackend for LocalBackend {
+    fn name(&self) -> String {
+        self.dir.display().to_string()
+    }
+
+    fn get<'a>(&'a self, key: &'a str) -> BackendFuture<'a, Option<Vec<u8>>> {
+        Box::pin(async move {
+            match std::fs::read(self.dir.join(key)) {
+                Ok(bytes) => Ok(Some(bytes)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e.into()),
+            }
+        })
+    }
+
+    fn put<'a>(&'a self, key: &'a str, bytes: Vec<u8>) -> BackendFuture<'a, ()> {
+        Box::pin(async move {
+            let path = self.dir.join(key);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, bytes)?;
+            Ok(())
+        })
+    }
+
+    fn list(&self) -> BackendFuture<'_, Vec<String>> {
+        Box::pin(async move {
+            let mut keys = vec![];
+            if !self.dir.is_dir() {
+                return Ok(keys);
+            }
+            let mut queue = vec![self.dir.clone()];
+            while let Some(next) = queue.pop() {
+                for entry in std::fs::read_dir(&next)? {
+                    let path = entry?.path();
+                    if path.is_dir() {
+                        queue.push(path);
+                    } else {
+                        // UNWRAP: safe because every path we visit is within `dir`
+                        keys.push(key_of(path.strip_prefix(&self.dir).unwrap()));
+                    }
+                }
+            }
+            keys.sort();
+            Ok(keys)
+        })
+    }
+}
+
+/// Returns the backend key of a path relative to the store's directory.
+fn key_of(relative: &Path) -> String {
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+pub(crate) fn stem_of(path: &Path) -> String {
+    path.file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "store".to_string())
+}
+
+/// SHA256 of each state file as of the last push or pull, by key.
+type SyncRecord = BTreeMap<String, String>;
+
+impl<Config> Store<Config> {
+    /// Returns the files that make up this store's state, by path relative to
+    /// the store's directory.
+    pub(crate) fn state_files(&self) -> anyhow::Result<Vec<(String, PathBuf)>> {
+        let dir = self.path.parent().context("store has no directory")?;
+        let stem = stem_of(&self.path);
+        let lock_path = self.lock_path();
+        let key_path = self.secrets_key_path();
+        let mut files = vec![];
+        let mut queue = vec![dir.to_path_buf()];
+        while let Some(next) = queue.pop() {
+            for entry in std::fs::read_dir(&next)? {
+                let path = entry?.path();
+                // UNWRAP: safe because every path we visit is within `dir`
+                let relative = path
+                    .strip_prefix(dir)
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string();
+                let is_state = relative.starts_with(&format!("{stem}."))
+                    || relative == "audit.log"
+                    || relative == crate::hash::CACHE_FILE_NAME;
+                if !is_state || path == lock_path || path == key_path {
+                    continue;
+                }
+                if path.is_dir() {
+                    queue.push(path);
+                } else {
+                    files.push((relative, path));
+                }
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    /// Set the backend that state is pushed to and pulled from.
+    pub fn set_backend(&mut self, backend: impl StateBackend + 'static) {
+        self.backend = Some(Box::new(backend));
+    }
+
+    fn sync_record_path(&self) -> PathBuf {
+        self.path
+            .with_file_name(format!(".{}.sync.json", stem_of(&self.path)))
+    }
+
+    fn read_sync_record(&self) -> anyhow::Result<SyncRecord> {
+        let path = self.sync_record_path();
+        if !path.is_file() {
+            return Ok(Default::default());
+        }
+        serde_json::from_slice(&std::fs::read(&path)?)
+            .with_context(|| format!("could not read {}", path.display()))
+    }
+
+    fn write_sync_record(&self, record: &SyncRecord) -> anyhow::Result<()> {
+        std::fs::write(self.sync_record_path(), serde_json::to_vec_pretty(record)?)?;
+        Ok(())
+    }
+
+    /// Publish this store's state to its backend.
+    ///
+    /// Errs without writing anything if a file in the backend changed since
+    /// the last push or pull - pull first to pick up those changes.
+    pub async fn push(&mut self) -> anyhow::Result<()> {
+        let backend = self.backend.as_ref().context("store has no backend")?;
+        let mut record = self.read_sync_record()?;
+        let mut uploads = vec![];
+        let mut conflicts = vec![];
+        for (relative, path) in self.state_files()? {
+            let key = key_of(Path::new(&relative));
+            let bytes = std::fs::read(&path)?;
+            let local = crate::sha256_hex(&bytes);
+            let remote = backend.get(&key).await?.map(|b| crate::sha256_hex(&b));
+            match remote {
+                Some(remote) if remote == local => {}
+                Some(remote) if record.get(&key) != Some(&remote) => conflicts.push(key.clone()),
+                _ => uploads.push((key.clone(), bytes)),
+            }
+            record.insert(key, local);
+        }
+        anyhow::ensure!(
+            conflicts.is_empty(),
+            "cannot push to {} - it changed since the last sync: {conflicts:?}.\nPull first.",
+            backend.name()
+        );
+        for (key, bytes) in uploads.iter() {
+            log::info!("pushing {key}");
+            backend.put(key, bytes.clone()).await?;
+        }
+        self.write_sync_record(&record)?;
+        log::info!("pushed {} state files to {}", uploads.len(), backend.name());
+        Ok(())
+    }
+
+    /// Fetch the latest state from this store's backend.
+    ///
+    /// Errs without writing anything if a local file changed since the last
+    /// push or pull - push first to publish those changes.
+    pub async fn pull(&mut self) -> anyhow::Result<()> {
+        self.lock()?;
+        let backend = self.backend.as_ref().context("store has no backend")?;
+        let dir = self
+            .path
+            .parent()
+            .context("store has no directory")?
+            .to_path_buf();
+        let mut record = self.read_sync_record()?;
+        let mut downloads = vec![];
+        let mut conflicts = vec![];
+        for key in backend.list().await? {
+            anyhow::ensure!(
+                Path::new(&key)
+                    .components()
+                    .all(|c| matches!(c, std::path::Component::Normal(_))),
+                "refusing to pull {key} - it is outside the store's directory"
+            );
+            let Some(bytes) = backend.get(&key).await? else {
+                continue;
+            };
+            let remote = crate::sha256_hex(&bytes);
+            let path = dir.join(&key);
+            let local = match std::fs::read(&path) {
+                Ok(local) => Some(crate::sha256_hex(&local)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => return Err(e.into()),
+            };
+            match local {
+                Some(local) if local == remote => {}
+                Some(local) if record.get(&key) != Some(&local) => conflicts.push(key.clone()),
+                _ => downloads.push((path, bytes)),
+            }
+            record.insert(key, remote);
+        }
+        anyhow::ensure!(
+            conflicts.is_empty(),
+            "cannot pull from {} - local state changed since the last sync: {conflicts:?}.\nPush \
+             first.",
+            backend.name()
+        );
+        for (path, bytes) in downloads.iter() {
+            log::info!("pulling {}", path.display());
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, bytes)?;
+        }
+        self.write_sync_record(&record)?;
+        if self.path.is_file() {
+            let bytes = std::fs::read(&self.path)?;
+            self.rez = file::decode(&self.path, &bytes)?;
+        }
+        log::info!(
+            "pulled {} state files from {}",
+            downloads.len(),
+            backend.name()
+        );
+        Ok(())
+    }
+}
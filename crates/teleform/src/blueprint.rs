@@ -0,0 +1,128 @@
+//! Reusable, parameterized bundles of resources.
+//!
+//! A [`Blueprint`] declares a group of resources, eg a lambda along with its
+//! role, log group and route, that can be stamped out many times with
+//! different parameters. Each instance is declared with
+//! [`Store::instantiate`] in a [`Scope`] that prefixes the names of the
+//! instance's resources with the name of the instance, so instances never
+//! clash:
+//!
+//! ```ignore
+//! struct Service {
+//!     handler: String,
+//! }
+//!
+//! impl Blueprint<Aws> for Service {
+//!     type Outputs = Lambda;
+//!
+//!     fn declare<'a>(&'a self, scope: &'a mut Scope<'_, Aws>) -> DeclareFuture<'a, Lambda> {
+//!         Box::pin(async move {
+//!             // stored as "api-service-a-role"
+//!             let role = scope.sync("role", Role { ... }).await?;
+//!             // stored as "api-service-a-lambda"
+//!             let lambda = scope.sync("lambda", Lambda { ... }).await?;
+//!             scope.depends_on("lambda", ["role"])?;
+//!             Ok(lambda)
+//!         })
+//!     }
+//! }
+//!
+//! let lambda = store.instantiate("api-service-a", Service { ... }).await?;
+//! ```
+use std::{future::Future, pin::Pin};
+
+use crate::{Store, TeleSync};
+
+/// The future returned by [`Blueprint::declare`].
+pub type DeclareFuture<'a, T> = Pin<Box<dyn Future<Output = anyhow::Result<T>> + Send + 'a>>;
+
+/// A reusable bundle of resources.
+pub trait Blueprint<Config>: Send + Sync {
+    /// The values an instance of the blueprint makes available to the rest of
+    /// the program, eg the ARN of its lambda.
+    type Outputs;
+
+    /// Declare the blueprint's resources in the given scope.
+    fn declare<'a>(&'a self, scope: &'a mut Scope<'_, Config>) -> DeclareFuture<'a, Self::Outputs>;
+}
+
+/// The resources of one instance of a [`Blueprint`].
+///
+/// Names given to the scope are prefixed with the name of the instance, eg
+/// "role" in the "api-service-a" instance is stored as
+/// "api-service-a-role".
+pub struct Scope<'s, Config> {
+    store: &'s mut Store<Config>,
+    prefix: String,
+}
+
+impl<'s, Config> Scope<'s, Config> {
+    /// Returns the name of the instance.
+    pub fn name(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Returns the stored name of the resource with the given name in this
+    /// scope.
+    pub fn id(&self, name: &str) -> String {
+        format!("{}-{name}", self.prefix)
+    }
+
+    /// Returns the store, eg to refer to resources outside of the instance.
+    pub fn store(&mut self) -> &mut Store<Config> {
+        self.store
+    }
+
+    /// Synchronize a resource of the instance, see [`Store::sync_by_name`].
+    pub async fn sync<Data>(&mut self, name: &str, data: Data) -> anyhow::Result<Data>
+    where
+        Config: AsRef<<Data as TeleSync>::Provider> + Send + Sync,
+        Data: std::any::Any + TeleSync + Clone,
+    {
+        let id = self.id(name);
+        self.store.sync_by_name(id, data).await
+    }
+
+    /// Record that a resource of the instance depends on others of the
+    /// instance, see [`Store::depends_on_by_name`].
+    pub fn depends_on<'a>(
+        &mut self,
+        name: &str,
+        on: impl IntoIterator<Item = &'a str>,
+    ) -> anyhow::Result<()> {
+        let on = on.into_iter().map(|dep| self.id(dep)).collect::<Vec<_>>();
+        let id = self.id(name);
+        self.store.depends_on_by_name(&id, on)
+    }
+
+    /// Declare a nested instance of a blueprint, whose names are prefixed
+    /// with the stored name of `name` in this scope.
+    pub async fn instantiate<B: Blueprint<Config>>(
+        &mut self,
+        name: &str,
+        blueprint: B,
+    ) -> anyhow::Result<B::Outputs> {
+        let id = self.id(name);
+        self.store.instantiate(id, blueprint).await
+    }
+}
+
+impl<Config> Store<Config> {
+    /// Declare an instance of a blueprint with the given name.
+    ///
+    /// The names of the instance's resources are prefixed with the name of the
+    /// instance, see [`Scope`].
+    pub async fn instantiate<B: Blueprint<Config>>(
+        &mut self,
+        name: impl Into<String>,
+        blueprint: B,
+    ) -> anyhow::Result<B::Outputs> {
+        let prefix = name.into();
+        log::debug!("instantiating {prefix}");
+        let mut scope = Scope {
+            store: self,
+            prefix,
+        };
+        blueprint.declare(&mut scope).await
+    }
+}
@@ -0,0 +1,93 @@
+//! Guardrails on the number of destructive changes a run may make.
+//!
+//! A store with a [`ChangeBudget`] errs with [`Error::ChangeBudgetExceeded`]
+//! before it deletes or updates more resources than the budget allows, unless
+//! the budget is explicitly overridden. This protects against eg. a mis-set
+//! store path planning the deletion of all of production.
+use crate::{Action, Error, Store};
+
+/// The maximum number of destructive changes a run may make.
+///
+/// `None` means unlimited.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ChangeBudget {
+    /// Maximum number of deletions, including those of recreations and prunes.
+    pub max_destroys: Option<usize>,
+    /// Maximum number of updates.
+    pub max_updates: Option<usize>,
+}
+
+/// Tracks changes against the budget.
+#[derive(Debug, Default)]
+pub(crate) struct Budget {
+    limit: Option<ChangeBudget>,
+    overridden: bool,
+    destroys: usize,
+    updates: usize,
+}
+
+impl Budget {
+    /// Returns a budget with the same limit and override, that nothing was
+    /// spent from yet.
+    pub(crate) fn fresh(&self) -> Self {
+        Budget {
+            limit: self.limit,
+            overridden: self.overridden,
+            ..Default::default()
+        }
+    }
+
+    fn check(&self, action: Action, count: usize) -> Result<(), Error> {
+        let Some(limit) = self.limit.filter(|_| !self.overridden) else {
+            return Ok(());
+        };
+        let max = match action {
+            Action::Delete => limit.max_destroys,
+            Action::Update => limit.max_updates,
+            _ => None,
+        };
+        match max {
+            Some(max) if count > max => Err(Error::ChangeBudgetExceeded { action, max }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Count one action against the budget, erring if that exceeds it.
+    pub(crate) fn spend(&mut self, action: Action) -> Result<(), Error> {
+        let count = match action {
+            Action::Delete => &mut self.destroys,
+            Action::Update => &mut self.updates,
+            _ => return Ok(()),
+        };
+        *count += 1;
+        let count = *count;
+        self.check(action, count)
+    }
+}
+
+impl<Config> Store<Config> {
+    /// Limit the number of destructive changes this store may make.
+    pub fn with_change_budget(mut self, budget: ChangeBudget) -> Self {
+        self.budget.limit = Some(budget);
+        self
+    }
+
+    /// Allow (or disallow) changes beyond the change budget.
+    pub fn override_change_budget(&mut self, overridden: bool) {
+        if overridden {
+            log::warn!("ignoring the change budget");
+        }
+        self.budget.overridden = overridden;
+    }
+
+    /// Check that pruning the pending prunes would stay within the change
+    /// budget.
+    ///
+    /// Call this before pruning, so that nothing is deleted by a run that
+    /// would exceed its budget.
+    pub fn check_change_budget(&self) -> anyhow::Result<()> {
+        let destroys = self.budget.destroys + self.get_prunes().len();
+        self.budget.check(Action::Delete, destroys)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+//! Human-readable changelog of applies.
+//!
+//! When a store has a changelog (see [`Store::set_changelog`]), each apply can
+//! append an entry listing the resources it created, updated and destroyed,
+//! with field diffs. A path ending in `.md` (eg `CHANGELOG.infra.md`) gets a
+//! markdown section per entry, any other path gets one JSON line per entry.
+use std::{collections::BTreeMap, io::Write, path::Path};
+
+use crate::{diff::Change, Action, ApplyReport, Store};
+
+/// One apply's worth of changes.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ChangelogEntry {
+    /// Seconds since the UNIX epoch.
+    pub timestamp: u64,
+    pub created: Vec<String>,
+    /// Resources that were deleted and created again, with field diffs.
+    pub recreated: BTreeMap<String, Vec<Change>>,
+    /// Resources that were updated, with field diffs.
+    pub updated: BTreeMap<String, Vec<Change>>,
+    pub destroyed: Vec<String>,
+}
+
+impl ChangelogEntry {
+    /// Create an entry from the actions taken in a report.
+    pub fn from_report(report: &ApplyReport) -> Self {
+        let named = |action: Action| {
+            report
+                .actions
+                .iter()
+                .filter(move |a| a.action == action)
+                .map(|a| a.name.clone())
+        };
+        let created = named(Action::Create).collect::<Vec<_>>();
+        let destroyed = named(Action::Delete).collect::<Vec<_>>();
+        let changes_of = |name: &String| (name.clone(), report.changes(name).to_vec());
+        ChangelogEntry {
+            timestamp: crate::now_secs(),
+            created: created
+                .iter()
+                .filter(|name| !destroyed.contains(name))
+                .cloned()
+                .collect(),
+            recreated: created
+                .iter()
+                .filter(|name| destroyed.contains(name))
+                .map(changes_of)
+                .collect(),
+            updated: named(Action::Update).map(|n| changes_of(&n)).collect(),
+            destroyed: destroyed
+                .iter()
+                .filter(|name| !created.contains(name))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Returns whether nothing changed.
+    pub fn is_empty(&self) -> bool {
+        self.created.is_empty()
+            && self.recreated.is_empty()
+            && self.updated.is_empty()
+            && self.destroyed.is_empty()
+    }
+
+    /// Render the entry as a markdown section.
+    pub fn to_markdown(&self) -> String {
+        let mut lines = vec![format!("## Apply at {}", self.timestamp), String::new()];
+        for name in self.created.iter() {
+            lines.push(format!("- created `{name}`"));
+        }
+        for (verb, resources) in [("recreated", &self.recreated), ("updated", &self.updated)] {
+            for (name, changes) in resources.iter() {
+                lines.push(format!("- {verb} `{name}`"));
+                if !changes.is_empty() {
+                    lines.push("  ```diff".to_string());
+                    lines.extend(
+                        crate::diff::render(changes, false)
+                            .lines()
+                            .map(|line| format!("  {line}")),
+                    );
+                    lines.push("  ```".to_string());
+                }
+            }
+        }
+        for name in self.destroyed.iter() {
+            lines.push(format!("- destroyed `{name}`"));
+        }
+        lines.push(String::new());
+        lines.join("\n")
+    }
+
+    /// Append this entry to the changelog at the given path.
+    pub fn append(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        if path.extension().is_some_and(|ext| ext == "md") {
+            writeln!(file, "{}", self.to_markdown())?;
+        } else {
+            writeln!(file, "{}", serde_json::to_string(self)?)?;
+        }
+        Ok(())
+    }
+}
+
+impl<Config> Store<Config> {
+    /// Returns the path of the changelog, if any.
+    pub fn changelog(&self) -> Option<&Path> {
+        self.runner.changelog.as_deref()
+    }
+
+    /// Set the path of the changelog, or `None` to disable it (the default).
+    pub fn set_changelog(&mut self, path: Option<std::path::PathBuf>) {
+        self.runner.changelog = path;
+    }
+
+    /// Append an entry for the changes applied so far to the changelog.
+    ///
+    /// Does nothing if there is no changelog, if not applying or if nothing
+    /// changed. Call this at the end of an apply.
+    pub fn write_changelog(&self) -> anyhow::Result<()> {
+        let Some(path) = self.changelog().filter(|_| self.apply) else {
+            return Ok(());
+        };
+        let entry = ChangelogEntry::from_report(self.report());
+        if !entry.is_empty() {
+            log::debug!("appending to changelog {}", path.display());
+            entry.append(path)?;
+        }
+        Ok(())
+    }
+}
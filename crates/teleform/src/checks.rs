@@ -0,0 +1,99 @@
+//! Health checks that run after the resources they check.
+//!
+//! A check is part of an apply's success criteria, eg "the API's endpoint
+//! actually serves 200". Declare it after the resources it depends on, with
+//! remote values from those resources:
+//!
+//! ```ignore
+//! store
+//!     .check("api-health", HttpCheck::new(stage.invoke_url.clone()))
+//!     .await?;
+//! ```
+use std::time::Duration;
+
+use crate::{Remote, Store};
+
+/// How long to wait between attempts of a failing check.
+pub const RETRY_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Checks that a URL serves the expected status.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpCheck {
+    /// The URL to `GET`, often the remote value of another resource.
+    pub url: Remote<String>,
+    /// The status the URL is expected to serve.
+    pub expected_status: u16,
+    /// Timeout of each request.
+    pub timeout: Duration,
+    /// How many times a failing check is retried.
+    pub retries: u32,
+}
+
+impl HttpCheck {
+    /// Check that the given URL serves 200, retrying 5 times.
+    pub fn new(url: impl Into<Remote<String>>) -> Self {
+        HttpCheck {
+            url: url.into(),
+            expected_status: 200,
+            timeout: Duration::from_secs(10),
+            retries: 5,
+        }
+    }
+
+    /// Run the check once.
+    async fn attempt(&self, client: &reqwest::Client, url: &str) -> anyhow::Result<()> {
+        let response = client.get(url).timeout(self.timeout).send().await?;
+        let status = response.status().as_u16();
+        anyhow::ensure!(
+            status == self.expected_status,
+            "{url} served {status}, expected {}",
+            self.expected_status
+        );
+        Ok(())
+    }
+
+    /// Run the check, retrying it until it passes or runs out of retries.
+    pub async fn run(&self) -> anyhow::Result<()> {
+        let url = self
+            .url
+            .maybe_ref()
+            .ok_or_else(|| anyhow::anyhow!("the url of the check is unknown"))?;
+        let client = reqwest::Client::new();
+        let mut retries = 0;
+        loop {
+            match self.attempt(&client, url).await {
+                Ok(()) => return Ok(()),
+                Err(e) if retries < self.retries => {
+                    retries += 1;
+                    log::debug!("check of {url} failed, retrying: {e}");
+                    crate::rt::sleep(RETRY_INTERVAL).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl<Config> Store<Config> {
+    /// Run the check with the given name.
+    ///
+    /// Checks only run when applying, failing the apply if they fail. When
+    /// not applying the check is only logged, as its URL may not be known yet.
+    pub async fn check(&mut self, name: &str, check: HttpCheck) -> anyhow::Result<()> {
+        if !self.apply || !self.is_targeted(name) {
+            log::info!(
+                "check {name}: GET {} serves {}",
+                check.url,
+                check.expected_status
+            );
+            return Ok(());
+        }
+        log::info!("checking {name}");
+        check
+            .run()
+            .await
+            .map_err(|e| e.context(format!("check {name} failed")))?;
+        log::info!("...{name} passed");
+        Ok(())
+    }
+}
@@ -0,0 +1,562 @@
+//! Utility functions for adding teleform IaC to your command line program.
+//!
+//! This is useful for setting up your infrastructure as a subcommand of xtask,
+//! for example.
+//!
+//! For a batteries-included `main` see [`run`].
+
+use std::{future::Future, io::Read, pin::Pin};
+
+use anyhow::Context;
+
+use crate::{
+    budget::ChangeBudget,
+    notify::{Notification, Notifier},
+    Action, Provider, Store,
+};
+
+/// A function that runs against the store, eg to declare infrastructure or prune it.
+pub type StoreFn<Cfg> = Box<
+    dyn for<'a> FnOnce(
+            &'a mut Store<Cfg>,
+        ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>>
+        + Send,
+>;
+
+/// A function that declares the infrastructure.
+///
+/// It may run more than once, eg to check the plan against the change budget
+/// before applying it.
+pub type InfrastructureFn<Cfg> = Box<
+    dyn for<'a> FnMut(
+            &'a mut Store<Cfg>,
+        ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>>
+        + Send,
+>;
+
+/// A function that imports a resource created outside of teleform into the store.
+///
+/// It is given the resource name and its JSON representation.
+pub type ImportFn<Cfg> =
+    Box<dyn FnOnce(&mut Store<Cfg>, &str, serde_json::Value) -> anyhow::Result<()> + Send>;
+
+/// Configuration of a teleform command line program.
+///
+/// See [`run`].
+pub struct AppConfig<Cfg> {
+    /// Name of the program, used in the usage message.
+    pub name: String,
+    /// Path to the store file. Defaults to `default_store.json` in the cargo
+    /// workspace directory.
+    pub store_path: Option<std::path::PathBuf>,
+    /// The provider configuration.
+    pub cfg: Cfg,
+    /// Declares the infrastructure.
+    pub infrastructure: InfrastructureFn<Cfg>,
+    /// Prunes unused resources, in order. Eg [`crate::aws::prune`].
+    pub prune: StoreFn<Cfg>,
+    /// Imports existing resources.
+    pub import: Option<ImportFn<Cfg>>,
+    /// Limits destructive changes, see [`crate::budget`].
+    ///
+    /// With a budget, `apply` plans first, and errs without changing anything
+    /// if the plan exceeds the budget.
+    pub change_budget: Option<ChangeBudget>,
+    /// Path of the changelog each apply appends to, see [`crate::changelog`].
+    pub changelog: Option<std::path::PathBuf>,
+    /// Whether to keep the state in git, see [`crate::git`].
+    pub git: bool,
+    /// Told the result of every apply, see [`crate::notify`].
+    pub notifiers: Vec<Box<dyn Notifier>>,
+}
+
+impl<Cfg: 'static> AppConfig<Cfg> {
+    pub fn new(
+        name: impl Into<String>,
+        cfg: Cfg,
+        infrastructure: impl for<'a> FnMut(
+                &'a mut Store<Cfg>,
+            )
+                -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>>
+            + Send
+            + 'static,
+        prune: impl for<'a> FnOnce(
+                &'a mut Store<Cfg>,
+            )
+                -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>>
+            + Send
+            + 'static,
+    ) -> Self {
+        AppConfig {
+            name: name.into(),
+            store_path: None,
+            cfg,
+            infrastructure: Box::new(infrastructure),
+            prune: Box::new(prune),
+            import: None,
+            change_budget: None,
+            changelog: None,
+            git: false,
+            notifiers: vec![],
+        }
+    }
+
+    pub fn with_store_path(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.store_path = Some(path.into());
+        self
+    }
+
+    pub fn with_import(
+        mut self,
+        import: impl FnOnce(&mut Store<Cfg>, &str, serde_json::Value) -> anyhow::Result<()>
+            + Send
+            + 'static,
+    ) -> Self {
+        self.import = Some(Box::new(import));
+        self
+    }
+
+    pub fn with_change_budget(mut self, budget: ChangeBudget) -> Self {
+        self.change_budget = Some(budget);
+        self
+    }
+
+    pub fn with_changelog(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.changelog = Some(path.into());
+        self
+    }
+
+    /// Refuse to apply while the state has uncommitted changes, and commit
+    /// the state after each successful apply.
+    pub fn with_git(mut self) -> Self {
+        self.git = true;
+        self
+    }
+
+    pub fn with_notifier(mut self, notifier: impl Notifier + 'static) -> Self {
+        self.notifiers.push(Box::new(notifier));
+        self
+    }
+}
+
+/// A subcommand of a teleform command line program.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Show what would change, without changing anything.
+    Plan,
+    /// Create, update and prune resources.
+    Apply,
+    /// Delete all resources.
+    Destroy,
+    /// Print the dependency graph of the planned resources in the DOT
+    /// language, see [`Store::graph_dot`].
+    Graph,
+    /// Import the resource with the given name from a JSON file.
+    Import {
+        name: String,
+        path: std::path::PathBuf,
+    },
+}
+
+/// Parsed command line arguments of a teleform command line program.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Args {
+    pub command: Command,
+    /// Only change these resources.
+    pub targets: Vec<String>,
+    /// Skip confirmation of destructive actions.
+    ///
+    /// Without this, `apply` prompts before each recreation or deletion.
+    pub auto_approve: bool,
+    /// Override the store path.
+    pub store_path: Option<std::path::PathBuf>,
+    /// Allow changes beyond the program's change budget.
+    pub ignore_change_budget: bool,
+}
+
+impl Args {
+    pub fn usage(name: &str) -> String {
+        format!(
+            "usage: {name} [plan|apply|destroy|graph|import <name> <file.json>] \
+             [--target <name>]... [--auto-approve] [--store <path>] [--ignore-change-budget]"
+        )
+    }
+
+    /// Parse arguments, not including the program name.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> anyhow::Result<Self> {
+        let mut command = None;
+        let mut targets = vec![];
+        let mut auto_approve = false;
+        let mut store_path = None;
+        let mut ignore_change_budget = false;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--target" => targets.push(args.next().context("--target requires a name")?),
+                "--auto-approve" => auto_approve = true,
+                "--ignore-change-budget" => ignore_change_budget = true,
+                "--store" => {
+                    store_path = Some(args.next().context("--store requires a path")?.into())
+                }
+                "plan" if command.is_none() => command = Some(Command::Plan),
+                "apply" if command.is_none() => command = Some(Command::Apply),
+                "destroy" if command.is_none() => command = Some(Command::Destroy),
+                "graph" if command.is_none() => command = Some(Command::Graph),
+                "import" if command.is_none() => {
+                    let name = args.next().context("import requires a resource name")?;
+                    let path = args.next().context("import requires a json file")?;
+                    command = Some(Command::Import {
+                        name,
+                        path: path.into(),
+                    });
+                }
+                other => anyhow::bail!("unexpected argument '{other}'"),
+            }
+        }
+        Ok(Args {
+            command: command.unwrap_or(Command::Plan),
+            targets,
+            auto_approve,
+            store_path,
+            ignore_change_budget,
+        })
+    }
+}
+
+/// Counts of the changes made (or planned) by a run.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RunSummary {
+    pub created: usize,
+    pub updated: usize,
+    pub destroyed: usize,
+}
+
+impl std::fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} created, {} updated, {} destroyed",
+            self.created, self.updated, self.destroyed
+        )
+    }
+}
+
+impl RunSummary {
+    /// Summarize the actions taken by the given store.
+    ///
+    /// When planning, resources that would be pruned count as destroyed,
+    /// whether or not the plan ran the prune.
+    pub fn from_store<Cfg>(store: &Store<Cfg>) -> Self {
+        let report = store.report();
+        let mut destroyed = report.count(Action::Delete);
+        if !store.apply {
+            destroyed += store.get_prunes().len();
+        }
+        RunSummary {
+            created: report.count(Action::Create),
+            updated: report.count(Action::Update),
+            destroyed,
+        }
+    }
+
+    /// Returns whether there were no changes.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// The outcome of a run, mapped to conventional exit codes.
+#[derive(Debug)]
+pub enum RunOutcome {
+    /// There was nothing to change - exit code `0`.
+    NoChanges,
+    /// Changes were planned but not applied - exit code `2`.
+    ChangesPlanned(RunSummary),
+    /// Changes were applied - exit code `0`.
+    Applied(RunSummary),
+    /// The run failed - exit code `1`.
+    Error(anyhow::Error),
+}
+
+impl RunOutcome {
+    /// Determine the outcome of a run from its result.
+    pub fn new(apply: bool, result: anyhow::Result<RunSummary>) -> Self {
+        match result {
+            Ok(summary) if summary.is_empty() => RunOutcome::NoChanges,
+            Ok(summary) if apply => RunOutcome::Applied(summary),
+            Ok(summary) => RunOutcome::ChangesPlanned(summary),
+            Err(e) => RunOutcome::Error(e),
+        }
+    }
+
+    pub fn exit_code(&self) -> std::process::ExitCode {
+        match self {
+            RunOutcome::NoChanges | RunOutcome::Applied(_) => std::process::ExitCode::SUCCESS,
+            RunOutcome::ChangesPlanned(_) => std::process::ExitCode::from(2),
+            RunOutcome::Error(_) => std::process::ExitCode::FAILURE,
+        }
+    }
+
+    /// Print the summary line of the run, or its error.
+    pub fn print(&self) {
+        match self {
+            RunOutcome::NoChanges => println!("No changes. {}", RunSummary::default()),
+            RunOutcome::ChangesPlanned(summary) => println!("Plan: {summary}"),
+            RunOutcome::Applied(summary) => println!("Apply complete: {summary}"),
+            RunOutcome::Error(e) => log::error!("{e:#}"),
+        }
+    }
+}
+
+/// Run a teleform command line program.
+///
+/// Parses the process' arguments and runs the `plan`, `apply`, `destroy`,
+/// `graph` or `import` subcommand, then prints a summary of the changes
+/// (except after `graph`, whose output is the graph alone). Returns the
+/// process exit code - see [`RunOutcome`].
+///
+/// The outcome of `apply` and `destroy` is sent to the program's notifiers.
+pub async fn run<Cfg: Provider + 'static>(mut app: AppConfig<Cfg>) -> std::process::ExitCode {
+    let name = app.name.clone();
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            log::error!("{e}");
+            eprintln!("{}", Args::usage(&name));
+            return std::process::ExitCode::FAILURE;
+        }
+    };
+    let apply = !matches!(args.command, Command::Plan | Command::Graph);
+    let graph = args.command == Command::Graph;
+    let notify = matches!(args.command, Command::Apply | Command::Destroy);
+    let notifiers = std::mem::take(&mut app.notifiers);
+    let outcome = RunOutcome::new(apply, run_with_args(app, args).await);
+    if !graph || matches!(outcome, RunOutcome::Error(_)) {
+        outcome.print();
+    }
+    if notify {
+        let notification = Notification::new(&name, &outcome);
+        crate::notify::notify_all(&notifiers, &notification).await;
+    }
+    outcome.exit_code()
+}
+
+/// Run a teleform command line program with already parsed arguments.
+///
+/// The provider is initialized and checked before the program's
+/// infrastructure runs, and shut down after (see [`Provider`]). `plan` prints
+/// why each resource would be recreated or updated.
+pub async fn run_with_args<Cfg: Provider + 'static>(
+    app: AppConfig<Cfg>,
+    args: Args,
+) -> anyhow::Result<RunSummary> {
+    let store_path = match args.store_path.or(app.store_path) {
+        Some(path) => path,
+        None => find_workspace_dir()?.join("default_store.json"),
+    };
+    let apply = !matches!(args.command, Command::Plan | Command::Graph);
+    let mut cfg = app.cfg;
+    let mut infrastructure = app.infrastructure;
+    let mut checked = false;
+    if let (Command::Apply, Some(budget), false) =
+        (&args.command, app.change_budget, args.ignore_change_budget)
+    {
+        // plan first, so that nothing is applied by a run that exceeds the budget
+        let mut plan = create_store(&store_path, cfg, false)?.with_change_budget(budget);
+        plan.init().await?;
+        plan.healthcheck().await?;
+        checked = true;
+        if !args.targets.is_empty() {
+            plan.set_targets(Some(args.targets.clone()));
+        }
+        infrastructure(&mut plan).await?;
+        plan.check_change_budget()?;
+        cfg = plan.cfg;
+    }
+    let mut store = open_store(&store_path, cfg, apply)?;
+    if app.git && store.apply {
+        store.ensure_state_committed()?;
+    }
+    if apply {
+        let ts = store.snapshot()?;
+        log::debug!("snapshotted store at {ts}");
+    }
+    if !checked {
+        store.init().await?;
+        store.healthcheck().await?;
+    }
+    if let Some(budget) = app.change_budget {
+        store = store.with_change_budget(budget);
+    }
+    store.override_change_budget(args.ignore_change_budget);
+    store.set_changelog(app.changelog);
+    if !args.targets.is_empty() {
+        store.set_targets(Some(args.targets));
+    }
+    if args.command == Command::Apply && !args.auto_approve {
+        store.set_interactive(true);
+    }
+
+    match args.command {
+        Command::Import { name, path } => {
+            let import = app
+                .import
+                .context("this program does not support importing")?;
+            let value: serde_json::Value = serde_json::from_reader(std::fs::File::open(&path)?)
+                .with_context(|| format!("could not read {}", path.display()))?;
+            import(&mut store, &name, value)?;
+            store.save(store.path())?;
+        }
+        Command::Plan | Command::Apply => {
+            infrastructure(&mut store).await?;
+            if !store.apply {
+                let reasons = store.report().render_reasons();
+                if !reasons.is_empty() {
+                    println!("{reasons}");
+                }
+            }
+            if display_prunes(&store) {
+                store.check_change_budget()?;
+                (app.prune)(&mut store).await?;
+            }
+        }
+        Command::Graph => {
+            infrastructure(&mut store).await?;
+            println!("{}", store.graph_dot());
+        }
+        Command::Destroy => {
+            if display_prunes(&store) {
+                store.check_change_budget()?;
+                if !args.auto_approve && !confirm_delete("destroy")? {
+                    return Ok(RunSummary::default());
+                }
+                (app.prune)(&mut store).await?;
+            }
+        }
+    }
+
+    store.shutdown().await?;
+
+    if store.apply {
+        let remaining_prunes = store.get_prunes();
+        anyhow::ensure!(
+            remaining_prunes.is_empty(),
+            "unhandled prunes {remaining_prunes:#?}"
+        );
+        store.write_changelog()?;
+        if app.git {
+            store.commit_state()?;
+        }
+    }
+    Ok(RunSummary::from_store(&store))
+}
+
+/// Attempt to find the cargo workspace directory by searching for Cargo.lock,
+/// recursively up the filesystem tree.
+pub fn find_workspace_dir() -> anyhow::Result<std::path::PathBuf> {
+    let mut workspace_dir = std::env::current_dir()?;
+    while !workspace_dir.join("Cargo.lock").is_file() {
+        let parent = workspace_dir
+            .parent()
+            .context("hit root dir while looking for the workspace!")?;
+        workspace_dir = parent.to_path_buf();
+    }
+    Ok(workspace_dir)
+}
+
+/// Create the infrastructure store, backed by a local file.
+///
+/// If `apply` is `true` the store is snapshotted before any changes are made.
+/// See [`Store::restore_snapshot`].
+pub fn create_store<Cfg>(
+    store_path: impl AsRef<std::path::Path>,
+    cfg: Cfg,
+    apply: bool,
+) -> anyhow::Result<Store<Cfg>> {
+    let store = open_store(store_path, cfg, apply)?;
+    if apply {
+        let ts = store.snapshot()?;
+        log::debug!("snapshotted store at {ts}");
+    }
+    Ok(store)
+}
+
+/// Open the store file at the given path, or create a new empty store.
+fn open_store<Cfg>(
+    store_path: impl AsRef<std::path::Path>,
+    cfg: Cfg,
+    apply: bool,
+) -> anyhow::Result<Store<Cfg>> {
+    let store: Store<Cfg> = if store_path.as_ref().exists() {
+        log::debug!(
+            "found store file - exists at: {}",
+            store_path.as_ref().display()
+        );
+        Store::from_path(apply, cfg, store_path.as_ref()).context("cannot open store json")?
+    } else {
+        log::debug!("creating a new empty store");
+        Store::new(apply, cfg, store_path.as_ref())
+    };
+    Ok(store)
+}
+
+/// Display any resources that should be pruned.
+/// Return whether there are resources to prune.
+pub fn display_prunes<Cfg>(store: &Store<Cfg>) -> bool {
+    let unused_resources = store.get_prunes();
+    if !unused_resources.is_empty() {
+        log::warn!(
+            "will prune unused resources: \n{}",
+            unused_resources
+                .iter()
+                .map(|n| format!("  {n}"))
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+        true
+    } else {
+        log::info!("no resources to prune/delete");
+        false
+    }
+}
+
+/// Get confirmation to perform a delete command
+pub fn confirm_delete(confirmation_message: &str) -> anyhow::Result<bool> {
+    log::info!("confirm deletion by entering \"{confirmation_message}\":");
+    let mut typing = String::new();
+    std::io::stdin().read_line(&mut typing)?;
+    if typing != format!("{confirmation_message}\n") {
+        log::info!("got \"{typing}\", so we're bailing");
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// Returns the sha256 digest of the file at the given path *if it exists*.
+/// If the file does _not_ exist it returns `Ok(None)`.
+pub fn sha256_digest(path: impl AsRef<std::path::Path>) -> anyhow::Result<Option<String>> {
+    log::trace!("determining sha256 of {}", path.as_ref().display());
+    if !path.as_ref().exists() {
+        return Ok(None);
+    }
+
+    fn sha256<R: Read>(mut reader: R) -> anyhow::Result<ring::digest::Digest> {
+        let mut context = ring::digest::Context::new(&ring::digest::SHA256);
+        let mut buffer = [0; 1024];
+
+        loop {
+            let count = reader.read(&mut buffer)?;
+            if count == 0 {
+                break;
+            }
+            context.update(&buffer[..count]);
+        }
+
+        Ok(context.finish())
+    }
+
+    let input = std::fs::File::open(path)?;
+    let reader = std::io::BufReader::new(input);
+    let digest = sha256(reader)?;
+    Ok(Some(data_encoding::HEXUPPER.encode(digest.as_ref())))
+}
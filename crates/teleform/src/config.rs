@@ -0,0 +1,340 @@
+//! Operational configuration, read from a `tele.toml` file.
+//!
+//! ```toml
+//! # relative to the directory containing tele.toml
+//! store = "store.json"
+//!
+//! [backend]
+//! kind = "local"
+//! dir = "/mnt/shared/tele-state"
+//!
+//! [default_tags]
+//! project = "my-project"
+//!
+//! [environments.staging]
+//! store = "staging.json"
+//! default_tags = { env = "staging" }
+//! vars = { DOMAIN = "staging.example.com" }
+//! ```
+//!
+//! See [`Store::from_config`].
+//!
+//! Values in local definitions can also be filled in from the environment (or
+//! another source of secrets) at plan time with [`Store::resolve_env`] and
+//! [`FromEnv`].
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::Context;
+use ring::{
+    hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+
+use crate::{backend::LocalBackend, Store, TeleEither};
+
+/// The name of the environment variable used to pick an environment from
+/// the config file.
+pub const ENVIRONMENT_VAR: &str = "TELE_ENV";
+
+/// The name of the environment variable holding the secrets key, in hex.
+///
+/// When set it takes precedence over the store's
+/// [secrets key file](Store::secrets_key_path).
+pub const SECRETS_KEY_VAR: &str = "TELE_SECRETS_KEY";
+
+/// Settings for a single environment, eg "staging" or "production".
+///
+/// Any settings given here override the top-level settings.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Environment {
+    pub store: Option<PathBuf>,
+    pub backend: Option<BTreeMap<String, serde_json::Value>>,
+    pub default_tags: BTreeMap<String, String>,
+    /// Arbitrary variables made available to the IaC definitions.
+    pub vars: BTreeMap<String, String>,
+}
+
+/// The contents of a `tele.toml` file.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// Path of the store file, relative to the config file.
+    pub store: Option<PathBuf>,
+    /// Settings of the backend the store's state is published to, see
+    /// [`crate::backend`].
+    ///
+    /// A backend of kind "local" is set up by [`Store::from_config`], with its
+    /// `dir` relative to the config file. Other kinds are left to the program,
+    /// which can read them from [`Store::settings`] and call
+    /// [`Store::set_backend`].
+    pub backend: BTreeMap<String, serde_json::Value>,
+    /// Tags of every synced resource, see [`Store::tag`].
+    pub default_tags: BTreeMap<String, String>,
+    /// Arbitrary variables made available to the IaC definitions.
+    pub vars: BTreeMap<String, String>,
+    /// Named environments.
+    pub environments: BTreeMap<String, Environment>,
+    /// The name of the selected environment, if any.
+    #[serde(skip)]
+    pub environment: Option<String>,
+}
+
+impl Settings {
+    /// Read settings from the given `tele.toml` file.
+    pub fn from_path(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("could not read {}", path.display()))?;
+        toml::from_str(&contents).with_context(|| format!("could not parse {}", path.display()))
+    }
+
+    /// Returns these settings with the named environment's settings applied.
+    pub fn for_environment(&self, name: &str) -> anyhow::Result<Self> {
+        let env = self
+            .environments
+            .get(name)
+            .with_context(|| format!("no environment '{name}' in config"))?;
+        let mut settings = self.clone();
+        if let Some(store) = env.store.as_ref() {
+            settings.store = Some(store.clone());
+        }
+        if let Some(backend) = env.backend.as_ref() {
+            settings.backend = backend.clone();
+        }
+        settings.default_tags.extend(env.default_tags.clone());
+        settings.vars.extend(env.vars.clone());
+        settings.environment = Some(name.to_string());
+        Ok(settings)
+    }
+}
+
+impl<Config> Store<Config> {
+    /// Create a store from a `tele.toml` config file.
+    ///
+    /// If the `TELE_ENV` environment variable is set, that environment's
+    /// settings are applied. The store file is read if it exists, otherwise
+    /// an empty store is created that will be saved there. A local backend
+    /// in the settings is set as the store's backend.
+    pub fn from_config(apply: bool, cfg: Config, path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let mut settings = Settings::from_path(path)?;
+        if let Ok(env) = std::env::var(ENVIRONMENT_VAR) {
+            settings = settings.for_environment(&env)?;
+        }
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let store_path = dir.join(
+            settings
+                .store
+                .clone()
+                .unwrap_or_else(|| PathBuf::from("store.json")),
+        );
+        let mut store = if store_path.exists() {
+            Store::from_path(apply, cfg, &store_path)?
+        } else {
+            Store::new(apply, cfg, &store_path)
+        };
+        match settings.backend.get("kind").and_then(|kind| kind.as_str()) {
+            Some("local") => {
+                let backend_dir = settings
+                    .backend
+                    .get("dir")
+                    .and_then(|dir| dir.as_str())
+                    .context("the local backend has no dir")?;
+                store.set_backend(LocalBackend::new(dir.join(backend_dir)));
+            }
+            Some(kind) => log::debug!("leaving the {kind} backend to the program"),
+            None => {}
+        }
+        store.settings = settings;
+        Ok(store)
+    }
+
+    /// Returns the operational settings of this store.
+    ///
+    /// These are the defaults unless the store was created with
+    /// [`Store::from_config`].
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+}
+
+/// A source of secrets and other values that should be kept out of source.
+pub trait Secrets {
+    /// Returns the value with the given key, if any.
+    fn secret(&self, key: &str) -> anyhow::Result<Option<String>>;
+}
+
+/// Secrets read from environment variables.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Env;
+
+impl Secrets for Env {
+    fn secret(&self, key: &str) -> anyhow::Result<Option<String>> {
+        match std::env::var(key) {
+            Ok(value) => Ok(Some(value)),
+            Err(std::env::VarError::NotPresent) => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("could not read env var {key}")),
+        }
+    }
+}
+
+/// Secrets read from the `vars` of the settings.
+impl Secrets for Settings {
+    fn secret(&self, key: &str) -> anyhow::Result<Option<String>> {
+        Ok(self.vars.get(key).cloned())
+    }
+}
+
+/// A value read from the environment (or another [`Secrets`] source) at plan
+/// time.
+///
+/// Only the variable name and a hash of the value are serialized, so the value
+/// never ends up in the store file, but changes to it still cause updates. The
+/// hash is keyed by the store's [secrets key](Store::secrets_key_path), so it
+/// can't be checked against guesses of the value without the key.
+///
+/// Values deserialized from the store are unresolved.
+#[derive(Debug, Clone)]
+pub struct FromEnv<T> {
+    pub var: String,
+    pub hash: String,
+    value: Option<T>,
+}
+
+impl<T> PartialEq for FromEnv<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.var == other.var && self.hash == other.hash
+    }
+}
+
+/// The `TeleEither` implementation for `FromEnv` always picks itself, as
+/// only the local definition has a resolved value.
+impl<T> TeleEither for FromEnv<T> {}
+
+impl<T> FromEnv<T> {
+    /// Returns the resolved value.
+    ///
+    /// Errs if the value was deserialized from the store instead of being
+    /// resolved.
+    pub fn value(&self) -> anyhow::Result<&T> {
+        self.value
+            .as_ref()
+            .with_context(|| format!("value of {} was not resolved", self.var))
+    }
+}
+
+impl<T> serde::Serialize for FromEnv<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut s = serializer.serialize_struct("FromEnv", 2)?;
+        s.serialize_field("var", &self.var)?;
+        s.serialize_field("hash", &self.hash)?;
+        s.end()
+    }
+}
+
+impl<'de, T> serde::Deserialize<'de> for FromEnv<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(serde::Deserialize)]
+        struct Stored {
+            var: String,
+            hash: String,
+        }
+        let Stored { var, hash } = Stored::deserialize(deserializer)?;
+        Ok(FromEnv {
+            var,
+            hash,
+            value: None,
+        })
+    }
+}
+
+impl<Config> Store<Config> {
+    /// Returns the path of the file holding the key that the hashes of
+    /// [`FromEnv`] values are keyed with, next to the store file.
+    ///
+    /// The key is created the first time a value is resolved while applying.
+    /// It is a secret, so it is left out of the state that is pushed, archived
+    /// and committed. Share it by copying the file, or with the
+    /// [`SECRETS_KEY_VAR`] environment variable - without it every value
+    /// resolved by the store looks changed.
+    pub fn secrets_key_path(&self) -> PathBuf {
+        self.path
+            .with_file_name(format!("{}.key", crate::backend::stem_of(&self.path)))
+    }
+
+    /// Returns the key of the hashes of [`FromEnv`] values, creating it if
+    /// need be.
+    ///
+    /// While planning a missing key isn't written, and a throwaway key is used
+    /// instead.
+    fn secrets_key(&self) -> anyhow::Result<hmac::Key> {
+        let path = self.secrets_key_path();
+        let bytes = if let Ok(hex) = std::env::var(SECRETS_KEY_VAR) {
+            data_encoding::HEXLOWER_PERMISSIVE
+                .decode(hex.trim().as_bytes())
+                .with_context(|| format!("could not decode {SECRETS_KEY_VAR}"))?
+        } else if path.is_file() {
+            let contents = std::fs::read(&path)
+                .with_context(|| format!("could not read {}", path.display()))?;
+            data_encoding::HEXLOWER
+                .decode(contents.trim_ascii())
+                .with_context(|| format!("could not decode {}", path.display()))?
+        } else {
+            let mut bytes = [0u8; 32];
+            SystemRandom::new()
+                .fill(&mut bytes)
+                .map_err(|_| anyhow::anyhow!("could not generate a secrets key"))?;
+            if self.apply {
+                std::fs::write(&path, data_encoding::HEXLOWER.encode(&bytes))
+                    .with_context(|| format!("could not write {}", path.display()))?;
+            } else {
+                log::debug!(
+                    "no secrets key at {} - resolved values look changed",
+                    path.display()
+                );
+            }
+            bytes.to_vec()
+        };
+        Ok(hmac::Key::new(hmac::HMAC_SHA256, &bytes))
+    }
+
+    /// Resolve the value of the given environment variable.
+    pub fn resolve_env<T>(&self, var: impl Into<String>) -> anyhow::Result<FromEnv<T>>
+    where
+        T: FromStr,
+        T::Err: std::fmt::Display,
+    {
+        self.resolve_secret(&Env, var)
+    }
+
+    /// Resolve the value with the given key from a source of secrets.
+    pub fn resolve_secret<T>(
+        &self,
+        secrets: &impl Secrets,
+        var: impl Into<String>,
+    ) -> anyhow::Result<FromEnv<T>>
+    where
+        T: FromStr,
+        T::Err: std::fmt::Display,
+    {
+        let var = var.into();
+        let raw = secrets
+            .secret(&var)?
+            .with_context(|| format!("{var} is not set"))?;
+        let value = raw
+            .parse::<T>()
+            .map_err(|e| anyhow::anyhow!("could not parse {var}: {e}"))?;
+        let tag = hmac::sign(&self.secrets_key()?, raw.as_bytes());
+        Ok(FromEnv {
+            hash: data_encoding::HEXUPPER.encode(tag.as_ref()),
+            var,
+            value: Some(value),
+        })
+    }
+}
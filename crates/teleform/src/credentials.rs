@@ -0,0 +1,55 @@
+//! Surviving credentials that expire in the middle of long applies.
+//!
+//! Some resources (eg RDS instances or CloudFront distributions) take long
+//! enough to apply that short-lived session credentials expire halfway. When
+//! an action fails because its credentials expired, the store calls its
+//! refresh hook (see [`Store::on_expired_credentials`]) and retries the action.
+use std::{future::Future, pin::Pin};
+
+use crate::Store;
+
+/// How many times an action is retried after its credentials expired.
+pub const MAX_RETRIES: u32 = 3;
+
+/// Error codes providers report for expired credentials.
+const EXPIRED_CODES: &[&str] = &[
+    "ExpiredToken",
+    "ExpiredTokenException",
+    "RequestExpired",
+    "InvalidClientTokenId",
+    "security token included in the request is expired",
+];
+
+/// Returns whether the error is caused by expired credentials.
+pub fn is_expired(error: &anyhow::Error) -> bool {
+    // SDK errors only mention their code in their debug representation
+    let debug = format!("{error:?}");
+    EXPIRED_CODES.iter().any(|code| debug.contains(code))
+}
+
+type RefreshFuture = Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send>>;
+
+/// A hook that refreshes credentials.
+pub(crate) struct Refresh(pub(crate) Box<dyn FnMut() -> RefreshFuture + Send + Sync>);
+
+impl std::fmt::Debug for Refresh {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Refresh")
+    }
+}
+
+impl<Config> Store<Config> {
+    /// Call the given hook to refresh credentials when an action fails
+    /// because its credentials expired, before the action is retried.
+    ///
+    /// Actions are retried up to [`MAX_RETRIES`] times, with or without a
+    /// hook - providers that cache credentials (like AWS's) fetch new ones on
+    /// their own once the old ones expire.
+    pub fn on_expired_credentials<F, Fut>(&mut self, mut f: F)
+    where
+        F: FnMut() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        self.runner.refresh = Some(Refresh(Box::new(move || Box::pin(f()))));
+    }
+}
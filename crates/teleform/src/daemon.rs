@@ -0,0 +1,164 @@
+//! Running teleform as a long-lived daemon that watches for drift.
+//!
+//! Every interval the daemon rebuilds the store, refreshes its resources from
+//! the provider and plans the infrastructure against them. Any planned change
+//! is drift - infrastructure that no longer matches its IaC definition. Drift
+//! is logged and sent to the daemon's notifiers, and can optionally be
+//! remediated by applying.
+//!
+//! Resources are refreshed with [`Store::refresh_ids`], so the store factory
+//! must register their types, eg with [`crate::aws::register`]. Resources that
+//! can't be read (see [`TeleSync::read`](crate::TeleSync::read)) are planned
+//! against their stored values.
+use std::{collections::BTreeMap, future::Future, pin::Pin, time::Duration};
+
+use crate::{
+    cli::RunSummary,
+    notify::{Notification, Notifier},
+    Error, Store,
+};
+
+/// Declares the infrastructure, every time the daemon checks for drift.
+pub use crate::cli::InfrastructureFn;
+
+/// Builds the store, given whether it should apply.
+pub type StoreFactory<Cfg> = Box<dyn FnMut(bool) -> anyhow::Result<Store<Cfg>> + Send>;
+
+/// A daemon that periodically checks for drift.
+pub struct Daemon<Cfg> {
+    /// Name of the program, used in notifications.
+    pub name: String,
+    /// How long to wait between checks.
+    pub interval: Duration,
+    pub store: StoreFactory<Cfg>,
+    pub infrastructure: InfrastructureFn<Cfg>,
+    /// Whether to apply when drift is detected.
+    ///
+    /// Remediation never prunes.
+    pub remediate: bool,
+    /// Told about drift, remediations and failed checks.
+    pub notifiers: Vec<Box<dyn Notifier>>,
+}
+
+impl<Cfg: 'static> Daemon<Cfg> {
+    pub fn new(
+        name: impl Into<String>,
+        interval: Duration,
+        store: impl FnMut(bool) -> anyhow::Result<Store<Cfg>> + Send + 'static,
+        infrastructure: impl for<'a> FnMut(
+                &'a mut Store<Cfg>,
+            )
+                -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>>
+            + Send
+            + 'static,
+    ) -> Self {
+        Daemon {
+            name: name.into(),
+            interval,
+            store: Box::new(store),
+            infrastructure: Box::new(infrastructure),
+            remediate: false,
+            notifiers: vec![],
+        }
+    }
+
+    /// Apply whenever drift is detected.
+    pub fn with_remediation(mut self) -> Self {
+        self.remediate = true;
+        self
+    }
+
+    pub fn with_notifier(mut self, notifier: impl Notifier + 'static) -> Self {
+        self.notifiers.push(Box::new(notifier));
+        self
+    }
+
+    /// Plan the infrastructure against the refreshed store, returning the
+    /// drift.
+    pub async fn detect_drift(&mut self) -> anyhow::Result<RunSummary> {
+        let mut store = (self.store)(false)?;
+        refresh_all(&mut store).await;
+        (self.infrastructure)(&mut store).await?;
+        Ok(RunSummary::from_store(&store))
+    }
+
+    /// Apply the infrastructure against the refreshed store, returning what
+    /// changed.
+    pub async fn remediate(&mut self) -> anyhow::Result<RunSummary> {
+        let mut store = (self.store)(true)?;
+        refresh_all(&mut store).await;
+        (self.infrastructure)(&mut store).await?;
+        Ok(RunSummary::from_store(&store))
+    }
+
+    /// Check for drift once, remediating it if need be.
+    pub async fn tick(&mut self) -> anyhow::Result<()> {
+        let drift = self.detect_drift().await?;
+        if drift.is_empty() {
+            log::info!("no drift detected");
+            return Ok(());
+        }
+        log::warn!("drift detected: {drift}");
+        let notification = Notification::Drifted {
+            program: self.name.clone(),
+            summary: drift,
+        };
+        crate::notify::notify_all(&self.notifiers, &notification).await;
+        if self.remediate {
+            let summary = self.remediate().await?;
+            log::info!("remediated drift: {summary}");
+            let notification = Notification::Applied {
+                program: self.name.clone(),
+                summary,
+            };
+            crate::notify::notify_all(&self.notifiers, &notification).await;
+        }
+        Ok(())
+    }
+
+    /// Check for drift every interval, forever.
+    ///
+    /// Failed checks are logged and sent to the notifiers, but don't stop the
+    /// daemon.
+    pub async fn run(mut self) {
+        loop {
+            if let Err(e) = self.tick().await {
+                log::error!("drift check failed: {e:#}");
+                let notification = Notification::Failed {
+                    program: self.name.clone(),
+                    error: format!("{e:#}"),
+                };
+                crate::notify::notify_all(&self.notifiers, &notification).await;
+            }
+            crate::rt::sleep(self.interval).await;
+        }
+    }
+}
+
+/// Refresh every resource of the store that can be read, so drift in the
+/// provider shows up as changes to the infrastructure.
+///
+/// Resources are refreshed by type, so each type's
+/// [`read_many`](crate::TeleSync::read_many) sweeps all of its resources at
+/// once.
+async fn refresh_all<Cfg>(store: &mut Store<Cfg>) {
+    let mut by_type = BTreeMap::<Option<String>, Vec<String>>::new();
+    for (name, rez) in store.rez.iter() {
+        by_type
+            .entry(rez.type_is.clone())
+            .or_default()
+            .push(name.clone());
+    }
+    for names in by_type.into_values() {
+        let all = names.join(", ");
+        match store.refresh_ids(&names).await {
+            Ok(()) => {}
+            Err(e) if matches!(e.downcast_ref(), Some(Error::NotImplemented { .. })) => {
+                log::debug!("{all} can't be read, planning against the stored values");
+            }
+            Err(e) => {
+                log::warn!("could not refresh {all}, planning against the stored values: {e:#}");
+            }
+        }
+    }
+}
@@ -0,0 +1,139 @@
+//! Transcripts of runs, for debugging.
+//!
+//! A store with a transcript (see [`Store::set_transcript`]) appends one JSON
+//! line per action to it - including failed actions and actions that were
+//! only planned - in the order they ran. The transcript is a shareable
+//! artifact of exactly what a run did, which [`replay`] prints in full:
+//!
+//! ```ignore
+//! store.set_transcript(Some("transcript.jsonl".into()));
+//! // ...later, or on another machine
+//! let transcript = tele::debug::Transcript::read("transcript.jsonl")?;
+//! println!("{}", tele::debug::replay(&transcript));
+//! ```
+use std::{io::Write, path::PathBuf, time::Duration};
+
+use crate::{Action, Store};
+
+/// One action of a transcript.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TranscriptEntry {
+    /// Position of the action in its run, from 1.
+    pub seq: usize,
+    /// Seconds since the UNIX epoch.
+    pub timestamp: u64,
+    /// Name of the resource.
+    pub resource: String,
+    /// Rust type name of the resource.
+    pub type_is: String,
+    pub action: Action,
+    /// Whether the store was applying.
+    pub apply: bool,
+    pub duration: Duration,
+    pub retries: u32,
+    /// SHA256 of the resource's data before the action.
+    pub input_hash: String,
+    /// SHA256 of the resource's data after the action, if it succeeded.
+    pub output_hash: Option<String>,
+    /// The error the action failed with, if any.
+    pub error: Option<String>,
+}
+
+/// The actions of a run, in order.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Transcript {
+    pub entries: Vec<TranscriptEntry>,
+}
+
+impl Transcript {
+    /// Read the transcript at the given path.
+    pub fn read(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        let entries = contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?;
+        Ok(Transcript { entries })
+    }
+
+    /// Returns the failed actions.
+    pub fn failures(&self) -> impl Iterator<Item = &TranscriptEntry> {
+        self.entries.iter().filter(|entry| entry.error.is_some())
+    }
+}
+
+/// Render a transcript for humans, one action after another with its full
+/// detail.
+pub fn replay(transcript: &Transcript) -> String {
+    let mut lines = vec![];
+    for entry in transcript.entries.iter() {
+        lines.push(format!(
+            "#{} {} {} {} ({}) at {}, took {:?}{}{}",
+            entry.seq,
+            if entry.apply { "applied" } else { "planned" },
+            entry.action,
+            entry.resource,
+            entry.type_is,
+            entry.timestamp,
+            entry.duration,
+            if entry.retries > 0 {
+                format!(", {} retries", entry.retries)
+            } else {
+                String::new()
+            },
+            if entry.error.is_some() {
+                " - FAILED"
+            } else {
+                ""
+            }
+        ));
+        lines.push(format!("    input:  {}", entry.input_hash));
+        if let Some(output_hash) = entry.output_hash.as_ref() {
+            lines.push(format!("    output: {output_hash}"));
+        }
+        if let Some(error) = entry.error.as_ref() {
+            lines.extend(error.lines().map(|line| format!("    error:  {line}")));
+        }
+    }
+    let failures = transcript.failures().count();
+    lines.push(format!(
+        "{} actions, {failures} failed",
+        transcript.entries.len()
+    ));
+    lines.join("\n")
+}
+
+/// Appends the actions of a run to a transcript file.
+#[derive(Debug)]
+pub(crate) struct Recorder {
+    path: PathBuf,
+    seq: usize,
+}
+
+impl Recorder {
+    /// Start a transcript at the given path, replacing any previous one.
+    pub(crate) fn new(path: PathBuf) -> anyhow::Result<Self> {
+        std::fs::write(&path, "")?;
+        Ok(Recorder { path, seq: 0 })
+    }
+
+    pub(crate) fn record(&mut self, mut entry: TranscriptEntry) -> anyhow::Result<()> {
+        self.seq += 1;
+        entry.seq = self.seq;
+        let mut file = std::fs::OpenOptions::new().append(true).open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+        Ok(())
+    }
+}
+
+impl<Config> Store<Config> {
+    /// Record a transcript of this run's actions at the given path, or `None`
+    /// to stop recording.
+    ///
+    /// The file is replaced.
+    pub fn set_transcript(&mut self, path: Option<PathBuf>) -> anyhow::Result<()> {
+        self.runner.transcript = path.map(Recorder::new).transpose()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,201 @@
+//! Dependencies between resources.
+//!
+//! Dependencies are recorded in the store file with [`Store::depends_on`], or
+//! automatically from the [values a resource refers
+//! to](crate::TeleSync::referenced_values), and are used to delete dependents
+//! before the resources they depend on.
+use std::collections::BTreeSet;
+
+use anyhow::Context;
+
+use crate::{AnyResourceId, ResourceId, Store};
+
+impl<Config> Store<Config> {
+    /// Record that the resource with the given handle depends on others, eg a
+    /// lambda depends on its role:
+    ///
+    /// ```ignore
+    /// store.depends_on(&lambda, &[&role, &table])?;
+    /// ```
+    ///
+    /// This replaces any previously recorded dependencies of the resource.
+    pub fn depends_on<Data>(
+        &mut self,
+        id: &ResourceId<Data>,
+        on: &[&dyn AnyResourceId],
+    ) -> anyhow::Result<()> {
+        self.depends_on_by_name(id.as_str(), on.iter().map(|dep| dep.name()))
+    }
+
+    /// Record that the resource with the given name depends on others, given
+    /// by name.
+    ///
+    /// Prefer [`Store::depends_on`], whose handles can't be misspelled.
+    pub fn depends_on_by_name(
+        &mut self,
+        name: &str,
+        on: impl IntoIterator<Item = impl Into<String>>,
+    ) -> anyhow::Result<()> {
+        let Some(rez) = self.rez.get_mut(name) else {
+            log::debug!("not recording dependencies of {name} - it is not in the store");
+            return Ok(());
+        };
+        rez.depends_on = on.into_iter().map(Into::into).collect();
+        if self.apply {
+            self.save(&self.path)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the names of the stored resources, other than the one with the
+    /// given name, with a field equal to one of the given values.
+    pub(crate) fn resources_with_values(
+        &self,
+        name: &str,
+        values: &[serde_json::Value],
+    ) -> BTreeSet<String> {
+        if values.is_empty() {
+            return BTreeSet::new();
+        }
+        self.rez
+            .iter()
+            .filter(|(other, rez)| {
+                *other != name
+                    && match &rez.data {
+                        serde_json::Value::Object(fields) => {
+                            fields.values().any(|value| values.contains(value))
+                        }
+                        _ => false,
+                    }
+            })
+            .map(|(other, _)| other.clone())
+            .collect()
+    }
+
+    /// Returns the names of the resources that depend on the resource with the
+    /// given name, or refer to it.
+    pub fn dependents(&self, name: &str) -> Vec<String> {
+        self.rez
+            .iter()
+            .filter(|(_, rez)| rez.depends_on.contains(name) || rez.references.contains(name))
+            .map(|(dependent, _)| dependent.clone())
+            .collect()
+    }
+
+    /// Returns the dependents of the resource with the given name that are
+    /// still waiting to be pruned.
+    pub(crate) fn pending_dependents(&self, name: &str) -> Vec<String> {
+        self.dependents(name)
+            .into_iter()
+            .filter(|dependent| {
+                dependent != name
+                    && self
+                        .rez
+                        .get(dependent)
+                        .map(|rez| rez.use_count == 0 && self.is_targeted(dependent))
+                        .unwrap_or_default()
+            })
+            .collect()
+    }
+
+    /// Sort the given names so that dependents come before the resources they
+    /// depend on.
+    ///
+    /// Names caught in a dependency cycle are kept in their original order, at
+    /// the end.
+    pub(crate) fn reverse_dependency_order(&self, names: Vec<String>) -> Vec<String> {
+        let mut remaining = names;
+        let mut ordered = Vec::with_capacity(remaining.len());
+        loop {
+            let pending = remaining.iter().cloned().collect::<BTreeSet<_>>();
+            let (ready, blocked): (Vec<_>, Vec<_>) = remaining.into_iter().partition(|name| {
+                !self
+                    .dependents(name)
+                    .iter()
+                    .any(|dependent| dependent != name && pending.contains(dependent))
+            });
+            if ready.is_empty() {
+                if !blocked.is_empty() {
+                    log::warn!("dependency cycle between {blocked:?}");
+                }
+                ordered.extend(blocked);
+                return ordered;
+            }
+            ordered.extend(ready);
+            remaining = blocked;
+        }
+    }
+
+    /// Schedule the given resources, along with everything that transitively
+    /// depends on them, for destruction.
+    ///
+    /// Returns the names of the scheduled resources in the order they will be
+    /// deleted.
+    fn schedule_destruction(&mut self, roots: Vec<String>) -> anyhow::Result<Vec<String>> {
+        let mut scheduled = roots.iter().cloned().collect::<BTreeSet<_>>();
+        let mut queue = roots;
+        while let Some(next) = queue.pop() {
+            for dependent in self.dependents(&next) {
+                if scheduled.insert(dependent.clone()) {
+                    queue.push(dependent);
+                }
+            }
+        }
+        for scheduled_name in scheduled.iter() {
+            let rez = self
+                .rez
+                .get_mut(scheduled_name)
+                .context("missing scheduled resource")?;
+            rez.use_count = 0;
+        }
+        self.orphans.destroying.extend(scheduled.iter().cloned());
+        if let Some(targets) = self.targets.as_mut() {
+            targets.extend(scheduled.iter().cloned());
+        }
+        Ok(self.reverse_dependency_order(scheduled.into_iter().collect()))
+    }
+
+    /// Schedule the resource with the given name, along with everything that
+    /// transitively depends on it, for destruction.
+    ///
+    /// The resources are deleted by the next prune (eg [`crate::aws::prune`]),
+    /// dependents first. Returns the names of the scheduled resources in the
+    /// order they will be deleted.
+    ///
+    /// Call this after syncing your infrastructure, as syncing a resource
+    /// keeps it from being pruned.
+    pub fn destroy_cascade(&mut self, name: &str) -> anyhow::Result<Vec<String>> {
+        anyhow::ensure!(
+            self.rez.contains_key(name),
+            "no resource {name} in the store"
+        );
+        let ordered = self.schedule_destruction(vec![name.to_string()])?;
+        log::warn!("destroying {name} and its dependents: {ordered:?}");
+        Ok(ordered)
+    }
+
+    /// Schedule every stored resource whose name matches the predicate, along
+    /// with their dependents, for destruction.
+    ///
+    /// This is useful for tearing down ephemeral environments, eg
+    /// `store.destroy_matching(|name| name.starts_with("preview-pr-123/"))`.
+    /// Like [`Store::destroy_cascade`], the resources are deleted by the next
+    /// prune, and the scheduled names are returned in deletion order.
+    pub fn destroy_matching(
+        &mut self,
+        predicate: impl Fn(&str) -> bool,
+    ) -> anyhow::Result<Vec<String>> {
+        let roots = self
+            .rez
+            .keys()
+            .filter(|name| predicate(name))
+            .cloned()
+            .collect::<Vec<_>>();
+        let ordered = self.schedule_destruction(roots)?;
+        log::warn!(
+            "destroying {} matching resources: {ordered:?}",
+            ordered.len()
+        );
+        Ok(ordered)
+    }
+}
@@ -0,0 +1,158 @@
+//! Path-based diffs of resource definitions.
+//!
+//! Used by the plan display, and available to custom UIs that want to show
+//! what will change about a resource.
+use colored::Colorize;
+use serde_json::Value;
+
+use crate::sensitive::{is_sensitive, redact};
+
+/// A single difference between two JSON values, located by path.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Change {
+    Added {
+        path: String,
+        value: Value,
+    },
+    Removed {
+        path: String,
+        value: Value,
+    },
+    Changed {
+        path: String,
+        old: Value,
+        new: Value,
+    },
+}
+
+impl Change {
+    pub fn path(&self) -> &str {
+        match self {
+            Change::Added { path, .. } => path,
+            Change::Removed { path, .. } => path,
+            Change::Changed { path, .. } => path,
+        }
+    }
+
+    fn lines(&self) -> Vec<String> {
+        let path = if self.path().is_empty() {
+            "."
+        } else {
+            self.path()
+        };
+        match self {
+            Change::Added { value, .. } => vec![format!("+ {path}: {value}")],
+            Change::Removed { value, .. } => vec![format!("- {path}: {value}")],
+            Change::Changed { old, new, .. } => {
+                vec![format!("- {path}: {old}"), format!("+ {path}: {new}")]
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.lines().join("\n"))
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+fn diff_into(path: String, old: &Value, new: &Value, changes: &mut Vec<Change>) {
+    if is_sensitive(old) || is_sensitive(new) {
+        if old != new {
+            changes.push(Change::Changed {
+                path,
+                old: redact(old),
+                new: redact(new),
+            });
+        }
+        return;
+    }
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            for (key, old_value) in old.iter() {
+                let path = join(&path, key);
+                match new.get(key) {
+                    Some(new_value) => diff_into(path, old_value, new_value, changes),
+                    None => changes.push(Change::Removed {
+                        path,
+                        value: redact(old_value),
+                    }),
+                }
+            }
+            for (key, new_value) in new.iter() {
+                if !old.contains_key(key) {
+                    changes.push(Change::Added {
+                        path: join(&path, key),
+                        value: redact(new_value),
+                    });
+                }
+            }
+        }
+        (Value::Array(old), Value::Array(new)) => {
+            for i in 0..old.len().max(new.len()) {
+                let path = format!("{path}[{i}]");
+                match (old.get(i), new.get(i)) {
+                    (Some(old_value), Some(new_value)) => {
+                        diff_into(path, old_value, new_value, changes)
+                    }
+                    (Some(old_value), None) => changes.push(Change::Removed {
+                        path,
+                        value: redact(old_value),
+                    }),
+                    (None, Some(new_value)) => changes.push(Change::Added {
+                        path,
+                        value: redact(new_value),
+                    }),
+                    (None, None) => unreachable!(),
+                }
+            }
+        }
+        (old, new) if old != new => changes.push(Change::Changed {
+            path,
+            old: old.clone(),
+            new: new.clone(),
+        }),
+        _ => {}
+    }
+}
+
+/// Returns the changes needed to get from `old` to `new`.
+///
+/// Nested objects are compared key by key and arrays index by index, so each
+/// change points at the smallest value that differs, eg `env.variables.FOO`
+/// or `layers[1]`.
+///
+/// [Sensitive](crate::Sensitive) values are redacted.
+pub fn diff(old: &Value, new: &Value) -> Vec<Change> {
+    let mut changes = vec![];
+    diff_into(String::new(), old, new, &mut changes);
+    changes
+}
+
+/// Render changes as a compact unified diff, optionally in color.
+pub fn render(changes: &[Change], color: bool) -> String {
+    changes
+        .iter()
+        .flat_map(Change::lines)
+        .map(|line| match line.chars().next() {
+            Some('+') if color => line.green().to_string(),
+            Some('-') if color => line.red().to_string(),
+            _ => line,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Render the difference between `old` and `new` as a compact unified diff.
+pub fn unified(old: &Value, new: &Value) -> String {
+    render(&diff(old, new), false)
+}
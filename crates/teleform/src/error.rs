@@ -0,0 +1,135 @@
+//! Errors with specific meaning to teleform.
+//!
+//! Most functions return `anyhow::Result`, and these errors can be recovered
+//! with `anyhow::Error::downcast_ref`.
+use std::{path::PathBuf, time::Duration};
+
+use crate::Action;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The checksum of a store file doesn't match its contents.
+    CorruptStoreFile {
+        path: PathBuf,
+        expected: String,
+        actual: String,
+    },
+    /// Another process holds the lock file of a store.
+    StoreLocked { path: PathBuf, pid: Option<u32> },
+    /// A run would make more changes than its change budget allows.
+    ///
+    /// See [`crate::budget`].
+    ChangeBudgetExceeded { action: Action, max: usize },
+    /// A manual step wasn't completed in time.
+    ///
+    /// See [`crate::manual`].
+    Manual { id: String, description: String },
+    /// A resource didn't become ready in time.
+    ///
+    /// See [`crate::wait`].
+    Timeout { what: String, timeout: Duration },
+    /// A resource type doesn't implement an action, eg reading or updating.
+    NotImplemented {
+        ty: &'static str,
+        method: &'static str,
+    },
+    /// A stored resource can't be read as the requested type, eg because two
+    /// resources of different types were given the same name.
+    TypeMismatch {
+        name: String,
+        stored: String,
+        requested: &'static str,
+    },
+    /// A stored resource was written by another schema version of its type.
+    ///
+    /// See [`crate::TeleSync::SCHEMA_VERSION`].
+    SchemaMismatch {
+        name: String,
+        type_is: &'static str,
+        stored: u32,
+        requested: Option<u32>,
+    },
+}
+
+impl Error {
+    /// The given method isn't implemented by resources of type `T`.
+    pub fn not_implemented<T>(method: &'static str) -> Self {
+        Error::NotImplemented {
+            ty: std::any::type_name::<T>(),
+            method,
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::CorruptStoreFile {
+                path,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "store file {} is corrupt - expected checksum {expected} but its contents hash \
+                 to {actual}.\nIf the file was edited by hand, revert the edit or restore a \
+                 snapshot (see `Store::restore_snapshot`). If the edit was intentional, remove \
+                 the \"checksum\" field and it will be recomputed on the next save.",
+                path.display()
+            ),
+            Error::StoreLocked { path, pid } => {
+                let holder = match pid {
+                    Some(pid) if *pid == std::process::id() => "this process".to_string(),
+                    Some(pid) => format!("another teleform process (pid {pid})"),
+                    None => "another teleform process".to_string(),
+                };
+                write!(
+                    f,
+                    "{holder} holds the lock {}.\nIf no teleform process is running, remove \
+                     the lock file.",
+                    path.display()
+                )
+            }
+            Error::ChangeBudgetExceeded { action, max } => write!(
+                f,
+                "this run exceeds its change budget of {max} {action}s.\nCheck that the store \
+                 is the one you expect. To go ahead anyway, override the change budget (eg \
+                 with --ignore-change-budget)."
+            ),
+            Error::Manual { id, description } => write!(
+                f,
+                "timed out waiting on manual step {id}:\n  {description}\nComplete the step \
+                 and apply again."
+            ),
+            Error::Timeout { what, timeout } => {
+                write!(f, "{what} timed out after {} seconds", timeout.as_secs())
+            }
+            Error::NotImplemented { ty, method } => {
+                write!(f, "{method} is not implemented for {ty}")
+            }
+            Error::TypeMismatch {
+                name,
+                stored,
+                requested,
+            } => write!(
+                f,
+                "resource {name} is stored as {stored} but was requested as {requested}.\nCheck \
+                 that no two resources of different types share the name {name}."
+            ),
+            Error::SchemaMismatch {
+                name,
+                type_is,
+                stored,
+                requested,
+            } => write!(
+                f,
+                "resource {name} was stored as version {stored} of its type, but was requested as \
+                 {type_is} {}.\nMigrate it with `Store::migrate_local`.",
+                requested
+                    .map(|v| format!("version {v}"))
+                    .unwrap_or_else(|| "without a version".to_string())
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,66 @@
+//! Events emitted while a store runs resource actions.
+use std::time::Duration;
+
+use crate::Action;
+
+/// Something that happened to a resource during plan or apply.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// An action on the named resource has started.
+    Started { name: String, action: Action },
+    /// An action on the named resource finished successfully.
+    Finished {
+        name: String,
+        action: Action,
+        duration: Duration,
+    },
+    /// An action on the named resource failed.
+    Failed {
+        name: String,
+        action: Action,
+        error: String,
+    },
+}
+
+impl Event {
+    pub fn name(&self) -> &str {
+        match self {
+            Event::Started { name, .. } => name,
+            Event::Finished { name, .. } => name,
+            Event::Failed { name, .. } => name,
+        }
+    }
+
+    pub fn action(&self) -> Action {
+        match self {
+            Event::Started { action, .. } => *action,
+            Event::Finished { action, .. } => *action,
+            Event::Failed { action, .. } => *action,
+        }
+    }
+}
+
+/// A callback subscribed to a store's events.
+type Listener = Box<dyn FnMut(&Event) + Send + Sync>;
+
+/// Callbacks subscribed to a store's events.
+#[derive(Default)]
+pub(crate) struct Listeners(Vec<Listener>);
+
+impl std::fmt::Debug for Listeners {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Listeners").field(&self.0.len()).finish()
+    }
+}
+
+impl Listeners {
+    pub(crate) fn push(&mut self, f: impl FnMut(&Event) + Send + Sync + 'static) {
+        self.0.push(Box::new(f));
+    }
+
+    pub(crate) fn emit(&mut self, event: Event) {
+        for f in self.0.iter_mut() {
+            f(&event);
+        }
+    }
+}
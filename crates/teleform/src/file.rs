@@ -0,0 +1,69 @@
+//! Reading and writing store files.
+//!
+//! Store files hold the resources along with the version of teleform that
+//! wrote them and a checksum of the resources, which is verified on read.
+//! Files written before checksums were added are still read.
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::Context;
+
+use crate::{Error, Rez};
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct StoreFile {
+    teleform_version: String,
+    #[serde(default)]
+    checksum: Option<String>,
+    resources: serde_json::Value,
+}
+
+fn checksum(resources: &serde_json::Value) -> anyhow::Result<String> {
+    Ok(crate::sha256_hex(&serde_json::to_vec(resources)?))
+}
+
+/// Encode resources as the contents of a store file.
+pub(crate) fn encode(rez: &BTreeMap<String, Rez>) -> anyhow::Result<String> {
+    let resources = serde_json::to_value(rez)?;
+    let file = StoreFile {
+        teleform_version: env!("CARGO_PKG_VERSION").to_string(),
+        checksum: Some(checksum(&resources)?),
+        resources,
+    };
+    Ok(serde_json::to_string_pretty(&file)?)
+}
+
+/// Decode the contents of the store file at the given path, verifying its checksum.
+pub(crate) fn decode(path: &Path, bytes: &[u8]) -> anyhow::Result<BTreeMap<String, Rez>> {
+    let value: serde_json::Value = serde_json::from_slice(bytes)
+        .with_context(|| format!("could not parse store file {}", path.display()))?;
+    let is_versioned = value.get("teleform_version").is_some() && value.get("resources").is_some();
+    let resources = if is_versioned {
+        let file: StoreFile = serde_json::from_value(value)?;
+        if file.teleform_version != env!("CARGO_PKG_VERSION") {
+            log::debug!(
+                "store file {} was written by teleform {}",
+                path.display(),
+                file.teleform_version
+            );
+        }
+        if let Some(expected) = file.checksum {
+            let actual = checksum(&file.resources)?;
+            if expected != actual {
+                return Err(Error::CorruptStoreFile {
+                    path: path.to_path_buf(),
+                    expected,
+                    actual,
+                }
+                .into());
+            }
+        } else {
+            log::warn!("store file {} has no checksum", path.display());
+        }
+        file.resources
+    } else {
+        log::debug!("reading legacy store file {}", path.display());
+        value
+    };
+    serde_json::from_value(resources)
+        .with_context(|| format!("could not read resources of store file {}", path.display()))
+}
@@ -0,0 +1,113 @@
+//! Keeping state in version control.
+//!
+//! Teleform's store files are meant to be committed. These helpers refuse to
+//! apply while the state has uncommitted changes, and commit the state after a
+//! successful apply with a message summarizing what changed.
+use std::process::Command;
+
+use anyhow::Context;
+
+use crate::{changelog::ChangelogEntry, Store};
+
+impl<Config> Store<Config> {
+    /// Returns the git pathspecs of this store's state files, relative to
+    /// the store's directory.
+    fn state_pathspecs(&self) -> Vec<String> {
+        let stem = self
+            .path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "store".to_string());
+        let lock = self
+            .lock_path()
+            .file_name()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let key = self
+            .secrets_key_path()
+            .file_name()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let snapshots = self
+            .snapshot_dir()
+            .file_name()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        // the lock file and snapshots change every run, so they're not state,
+        // and the secrets key must not be committed
+        let mut pathspecs = vec![
+            format!("{stem}.*"),
+            format!(":(exclude){lock}"),
+            format!(":(exclude){snapshots}"),
+            format!(":(exclude){key}"),
+        ];
+        // git errs on pathspecs that match nothing, so only add these if they exist
+        for name in ["audit.log", crate::hash::CACHE_FILE_NAME] {
+            if self.path.with_file_name(name).exists() {
+                pathspecs.push(name.to_string());
+            }
+        }
+        pathspecs
+    }
+
+    fn git(&self, args: &[&str]) -> anyhow::Result<String> {
+        let dir = self.path.parent().context("store has no directory")?;
+        let output = Command::new("git")
+            .current_dir(dir)
+            .args(args)
+            .arg("--")
+            .args(self.state_pathspecs())
+            .output()
+            .context("could not run git")?;
+        anyhow::ensure!(
+            output.status.success(),
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    /// Err if this store's state files have uncommitted changes.
+    ///
+    /// Call this before applying, so every apply starts from committed state.
+    pub fn ensure_state_committed(&self) -> anyhow::Result<()> {
+        let status = self.git(&["status", "--porcelain"])?;
+        anyhow::ensure!(
+            status.trim().is_empty(),
+            "refusing to apply - the state has uncommitted changes:\n{status}\nCommit or \
+             revert them first."
+        );
+        Ok(())
+    }
+
+    /// Stage and commit this store's state files, with a message summarizing
+    /// the changes applied so far.
+    ///
+    /// Returns whether anything was committed. Does nothing when not applying.
+    pub fn commit_state(&self) -> anyhow::Result<bool> {
+        if !self.apply {
+            return Ok(false);
+        }
+        self.git(&["add", "--all"])?;
+        if self
+            .git(&["diff", "--cached", "--name-only"])?
+            .trim()
+            .is_empty()
+        {
+            log::debug!("no state changes to commit");
+            return Ok(false);
+        }
+        let entry = ChangelogEntry::from_report(self.report());
+        let message = format!(
+            "tele: apply created {}, updated {}, destroyed {}\n\n{}",
+            entry.created.len() + entry.recreated.len(),
+            entry.updated.len(),
+            entry.destroyed.len() + entry.recreated.len(),
+            entry.to_markdown()
+        );
+        self.git(&["commit", "--quiet", "-m", &message])?;
+        log::info!("committed state changes");
+        Ok(true)
+    }
+}
@@ -0,0 +1,171 @@
+//! The dependency graph of a store's resources.
+//!
+//! [`Store::graph_dot`] renders the graph in Graphviz's DOT language, with
+//! each resource colored by the action taken on it (or planned for it) by
+//! this run. With the `graph` feature, [`Store::serve_graph`] serves the
+//! graph as an interactive web page, where clicking a resource shows the
+//! changes to its definition.
+use std::collections::BTreeSet;
+
+use crate::{Action, Store};
+
+/// Returns the DOT fill color of a resource, by the actions taken on it.
+fn color(actions: &BTreeSet<Action>, pruned: bool) -> &'static str {
+    if actions.contains(&Action::Delete) && actions.contains(&Action::Create) {
+        // recreated
+        "orange"
+    } else if actions.contains(&Action::Create) {
+        "palegreen"
+    } else if actions.contains(&Action::Update) {
+        "khaki"
+    } else if actions.contains(&Action::Delete) || pruned {
+        "lightcoral"
+    } else {
+        "white"
+    }
+}
+
+/// Returns the given value as JSON that can be embedded in a `<script>`.
+///
+/// `<` only occurs in JSON strings, where it is escaped so that names and
+/// definitions containing `</script>` can't end the script early.
+#[cfg(feature = "graph")]
+fn script_json(value: &impl serde::Serialize) -> anyhow::Result<String> {
+    Ok(serde_json::to_string(value)?.replace('<', "\\u003c"))
+}
+
+impl<Config> Store<Config> {
+    /// Returns the dependency graph of the store's resources in the DOT
+    /// language, eg to render with `dot -Tsvg`.
+    ///
+    /// Edges point from dependents to the resources they depend on. Resources
+    /// are filled green when created, orange when recreated, yellow when
+    /// updated and red when deleted or pending a prune.
+    pub fn graph_dot(&self) -> String {
+        let prunes = self.get_prunes().into_iter().collect::<BTreeSet<_>>();
+        let mut lines = vec![
+            "digraph {".to_string(),
+            "  node [style=filled];".to_string(),
+        ];
+        for (name, rez) in self.rez.iter() {
+            let actions = self
+                .runner
+                .report
+                .actions
+                .iter()
+                .filter(|report| &report.name == name)
+                .map(|report| report.action)
+                .collect::<BTreeSet<_>>();
+            let type_is = rez.type_is.as_deref().unwrap_or("unknown");
+            let short_type = type_is.rsplit("::").next().unwrap_or(type_is);
+            lines.push(format!(
+                "  {name:?} [label={:?}, tooltip={type_is:?}, fillcolor={}];",
+                format!("{name}\n{short_type}"),
+                color(&actions, prunes.contains(name))
+            ));
+            for dependency in rez.depends_on.union(&rez.references) {
+                lines.push(format!("  {name:?} -> {dependency:?};"));
+            }
+        }
+        lines.push("}".to_string());
+        lines.join("\n")
+    }
+
+    /// Returns the dependency graph as an interactive web page.
+    ///
+    /// Clicking a resource shows the changes made to its definition by this
+    /// run. The page loads its graph renderer from a CDN.
+    #[cfg(feature = "graph")]
+    pub fn graph_page(&self) -> anyhow::Result<String> {
+        Ok(PAGE
+            .replace("{{dot}}", &script_json(&self.graph_dot())?)
+            .replace("{{changes}}", &script_json(&self.runner.report.changes)?))
+    }
+
+    /// Serve the dependency graph as an interactive web page (see
+    /// [`Store::graph_page`]) at the given address, eg "127.0.0.1:8080",
+    /// until the process is stopped.
+    ///
+    /// This blocks the calling thread.
+    #[cfg(feature = "graph")]
+    pub fn serve_graph(&self, addr: impl std::net::ToSocketAddrs) -> anyhow::Result<()> {
+        use std::io::{BufRead, BufReader, Write};
+
+        let page = self.graph_page()?;
+        let listener = std::net::TcpListener::bind(addr)?;
+        log::info!(
+            "serving the resource graph at http://{}",
+            listener.local_addr()?
+        );
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    log::warn!("could not accept a connection: {e}");
+                    continue;
+                }
+            };
+            // read (and ignore) the request, up to the blank line after its headers
+            let mut reader = BufReader::new(&stream);
+            let mut line = String::new();
+            while reader.read_line(&mut line).map(|n| n > 2).unwrap_or(false) {
+                line.clear();
+            }
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: \
+                 {}\r\nConnection: close\r\n\r\n{page}",
+                page.len()
+            );
+            if let Err(e) = stream.write_all(response.as_bytes()) {
+                log::warn!("could not serve the resource graph: {e}");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "graph")]
+const PAGE: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>teleform resources</title>
+<style>
+  body { margin: 0; display: flex; height: 100vh; font-family: sans-serif; }
+  #graph { flex: 3; overflow: auto; }
+  #changes { flex: 1; padding: 1em; border-left: 1px solid #ccc; overflow: auto; }
+  .node { cursor: pointer; }
+</style>
+</head>
+<body>
+<div id="graph"></div>
+<div id="changes"><p>Click a resource to see its changes.</p></div>
+<script src="https://unpkg.com/d3@7/dist/d3.min.js"></script>
+<script src="https://unpkg.com/@hpcc-js/wasm@2/dist/graphviz.umd.js"></script>
+<script src="https://unpkg.com/d3-graphviz@5/build/d3-graphviz.min.js"></script>
+<script>
+  const dot = {{dot}};
+  const changes = {{changes}};
+  function show(name) {
+    const panel = document.getElementById("changes");
+    const title = document.createElement("h3");
+    title.textContent = name;
+    const body = document.createElement("pre");
+    const cs = changes[name] || [];
+    body.textContent = cs.length === 0
+      ? "no changes"
+      : cs.map(c => c.kind === "changed"
+          ? `~ ${c.path}: ${JSON.stringify(c.old)} → ${JSON.stringify(c.new)}`
+          : `${c.kind === "added" ? "+" : "-"} ${c.path}: ${JSON.stringify(c.value)}`
+        ).join("\n");
+    panel.replaceChildren(title, body);
+  }
+  d3.select("#graph").graphviz().renderDot(dot).on("end", () => {
+    d3.selectAll(".node").on("click", function () {
+      show(d3.select(this).select("title").text());
+    });
+  });
+</script>
+</body>
+</html>
+"##;
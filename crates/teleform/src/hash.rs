@@ -0,0 +1,118 @@
+//! Content hashes of local files, cached by path, modification time and size.
+//!
+//! Hashing large artifacts (like lambda zips) on every run is slow, so
+//! digests are kept in a cache file next to the store and only recomputed
+//! when a file's modification time or size changes.
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use crate::Store;
+
+/// The file name of the hash cache, kept in the store's directory.
+pub const CACHE_FILE_NAME: &str = "hash_cache.json";
+
+/// A local file and the sha256 digest of its contents.
+///
+/// Resources that upload files can embed this so that changes to the file's
+/// contents cause an update.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct HashedFile {
+    pub path: PathBuf,
+    pub hash: String,
+}
+
+impl HashedFile {
+    /// Hash the file at the given path, using the cache at `cache_path`.
+    pub fn new(path: impl AsRef<Path>, cache_path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let mut cache = HashCache::from_path(cache_path.as_ref())?;
+        let file = cache.hashed_file(path)?;
+        cache.save(cache_path)?;
+        Ok(file)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+struct CacheEntry {
+    modified_ns: u64,
+    size: u64,
+    digest: String,
+}
+
+/// A cache of file digests, keyed by path.
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct HashCache {
+    entries: BTreeMap<PathBuf, CacheEntry>,
+}
+
+impl HashCache {
+    /// Read the cache at the given path, or an empty cache if it doesn't exist.
+    pub fn from_path(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let file = std::fs::File::open(path)?;
+        serde_json::from_reader(file)
+            .with_context(|| format!("could not read hash cache {}", path.display()))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Returns the sha256 digest of the file at the given path, hashing it only
+    /// if it has changed since it was last hashed.
+    pub fn digest(&mut self, path: impl AsRef<Path>) -> anyhow::Result<String> {
+        let path = path.as_ref();
+        let metadata = std::fs::metadata(path)
+            .with_context(|| format!("could not read metadata of {}", path.display()))?;
+        let modified_ns = metadata
+            .modified()?
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_nanos() as u64;
+        let size = metadata.len();
+        if let Some(entry) = self.entries.get(path) {
+            if entry.modified_ns == modified_ns && entry.size == size {
+                log::trace!("using cached sha256 of {}", path.display());
+                return Ok(entry.digest.clone());
+            }
+        }
+        let digest = crate::cli::sha256_digest(path)?
+            .with_context(|| format!("{} does not exist", path.display()))?;
+        self.entries.insert(
+            path.to_path_buf(),
+            CacheEntry {
+                modified_ns,
+                size,
+                digest: digest.clone(),
+            },
+        );
+        Ok(digest)
+    }
+
+    /// Returns the file at the given path along with its digest.
+    pub fn hashed_file(&mut self, path: impl AsRef<Path>) -> anyhow::Result<HashedFile> {
+        let hash = self.digest(path.as_ref())?;
+        Ok(HashedFile {
+            path: path.as_ref().to_path_buf(),
+            hash,
+        })
+    }
+}
+
+impl<Config> Store<Config> {
+    /// Returns the path of the hash cache used by this store.
+    pub fn hash_cache_path(&self) -> PathBuf {
+        self.path.with_file_name(CACHE_FILE_NAME)
+    }
+
+    /// Hash the file at the given path, using this store's hash cache.
+    pub fn hashed_file(&self, path: impl AsRef<Path>) -> anyhow::Result<HashedFile> {
+        HashedFile::new(path, self.hash_cache_path())
+    }
+}
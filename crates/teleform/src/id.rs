@@ -0,0 +1,138 @@
+//! Typed handles of resources.
+//!
+//! Resources are stored by name, and referring to them by string makes it easy
+//! to misspell a name, which then only fails at runtime. A [`ResourceId`]
+//! is made once with [`Store::resource`] and passed around instead, so a
+//! typo is a compile error:
+//!
+//! ```ignore
+//! let role = store.resource::<Role>("lambda-role");
+//! let lambda = store.resource::<Lambda>("lambda");
+//! store.sync(&role, Role { ... }).await?;
+//! store.sync(&lambda, Lambda { ... }).await?;
+//! store.depends_on(&lambda, &[&role])?;
+//! let stored: Lambda = store.get(&lambda)?;
+//! ```
+//!
+//! Resources can still be referred to by name with the `*_by_name` methods,
+//! eg [`Store::sync_by_name`].
+use std::marker::PhantomData;
+
+use anyhow::Context;
+
+use crate::{Store, TeleSync};
+
+/// The name of a resource of type `T`.
+pub struct ResourceId<T> {
+    name: String,
+    _type: PhantomData<fn() -> T>,
+}
+
+impl<T> ResourceId<T> {
+    /// Returns the handle of the resource with the given name.
+    pub fn new(name: impl Into<String>) -> Self {
+        ResourceId {
+            name: name.into(),
+            _type: PhantomData,
+        }
+    }
+
+    /// Returns the name of the resource.
+    pub fn as_str(&self) -> &str {
+        &self.name
+    }
+}
+
+impl<T> Clone for ResourceId<T> {
+    fn clone(&self) -> Self {
+        ResourceId::new(self.name.clone())
+    }
+}
+
+impl<T> PartialEq for ResourceId<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl<T> Eq for ResourceId<T> {}
+
+impl<T> std::fmt::Debug for ResourceId<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ResourceId")
+            .field(&std::any::type_name::<T>())
+            .field(&self.name)
+            .finish()
+    }
+}
+
+impl<T> std::fmt::Display for ResourceId<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+impl<T> AsRef<str> for ResourceId<T> {
+    fn as_ref(&self) -> &str {
+        &self.name
+    }
+}
+
+/// The handle of a resource of any type, eg to list the dependencies of a
+/// resource with [`Store::depends_on`].
+pub trait AnyResourceId {
+    /// Returns the name of the resource.
+    fn name(&self) -> &str;
+}
+
+impl<T> AnyResourceId for ResourceId<T> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl<Config> Store<Config> {
+    /// Returns the handle of the resource of type `Data` with the given name.
+    pub fn resource<Data: TeleSync>(&self, name: impl Into<String>) -> ResourceId<Data> {
+        ResourceId::new(name)
+    }
+
+    /// Returns the stored resource with the given handle.
+    pub fn get<Data: TeleSync>(&self, id: &ResourceId<Data>) -> anyhow::Result<Data> {
+        let rez = self
+            .rez
+            .get(id.as_str())
+            .with_context(|| format!("no resource {id} in the store"))?;
+        rez.deserialize_as(id.as_str())
+    }
+
+    /// Returns the names of all stored resources, whether or not they have
+    /// been declared.
+    ///
+    /// This lets tools built on teleform, eg dashboards or cleanup scripts,
+    /// inspect a store opened with [`Store::load`] without declaring anything.
+    pub fn stored_ids(&self) -> Vec<&str> {
+        self.rez.keys().map(String::as_str).collect()
+    }
+
+    /// Returns the handles of the stored resources of type `Data`.
+    pub fn stored_ids_of<Data: TeleSync>(&self) -> Vec<ResourceId<Data>> {
+        let type_is = std::any::type_name::<Data>();
+        self.rez
+            .iter()
+            .filter(|(_, rez)| rez.type_is.as_deref() == Some(type_is))
+            .map(|(name, _)| ResourceId::new(name.clone()))
+            .collect()
+    }
+
+    /// Returns the stored resource with the given name, if any, without
+    /// declaring it.
+    pub fn stored<Data: TeleSync>(&self, name: impl AsRef<str>) -> anyhow::Result<Option<Data>> {
+        let name = name.as_ref();
+        let Some(rez) = self.rez.get(name) else {
+            return Ok(None);
+        };
+        rez.check_schema_version::<Data>(name)?;
+        rez.deserialize_as(name).map(Some)
+    }
+}
@@ -0,0 +1,64 @@
+//! Statistics about the resources in a store.
+use std::collections::BTreeMap;
+
+use crate::Store;
+
+/// Statistics about the resources of one type.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TypeInventory {
+    /// Number of resources of this type.
+    pub count: usize,
+    /// Number of resources of this type that are no longer used by the IaC definition.
+    pub orphans: usize,
+    /// The most recent time a resource of this type was applied, in seconds
+    /// since the UNIX epoch.
+    pub last_applied: Option<u64>,
+}
+
+/// Statistics about all the resources in a store.
+///
+/// Useful for dashboards and `status` style subcommands.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Inventory {
+    /// Statistics per resource type name.
+    pub types: BTreeMap<String, TypeInventory>,
+    /// Total number of resources.
+    pub total: usize,
+    /// Total number of resources that are no longer used by the IaC definition.
+    ///
+    /// Only meaningful after the IaC definition has been synced.
+    pub orphans: usize,
+    /// The most recent time any resource was applied, in seconds since the UNIX epoch.
+    pub last_applied: Option<u64>,
+}
+
+impl std::fmt::Display for Inventory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} resources, {} orphaned", self.total, self.orphans)?;
+        for (type_is, inv) in self.types.iter() {
+            writeln!(f, "  {type_is}: {} ({} orphaned)", inv.count, inv.orphans)?;
+        }
+        Ok(())
+    }
+}
+
+impl<Config> Store<Config> {
+    /// Returns statistics about the resources in the store.
+    pub fn inventory(&self) -> Inventory {
+        let mut inventory = Inventory::default();
+        for rez in self.rez.values() {
+            let type_is = rez.type_is.clone().unwrap_or_else(|| "unknown".to_string());
+            let entry = inventory.types.entry(type_is).or_default();
+            let orphaned = rez.use_count == 0;
+            entry.count += 1;
+            entry.last_applied = entry.last_applied.max(rez.last_applied);
+            inventory.total += 1;
+            inventory.last_applied = inventory.last_applied.max(rez.last_applied);
+            if orphaned {
+                entry.orphans += 1;
+                inventory.orphans += 1;
+            }
+        }
+        inventory
+    }
+}
@@ -4,20 +4,112 @@ use anyhow::Context;
 use colored::Colorize;
 use std::{
     cmp::Ordering,
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
     future::Future,
     ops::{Deref, DerefMut},
     pin::Pin,
 };
 
 pub use teleform_derive::TeleSync;
+pub mod approval;
+#[cfg(feature = "archive")]
+pub mod archive;
+pub mod audit;
+#[cfg(feature = "aws")]
 pub mod aws;
+pub mod backend;
+pub mod blueprint;
+pub mod budget;
+pub mod changelog;
+#[cfg(feature = "http")]
+pub mod checks;
+pub mod cli;
+pub mod config;
+pub mod credentials;
+pub mod daemon;
+pub mod debug;
+mod deps;
+pub mod diff;
+pub mod error;
+pub mod events;
+mod file;
+pub mod git;
+pub mod graph;
+pub mod hash;
+pub mod id;
+pub mod inventory;
+pub mod lint;
+mod lock;
+mod lookup;
+pub mod manual;
+mod merge;
+pub mod middleware;
+mod migrate;
+pub mod notify;
+pub mod options;
+pub mod orphans;
+#[cfg(feature = "progress")]
+pub mod progress;
+pub mod provider;
+mod refresh;
+mod registry;
+pub mod report;
+mod rt;
+mod runner;
+pub mod sensitive;
+pub mod snapshot;
+pub mod stack;
+pub mod state;
+pub mod tags;
+mod timing;
+pub mod wait;
+pub use error::Error;
+pub use id::{AnyResourceId, ResourceId};
+pub use provider::Provider;
+pub use report::{ActionReport, ApplyReport};
+pub use sensitive::Sensitive;
 
 /// A remote infrastructure resource.
 #[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct Rez {
     pub type_is: Option<String>,
+    /// The [schema version](TeleSync::SCHEMA_VERSION) of the type the
+    /// resource was written as, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema_version: Option<u32>,
     pub data: serde_json::Value,
+    /// When the resource was last created or updated, in seconds since the UNIX epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_applied: Option<u64>,
+    /// How long the resource's actions took when it was last created or
+    /// updated, in milliseconds.
+    ///
+    /// See [`Store::with_time_budget`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_duration_ms: Option<u64>,
+    /// Names of the resources this resource depends on.
+    ///
+    /// See [`Store::depends_on`].
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub depends_on: BTreeSet<String>,
+    /// Names of the resources whose values this resource refers to, recorded
+    /// each time it is synced. They count as dependencies, like those of
+    /// `depends_on`.
+    ///
+    /// See [`TeleSync::referenced_values`].
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub references: BTreeSet<String>,
+    /// Tags of the resource, eg the account it belongs to.
+    ///
+    /// See [`Store::tag`].
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, String>,
+    /// Provider settings particular to the resource, eg its region, so it can
+    /// still be found once it's no longer declared.
+    ///
+    /// See [`Provider::settings_of`].
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub provider_settings: BTreeMap<String, String>,
     #[serde(skip_serializing, skip_deserializing)]
     use_count: usize,
 }
@@ -34,14 +126,94 @@ impl PartialOrd for Rez {
     }
 }
 
+/// Explain why a resource's stored data differs from its IaC definition,
+/// eg "field `zip_file_hash` changed".
+///
+/// Syncing records these in the store's [report](ApplyReport::reasons), which
+/// the CLI renders in its plan output.
+fn change_reasons(stored: &serde_json::Value, new: &serde_json::Value) -> Vec<String> {
+    match (stored, new) {
+        (serde_json::Value::Object(stored), serde_json::Value::Object(new)) => {
+            let fields = stored.keys().chain(new.keys()).collect::<BTreeSet<_>>();
+            fields
+                .into_iter()
+                .filter(|field| stored.get(*field) != new.get(*field))
+                .map(|field| format!("field `{field}` changed"))
+                .collect()
+        }
+        (stored, new) if stored != new => vec!["value changed".to_string()],
+        _ => vec![],
+    }
+}
+
+/// Returns the number of seconds since the UNIX epoch.
+pub(crate) fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Returns the hex encoded SHA256 digest of the given bytes.
+pub(crate) fn sha256_hex(bytes: &[u8]) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA256, bytes);
+    data_encoding::HEXUPPER.encode(digest.as_ref())
+}
+
 impl Rez {
     pub fn new<T: std::any::Any + serde::Serialize>(data: T) -> anyhow::Result<Self> {
         Ok(Self {
             type_is: Some(std::any::type_name::<T>().to_string()),
+            schema_version: None,
             data: serde_json::to_value(data)?,
+            last_applied: None,
+            last_duration_ms: None,
+            depends_on: Default::default(),
+            references: Default::default(),
+            tags: Default::default(),
+            provider_settings: Default::default(),
             use_count: 0,
         })
     }
+
+    /// Deserialize the resource with the given name as `Data`.
+    ///
+    /// Errs with [`Error::TypeMismatch`] if it doesn't deserialize and was
+    /// stored as another type.
+    pub fn deserialize_as<Data: serde::de::DeserializeOwned>(
+        &self,
+        name: &str,
+    ) -> anyhow::Result<Data> {
+        Data::deserialize(&self.data).map_err(|e| {
+            let requested = std::any::type_name::<Data>();
+            match self.type_is.as_deref() {
+                Some(stored) if stored != requested => {
+                    anyhow::Error::new(e).context(Error::TypeMismatch {
+                        name: name.to_string(),
+                        stored: stored.to_string(),
+                        requested,
+                    })
+                }
+                _ => anyhow::Error::new(e).context(format!("could not deserialize {name}")),
+            }
+        })
+    }
+
+    /// Errs with [`Error::SchemaMismatch`] if the resource was written by
+    /// another schema version of `Data`.
+    ///
+    /// Resources stored before their type had a version match any version.
+    pub fn check_schema_version<Data: TeleSync>(&self, name: &str) -> Result<(), Error> {
+        match self.schema_version {
+            Some(stored) if Some(stored) != Data::SCHEMA_VERSION => Err(Error::SchemaMismatch {
+                name: name.to_string(),
+                type_is: std::any::type_name::<Data>(),
+                stored,
+                requested: Data::SCHEMA_VERSION,
+            }),
+            _ => Ok(()),
+        }
+    }
 }
 
 /// Pick between two values.
@@ -69,6 +241,19 @@ impl<T: TeleEither> TeleEither for Option<T> {
     }
 }
 
+/// Maps pick between the values of the keys in `self`, so a map can hold
+/// remote values.
+impl<K: Ord, V: TeleEither> TeleEither for BTreeMap<K, V> {
+    fn either(self, mut other: Self) -> Self {
+        self.into_iter()
+            .map(|(k, v)| match other.remove(&k) {
+                Some(o) => (k, v.either(o)),
+                None => (k, v),
+            })
+            .collect()
+    }
+}
+
 /// A local value, known before resource creation.
 #[derive(Debug, Default, PartialEq, Clone, Copy, serde::Serialize, serde::Deserialize)]
 pub struct Local<T>(pub T);
@@ -139,6 +324,12 @@ impl<T> From<T> for Remote<T> {
     }
 }
 
+impl<'a> From<&'a str> for Remote<String> {
+    fn from(value: &'a str) -> Self {
+        Remote::Remote(value.to_string())
+    }
+}
+
 impl<T: serde::Serialize> serde::Serialize for Remote<T> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -191,9 +382,18 @@ impl<T> Remote<T> {
 /// Synchronize an IaC definition with a stored type, mutating infrastructure to match.
 pub trait TeleSync
 where
-    Self: serde::Serialize + serde::de::DeserializeOwned + std::fmt::Debug,
+    Self: serde::Serialize + serde::de::DeserializeOwned + std::fmt::Debug + Send + Sync,
 {
-    type Provider;
+    /// The provider the resource is created with, see [`Provider`].
+    type Provider: Provider;
+
+    /// The version of the type's definition, recorded with each stored
+    /// resource and checked when it is read back.
+    ///
+    /// Bump it with `#[tele(schema_version = 2)]` when the fields of the type
+    /// change shape, and migrate stored resources with
+    /// [`Store::migrate_local`].
+    const SCHEMA_VERSION: Option<u32> = None;
 
     fn composite(self, other: Self) -> Self;
 
@@ -201,54 +401,241 @@ where
 
     fn should_update(&self, other: &Self) -> bool;
 
+    /// Summarize the changes from the previous definition of the resource, in
+    /// place of the generic field diff shown when it is recreated or updated.
+    ///
+    /// This is useful for complex types, eg a lambda whose code changed.
+    fn describe_change(&self, _previous: &Self) -> Option<String> {
+        None
+    }
+
+    /// Returns the values of other resources that this resource refers to, eg
+    /// the name of a table in a lambda's environment.
+    ///
+    /// Syncing records a reference to every stored resource with a field of
+    /// one of these values, so it is deleted after this resource (see
+    /// [`Rez::references`]).
+    fn referenced_values(&self) -> Vec<serde_json::Value> {
+        vec![]
+    }
+
+    /// Check that the definition is valid before it is created or updated.
+    ///
+    /// This runs while planning, before any infrastructure is mutated, so
+    /// obviously invalid definitions (empty names, missing files) fail early.
+    fn validate(&self, name: &str) -> anyhow::Result<()> {
+        let _ = name;
+        Ok(())
+    }
+
+    /// Create the resource.
+    ///
+    /// By default resources can't be created, and this errs with
+    /// [`Error::NotImplemented`].
     fn create<'a>(
         &'a mut self,
-        apply: bool,
-        helper: &'a Self::Provider,
-        name: &'a str,
-    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + 'a>>;
+        _apply: bool,
+        _helper: &'a Self::Provider,
+        _name: &'a str,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>> {
+        Box::pin(async { Err(Error::not_implemented::<Self>("create").into()) })
+    }
 
     fn create_finalize<'a>(
         &'a mut self,
         _apply: bool,
         _helper: &'a Self::Provider,
         _name: &'a str,
-    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + 'a>> {
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>> {
         Box::pin(async { Ok(()) })
     }
 
+    /// Update the resource in place.
+    ///
+    /// By default resources can't be updated, and this errs with
+    /// [`Error::NotImplemented`].
     fn update<'a>(
         &'a mut self,
-        apply: bool,
-        helper: &'a Self::Provider,
-        name: &'a str,
-        previous: &'a Self,
-    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + 'a>>;
+        _apply: bool,
+        _helper: &'a Self::Provider,
+        _name: &'a str,
+        _previous: &'a Self,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>> {
+        Box::pin(async { Err(Error::not_implemented::<Self>("update").into()) })
+    }
 
     fn update_finalize<'a>(
         &'a mut self,
         _apply: bool,
         _helper: &'a Self::Provider,
         _name: &'a str,
-    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + 'a>> {
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>> {
         Box::pin(async { Ok(()) })
     }
 
+    /// Delete the resource.
+    ///
+    /// By default resources can't be deleted, and this errs with
+    /// [`Error::NotImplemented`].
     fn delete<'a>(
         &'a self,
-        apply: bool,
+        _apply: bool,
+        _helper: &'a Self::Provider,
+        _name: &'a str,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>> {
+        Box::pin(async { Err(Error::not_implemented::<Self>("delete").into()) })
+    }
+
+    /// Re-read the resource from the provider, updating it in place.
+    ///
+    /// Used by [`TeleSync::read_many`]. By default resources can't be read, and
+    /// this errs with [`Error::NotImplemented`].
+    fn read<'a>(
+        &'a mut self,
+        _helper: &'a Self::Provider,
+        _name: &'a str,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>> {
+        Box::pin(async { Err(Error::not_implemented::<Self>("read").into()) })
+    }
+
+    /// Re-read many resources of this type from the provider at once, keyed
+    /// by name, updating them in place and removing those that no longer
+    /// exist.
+    ///
+    /// Used by [`Store::refresh_ids`], which reads all the named resources of
+    /// one type with a single call. Override it with
+    /// `#[tele(read_many = ...)]` where the provider can describe many
+    /// resources with one request. By default each resource is checked with
+    /// [`TeleSync::exists`] and then [read](TeleSync::read) in turn.
+    fn read_many<'a>(
+        resources: &'a mut BTreeMap<String, Self>,
         helper: &'a Self::Provider,
-        name: &'a str,
-    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + 'a>>;
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>> {
+        Box::pin(async move {
+            let mut gone = vec![];
+            for (name, data) in resources.iter_mut() {
+                if !data
+                    .exists(helper, name)
+                    .await
+                    .with_context(|| format!("could not check that {name} exists"))?
+                {
+                    gone.push(name.clone());
+                    continue;
+                }
+                data.read(helper, name)
+                    .await
+                    .with_context(|| format!("could not read {name}"))?;
+            }
+            for name in gone {
+                resources.remove(&name);
+            }
+            Ok(())
+        })
+    }
+
+    /// Returns whether the stored resource still exists.
+    ///
+    /// Syncing checks this of every stored resource that isn't recreated, so
+    /// resources deleted outside of teleform are created again, whether or
+    /// not their definition changed. They are dropped from the store by
+    /// [`Store::refresh`]. By default resources are assumed to exist.
+    fn exists<'a>(
+        &'a self,
+        _helper: &'a Self::Provider,
+        _name: &'a str,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<bool>> + Send + 'a>> {
+        Box::pin(async { Ok(true) })
+    }
+}
+
+/// An action taken on a resource.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+pub enum Action {
+    Create,
+    CreateFinalize,
+    Update,
+    UpdateFinalize,
+    Delete,
+    Read,
+}
+
+impl Action {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Action::Create => "create",
+            Action::CreateFinalize => "create_finalize",
+            Action::Update => "update",
+            Action::UpdateFinalize => "update_finalize",
+            Action::Delete => "delete",
+            Action::Read => "read",
+        }
+    }
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A simulated run of a store.
+///
+/// See [`Store::simulate`].
+#[derive(Debug, Default)]
+pub struct Simulation {
+    fakes: BTreeMap<String, serde_json::Value>,
+    unresolved: Vec<String>,
+}
+
+impl Simulation {
+    /// Fill in the remote values of a newly created resource using its fake, if any.
+    fn synthesize<Data: TeleSync>(&mut self, name: &str, data: Data) -> anyhow::Result<Data> {
+        if let Some(fake) = self.fakes.get(name) {
+            let fake = Data::deserialize(fake)
+                .with_context(|| format!("could not deserialize fake {name}"))?;
+            log::debug!("synthesized remote values of {name} from its fake");
+            Ok(data.composite(fake))
+        } else {
+            log::warn!("no fake for {name} - its remote values will be unknown");
+            self.unresolved.push(name.to_string());
+            Ok(data)
+        }
+    }
+
+    /// Names of created resources whose remote values could not be synthesized.
+    pub fn unresolved(&self) -> &[String] {
+        &self.unresolved
+    }
 }
 
 /// An IaC store.
+///
+/// The store's config is usually a [`Provider`], whose hooks can be run with
+/// [`Store::init`], [`Store::healthcheck`] and [`Store::shutdown`].
+///
+/// A store is `Send` and `Sync` when its config is, and the futures of its
+/// actions are `Send`, so it can be moved into spawned tasks or shared with
+/// request handlers, eg behind a `tokio::sync::Mutex`.
 #[derive(Debug)]
 pub struct Store<Config> {
     path: std::path::PathBuf,
     pub apply: bool,
     pub cfg: Config,
     rez: BTreeMap<String, Rez>,
+    simulation: Option<Simulation>,
+    runner: runner::Runner,
+    snapshot_generations: usize,
+    targets: Option<BTreeSet<String>>,
+    settings: config::Settings,
+    lock: Option<lock::StoreLock>,
+    registry: registry::Registry<Config>,
+    budget: budget::Budget,
+    orphans: orphans::Orphans,
+    lints: lint::Lints,
+    backend: Option<Box<dyn backend::StateBackend>>,
+    timing: timing::Timing,
 }
 
 impl<Config> Store<Config> {
@@ -256,107 +643,355 @@ impl<Config> Store<Config> {
         &self.path
     }
 
+    /// Turn this store into a simulation.
+    ///
+    /// A simulated store never applies changes or writes to disk. The remote
+    /// values of created resources are synthesized from the values already in the
+    /// store, or from fakes provided with [`Store::fake`]. This lets the IaC
+    /// definition run end-to-end without any provider access, which is great for
+    /// PR-level checks.
+    pub fn simulate(mut self) -> Self {
+        self.apply = false;
+        self.simulation = Some(Simulation::default());
+        self
+    }
+
+    /// Provide a fake for the resource with the given name.
+    ///
+    /// During a simulation the fake's remote values are used in place of the
+    /// values that would be determined by creating the resource.
+    pub fn fake<Data>(&mut self, name: impl Into<String>, data: Data) -> anyhow::Result<()>
+    where
+        Data: TeleSync,
+    {
+        let simulation = self
+            .simulation
+            .as_mut()
+            .context("cannot provide a fake - store is not a simulation")?;
+        simulation
+            .fakes
+            .insert(name.into(), serde_json::to_value(data)?);
+        Ok(())
+    }
+
+    /// Returns the metrics and timing of all actions taken so far.
+    pub fn report(&self) -> &ApplyReport {
+        &self.runner.report
+    }
+
+    /// Returns the path of the audit log, if any.
+    ///
+    /// See [`audit`].
+    pub fn audit_log(&self) -> Option<&std::path::Path> {
+        self.runner.audit_log.as_deref()
+    }
+
+    /// Set the path of the audit log, or `None` to disable auditing.
+    pub fn set_audit_log(&mut self, path: Option<std::path::PathBuf>) {
+        self.runner.audit_log = path;
+    }
+
+    /// Subscribe to the events emitted as resource actions start and finish.
+    ///
+    /// See [`events`].
+    pub fn on_event(&mut self, f: impl FnMut(&events::Event) + Send + Sync + 'static) {
+        self.runner.listeners.push(f);
+    }
+
+    /// Returns the simulation, if this store is simulated.
+    pub fn simulation(&self) -> Option<&Simulation> {
+        self.simulation.as_ref()
+    }
+
     /// Insert an IaC resource into the store.
     ///
     /// This is useful for adding resources created outside of teleform.
+    ///
+    /// A resource already stored with the given name is replaced, see
+    /// [`Store::insert_merge`] to keep its values.
     pub fn insert<'a, Data>(&mut self, name: impl Into<String>, data: Data) -> anyhow::Result<()>
     where
         Config: AsRef<<Data as TeleSync>::Provider>,
         Data: std::any::Any + TeleSync,
     {
         let name = name.into();
-        let json = serde_json::to_string_pretty(&data)?;
+        let json = serde_json::to_string_pretty(&sensitive::redact(&serde_json::to_value(&data)?))?;
         log::info!("inserting {name}:\n{json}");
+        if let Some(existing) = self.rez.get(&name) {
+            let changes = diff::diff(&existing.data, &serde_json::to_value(&data)?);
+            if !changes.is_empty() {
+                log::warn!(
+                    "replacing the stored {name} - use `Store::insert_merge` to only fill in \
+                     missing fields:\n{}",
+                    diff::render(&changes, true)
+                );
+            }
+        }
         let entry = self.rez.entry(name).or_default();
         entry.type_is = Some(std::any::type_name::<Data>().to_string());
+        entry.schema_version = Data::SCHEMA_VERSION;
         entry.data = serde_json::to_value(&data)?;
         entry.use_count += 1;
         Ok(())
     }
 
-    /// Synchronize a singular IaC resource.
-    pub async fn sync<'a, Data>(
+    /// Synchronize a singular IaC resource, given its handle (see
+    /// [`Store::resource`]).
+    pub async fn sync<Data>(&mut self, id: &ResourceId<Data>, data: Data) -> anyhow::Result<Data>
+    where
+        Config: AsRef<<Data as TeleSync>::Provider> + Send + Sync,
+        Data: std::any::Any + TeleSync + Clone,
+    {
+        self.sync_by_name(id.as_str(), data).await
+    }
+
+    /// Synchronize a singular IaC resource, given its name.
+    ///
+    /// Prefer [`Store::sync`], whose handles can't be misspelled or given the
+    /// data of another type.
+    pub async fn sync_by_name<'a, Data>(
         &mut self,
         name: impl Into<String>,
         mut data: Data,
     ) -> anyhow::Result<Data>
     where
-        Config: AsRef<<Data as TeleSync>::Provider>,
+        Config: AsRef<<Data as TeleSync>::Provider> + Send + Sync,
         Data: std::any::Any + TeleSync + Clone,
     {
         use colored::*;
 
+        self.lock()?;
+        self.registry.register::<Data>();
         let name = name.into();
+        let type_is = std::any::type_name::<Data>();
         let provider: &Data::Provider = self.cfg.as_ref();
         let mut created = false;
         let mut updated = false;
         log::trace!("sync'ing {name}");
+        if !self.is_targeted(&name) {
+            log::debug!("skipping {name} - not targeted");
+            return if let Some(existing) = self.rez.get_mut(&name) {
+                existing.use_count += 1;
+                existing.deserialize_as(&name)
+            } else {
+                Ok(data)
+            };
+        }
+        self.lints.check(&name, &data)?;
         if let Some(existing) = self.rez.get_mut(&name) {
-            let existing_data: Data = serde_json::from_value(existing.data.clone())
-                .with_context(|| format!("could not deserialize {name}"))?;
+            existing.check_schema_version::<Data>(&name)?;
+            // deserialize from a borrow of the stored JSON to avoid cloning it
+            let existing_data: Data = existing.deserialize_as(&name)?;
             data = data.composite(existing_data.clone());
-            // UNWRAP: safe because rez always serializes
-            let prev = serde_json::to_string_pretty(&existing).unwrap();
-            let new = {
-                let mut new_rez = existing.clone();
-                new_rez.data = serde_json::to_value(data.clone())?;
-                serde_json::to_string_pretty(&new_rez)?
-            };
-            let comparison = pretty_assertions::StrComparison::new(&prev, &new);
+            if let Some(options) = self.runner.options.get(&name) {
+                data = options.keep_ignored(&existing.data, data)?;
+            }
+            let new_data = serde_json::to_value(&data)?;
+            let reasons = change_reasons(&existing.data, &new_data);
+            let because = reasons.join(", ");
+            let changes = diff::diff(&existing.data, &new_data);
+            let description = data.describe_change(&existing_data);
+            let comparison = description
+                .clone()
+                .unwrap_or_else(|| diff::render(&changes, true));
             // recreate or update
             if existing_data.should_recreate(&data) {
-                log::info!("recreating {name}:\n{comparison}");
-                log::info!("deleting {name}");
-                data.delete(self.apply, provider, &name).await?;
-                if self.apply {
-                    log::info!("...deleted");
+                data.validate(&name)
+                    .with_context(|| format!("invalid definition of {name}"))?;
+                log::info!("recreating {name} because {because}:\n{comparison}");
+                self.runner.check_prevent_destroy(&name)?;
+                if self.runner.approve(self.apply, "recreate", &name).await? {
+                    self.budget.spend(Action::Delete)?;
+                    self.runner
+                        .report
+                        .explain(&name, reasons, changes, description);
+                    log::info!("deleting {name}");
+                    self.runner
+                        .run(
+                            self.apply,
+                            Action::Delete,
+                            &name,
+                            Some(&existing.data),
+                            &mut data,
+                            |d, _| d.delete(self.apply, provider, &name),
+                        )
+                        .await?;
+                    if self.apply {
+                        log::info!("...deleted");
+                    }
+                    log::info!("creating {name}");
+                    self.runner
+                        .run(
+                            self.apply,
+                            Action::Create,
+                            &name,
+                            Some(&existing.data),
+                            &mut data,
+                            |d, _| d.create(self.apply, provider, &name),
+                        )
+                        .await?;
+                    if let Some(simulation) = self.simulation.as_mut() {
+                        data = simulation.synthesize(&name, data)?;
+                    }
+                    created = true;
+                    if self.apply {
+                        log::info!("...created");
+                    }
+                } else {
+                    data = existing_data;
                 }
-                log::info!("creating {name}");
-                data.create(self.apply, provider, &name).await?;
-                created = true;
-                if self.apply {
-                    log::info!("...created");
+            } else if self.simulation.is_none()
+                && !existing_data
+                    .exists(provider, &name)
+                    .await
+                    .with_context(|| format!("could not check that {name} exists"))?
+            {
+                data.validate(&name)
+                    .with_context(|| format!("invalid definition of {name}"))?;
+                log::warn!("{name} was deleted outside of teleform - creating it again");
+                if self.runner.approve(self.apply, "create", &name).await? {
+                    self.runner
+                        .report
+                        .explain(&name, reasons, changes, description);
+                    self.runner
+                        .run(
+                            self.apply,
+                            Action::Create,
+                            &name,
+                            Some(&existing.data),
+                            &mut data,
+                            |d, _| d.create(self.apply, provider, &name),
+                        )
+                        .await?;
+                    created = true;
+                    if self.apply {
+                        log::info!("...created");
+                    }
+                } else {
+                    data = existing_data;
                 }
             } else if existing_data.should_update(&data) {
-                log::info!("updating {name}:\n{comparison}");
-                data.update(self.apply, provider, &name, &existing_data)
-                    .await?;
-                updated = true;
-                if self.apply {
-                    log::info!("...updated");
+                data.validate(&name)
+                    .with_context(|| format!("invalid definition of {name}"))?;
+                log::info!("updating {name} because {because}:\n{comparison}");
+                if self.runner.approve(self.apply, "update", &name).await? {
+                    self.budget.spend(Action::Update)?;
+                    self.runner
+                        .report
+                        .explain(&name, reasons, changes, description);
+                    self.runner
+                        .run(
+                            self.apply,
+                            Action::Update,
+                            &name,
+                            Some(&existing.data),
+                            &mut data,
+                            |d, _| d.update(self.apply, provider, &name, &existing_data),
+                        )
+                        .await?;
+                    updated = true;
+                    if self.apply {
+                        log::info!("...updated");
+                    }
+                } else {
+                    data = existing_data;
                 }
             } else {
                 data = existing_data;
             }
-            existing.type_is = Some(std::any::type_name::<Data>().to_string());
-            existing.data = serde_json::to_value(data.clone())?;
+            existing.type_is = Some(type_is.to_string());
+            existing.schema_version = Data::SCHEMA_VERSION;
+            existing.provider_settings = provider.settings_of(&name);
+            if created || updated {
+                existing.data = serde_json::to_value(&data)?;
+            }
             existing.use_count += 1;
+            for (key, value) in self.settings.default_tags.iter() {
+                existing
+                    .tags
+                    .entry(key.clone())
+                    .or_insert_with(|| value.clone());
+            }
+            if self.apply && (created || updated) {
+                existing.last_applied = Some(now_secs());
+            }
         } else {
             // create
+            data.validate(&name)
+                .with_context(|| format!("invalid definition of {name}"))?;
             log::info!(
                 "creating {name}:\n{}",
-                serde_json::to_string_pretty(&data).context("json")?.green()
+                serde_json::to_string_pretty(&sensitive::redact(&serde_json::to_value(&data)?))
+                    .context("json")?
+                    .green()
             );
-            data.create(self.apply, provider, &name).await?;
+            if !self.runner.approve(self.apply, "create", &name).await? {
+                return Ok(data);
+            }
+            self.runner
+                .run(
+                    self.apply,
+                    Action::Create,
+                    &name,
+                    None,
+                    &mut data,
+                    |d, _| d.create(self.apply, provider, &name),
+                )
+                .await?;
+            if let Some(simulation) = self.simulation.as_mut() {
+                data = simulation.synthesize(&name, data)?;
+            }
             created = true;
             if self.apply {
                 log::info!("...created");
             }
-            let mut rez = Rez::new(data.clone())?;
-            rez.use_count += 1;
+            let rez = Rez {
+                type_is: Some(type_is.to_string()),
+                schema_version: Data::SCHEMA_VERSION,
+                data: serde_json::to_value(&data)?,
+                last_applied: self.apply.then(now_secs),
+                last_duration_ms: None,
+                depends_on: Default::default(),
+                references: Default::default(),
+                tags: self.settings.default_tags.clone(),
+                provider_settings: provider.settings_of(&name),
+                use_count: 1,
+            };
             self.rez.insert(name.clone(), rez);
         };
+        let references = self.resources_with_values(&name, &data.referenced_values());
+        if let Some(rez) = self.rez.get_mut(&name) {
+            rez.references = references;
+        }
         if self.apply {
             self.save(&self.path)?;
         }
-        if created {
-            data.create_finalize(self.apply, provider, &name).await?;
-            if self.apply {
-                self.save(&self.path)?;
+        if created || updated {
+            let action = if created {
+                Action::CreateFinalize
+            } else {
+                Action::UpdateFinalize
+            };
+            self.runner
+                .run(
+                    self.apply,
+                    action,
+                    &name,
+                    self.rez.get(&name).map(|rez| &rez.data),
+                    &mut data,
+                    |d, _| {
+                        if created {
+                            d.create_finalize(self.apply, provider, &name)
+                        } else {
+                            d.update_finalize(self.apply, provider, &name)
+                        }
+                    },
+                )
+                .await?;
+            if let Some(rez) = self.rez.get_mut(&name) {
+                rez.data = serde_json::to_value(&data)?;
             }
-        }
-        if updated {
-            data.update_finalize(self.apply, provider, &name).await?;
+            self.time_actions(&name);
             if self.apply {
                 self.save(&self.path)?;
             }
@@ -370,36 +1005,128 @@ impl<Config> Store<Config> {
         path: impl AsRef<std::path::Path>,
     ) -> anyhow::Result<Self> {
         let path = path.as_ref().to_path_buf();
-        let file = std::fs::File::open(&path)?;
-        let rez: BTreeMap<String, Rez> = serde_json::from_reader(file)?;
-        Ok(Store {
-            path,
-            apply,
-            cfg,
-            rez,
-        })
+        let bytes = std::fs::read(&path)?;
+        let rez = file::decode(&path, &bytes)?;
+        let mut store = Store::new(apply, cfg, path);
+        store.rez = rez;
+        Ok(store)
     }
 
-    pub fn empty(apply: bool, cfg: Config) -> Self {
+    /// Read a store from the given path without blocking the async runtime.
+    ///
+    /// With the `tokio` feature the file is read with `tokio::fs` and
+    /// deserialized on the blocking thread pool, which matters for stores with
+    /// thousands of resources.
+    pub async fn load(
+        apply: bool,
+        cfg: Config,
+        path: impl AsRef<std::path::Path>,
+    ) -> anyhow::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let bytes = rt::read(&path)
+            .await
+            .with_context(|| format!("could not read {}", path.display()))?;
+        let rez = {
+            let path = path.clone();
+            rt::spawn_blocking(move || file::decode(&path, &bytes)).await??
+        };
+        let mut store = Store::new(apply, cfg, path);
+        store.rez = rez;
+        Ok(store)
+    }
+
+    /// Create an empty store that will be saved to the given path.
+    pub fn new(apply: bool, cfg: Config, path: impl AsRef<std::path::Path>) -> Self {
+        let path = path.as_ref().to_path_buf();
         Store {
-            path: std::env::current_dir().unwrap().join("default_store.json"),
+            runner: runner::Runner {
+                audit_log: Some(path.with_file_name("audit.log")),
+                ..Default::default()
+            },
+            path,
             apply,
             cfg,
             rez: Default::default(),
+            simulation: None,
+            snapshot_generations: snapshot::DEFAULT_GENERATIONS,
+            targets: None,
+            settings: Default::default(),
+            lock: None,
+            registry: Default::default(),
+            budget: Default::default(),
+            orphans: Default::default(),
+            lints: Default::default(),
+            backend: None,
+            timing: Default::default(),
         }
     }
 
+    pub fn empty(apply: bool, cfg: Config) -> Self {
+        let path = std::env::current_dir().unwrap().join("default_store.json");
+        Store::new(apply, cfg, path)
+    }
+
+    /// Restrict changes to the resources with the given names.
+    ///
+    /// Resources that are not targeted are left as they are in the store -
+    /// they are neither created, updated nor pruned.
+    /// Passing `None` targets all resources (the default).
+    pub fn set_targets(&mut self, targets: Option<impl IntoIterator<Item = impl Into<String>>>) {
+        self.targets = targets.map(|ts| ts.into_iter().map(Into::into).collect());
+    }
+
+    /// Prompt for approval before each destructive action (recreations and
+    /// deletions) while applying.
+    ///
+    /// If stdin is not a terminal this has no effect and every action is
+    /// approved, as if `--auto-approve` was given.
+    pub fn set_interactive(&mut self, interactive: bool) {
+        if interactive && !approval::stdin_is_terminal() {
+            log::warn!("stdin is not a terminal - auto-approving all actions");
+            self.runner.interactive.enabled = false;
+        } else {
+            self.runner.interactive.enabled = interactive;
+        }
+    }
+
+    /// Approve each action with the given callback, just before it runs.
+    ///
+    /// The callback can approve, skip or abort every create, update, recreate
+    /// and delete while applying. This replaces interactive approval.
+    pub fn set_approval<F, Fut>(&mut self, mut f: F)
+    where
+        F: FnMut(approval::PendingAction) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = anyhow::Result<approval::Approval>> + Send + 'static,
+    {
+        self.runner.approval = Some(approval::Callback(std::sync::Arc::new(
+            std::sync::Mutex::new(Box::new(move |pending| Box::pin(f(pending)))),
+        )));
+    }
+
+    /// Returns whether the resource with the given name is targeted for changes.
+    pub fn is_targeted(&self, name: &str) -> bool {
+        self.targets
+            .as_ref()
+            .map(|targets| targets.contains(name))
+            .unwrap_or(true)
+    }
+
+    /// Returns the names of resources that should be pruned, dependents first.
+    ///
+    /// Resources are only pruned if their [orphan policy](orphans) allows it.
     pub fn get_prunes(&self) -> Vec<String> {
-        self.rez
+        let prunes = self
+            .rez
             .iter()
             .filter_map(|(name, rez)| {
-                if rez.use_count == 0 {
+                if rez.use_count == 0 && self.is_targeted(name) && self.is_deletable_orphan(name) {
                     Some(name.clone())
                 } else {
                     None
                 }
             })
-            .collect::<Vec<_>>()
+            .collect::<Vec<_>>();
+        self.reverse_dependency_order(prunes)
     }
 
     pub async fn prune<Data>(&mut self) -> anyhow::Result<()>
@@ -407,34 +1134,65 @@ impl<Config> Store<Config> {
         Config: AsRef<Data::Provider>,
         Data: TeleSync,
     {
+        self.lock()?;
+        self.prune_lookups()?;
+        self.check_orphans::<Data>()?;
         let to_prune = self.get_prunes();
         if !to_prune.is_empty() {
             for name in to_prune.into_iter() {
                 if let Some(rez) = self.rez.get(&name) {
                     let type_is = Some(std::any::type_name::<Data>().to_string());
                     if rez.type_is == type_is {
+                        let dependents = self.pending_dependents(&name);
+                        if !dependents.is_empty() {
+                            log::debug!(
+                                "deferring pruning {name} until its dependents are pruned: \
+                                 {dependents:?}"
+                            );
+                            continue;
+                        }
                         // UNWRAP: safe because we just created it above
                         log::warn!("cleaning up resource {name} {}", type_is.unwrap());
                         // UNWRAP: safe because Value always converts
-                        log::info!("{}", serde_json::to_string_pretty(&rez.data).unwrap().red());
+                        log::info!(
+                            "{}",
+                            serde_json::to_string_pretty(&sensitive::redact(&rez.data))
+                                .unwrap()
+                                .red()
+                        );
                     } else {
                         continue;
                     }
                 }
+                self.runner.check_prevent_destroy(&name)?;
                 // UNWRAP: safe because we just got this rez from the store, or we would
                 // have `continue`d above
-                let rez = self.rez.remove(&name).unwrap();
-                match serde_json::from_value::<Data>(rez.data.clone()) {
-                    Ok(data) => {
-                        if self.apply {
-                            data.delete(self.apply, self.cfg.as_ref(), &name).await?;
-                            self.save(&self.path)?;
-                            log::info!("...deleted");
-                        }
-                    }
-                    Err(_) => {
-                        self.rez.insert(name, rez);
-                    }
+                let Ok(mut data) = Data::deserialize(&self.rez.get(&name).unwrap().data) else {
+                    continue;
+                };
+                if !self.runner.approve(self.apply, "delete", &name).await? {
+                    continue;
+                }
+                self.budget.spend(Action::Delete)?;
+                let stored = self.rez.remove(&name);
+                // run the delete even when planning, so it's in the report
+                let provider: &Data::Provider = self.cfg.as_ref();
+                if let Some(rez) = stored.as_ref() {
+                    provider.restore_settings(&name, &rez.provider_settings);
+                }
+                self.runner
+                    .run(
+                        self.apply,
+                        Action::Delete,
+                        &name,
+                        stored.as_ref().map(|rez| &rez.data),
+                        &mut data,
+                        |d, _| d.delete(self.apply, provider, &name),
+                    )
+                    .await?;
+                if self.apply {
+                    self.save(&self.path)?;
+                    log::info!("...deleted");
                 }
             }
         }
@@ -442,16 +1200,65 @@ impl<Config> Store<Config> {
         Ok(())
     }
 
+    /// Delete the resource with the given handle, if it is stored.
+    ///
+    /// To delete a resource whose type is no longer known, see
+    /// [`Store::destroy_by_id`].
+    pub async fn destroy<Data>(&mut self, id: &ResourceId<Data>) -> anyhow::Result<()>
+    where
+        Config: AsRef<Data::Provider>,
+        Data: TeleSync,
+    {
+        self.destroy_by_name::<Data>(id.as_str()).await
+    }
+
+    /// Delete the resource of type `Data` with the given name, if it is
+    /// stored.
+    ///
+    /// Prefer [`Store::destroy`], whose handles can't be misspelled.
+    pub async fn destroy_by_name<Data>(&mut self, name: impl Into<String>) -> anyhow::Result<()>
+    where
+        Config: AsRef<Data::Provider>,
+        Data: TeleSync,
+    {
+        self._delete::<Data>(name).await?;
+        if self.apply {
+            self.save(&self.path)?;
+        }
+        Ok(())
+    }
+
     /// Delete the resource with the given name, if any.
     pub async fn _delete<Data>(&mut self, name: impl Into<String>) -> anyhow::Result<()>
     where
         Config: AsRef<Data::Provider>,
         Data: TeleSync,
     {
+        self.lock()?;
         let name = name.into();
+        if self.rez.contains_key(&name) {
+            self.runner.check_prevent_destroy(&name)?;
+        }
+        if !self.runner.approve(self.apply, "delete", &name).await? {
+            return Ok(());
+        }
+        if self.rez.contains_key(&name) {
+            self.budget.spend(Action::Delete)?;
+        }
         if let Some(rez) = self.rez.remove(&name) {
-            let data: Data = serde_json::from_value(rez.data)?;
-            data.delete(self.apply, self.cfg.as_ref(), &name).await?;
+            let mut data: Data = rez.deserialize_as(&name)?;
+            let provider: &Data::Provider = self.cfg.as_ref();
+            provider.restore_settings(&name, &rez.provider_settings);
+            self.runner
+                .run(
+                    self.apply,
+                    Action::Delete,
+                    &name,
+                    Some(&rez.data),
+                    &mut data,
+                    |d, _| d.delete(self.apply, provider, &name),
+                )
+                .await?;
         } else {
             log::warn!("cannot delete {name} - no such resource");
         }
@@ -459,118 +1266,7 @@ impl<Config> Store<Config> {
     }
 
     pub fn save(&self, path: impl AsRef<std::path::Path>) -> anyhow::Result<()> {
-        std::fs::write(path, serde_json::to_string_pretty(&self.rez)?)?;
+        std::fs::write(path, file::encode(&self.rez)?)?;
         Ok(())
     }
 }
-
-pub mod cli {
-    //! Utility functions for adding teleform IaC to your command line program.
-    //!
-    //! This is useful for setting up your infrastructure as a subcommand of xtask,
-    //! for example.
-
-    use std::io::Read;
-
-    use anyhow::Context;
-
-    use crate::Store;
-
-    /// Attempt to find the cargo workspace directory by searching for Cargo.lock,
-    /// recursively up the filesystem tree.
-    pub fn find_workspace_dir() -> anyhow::Result<std::path::PathBuf> {
-        let mut workspace_dir = std::env::current_dir()?;
-        while !workspace_dir.join("Cargo.lock").is_file() {
-            let parent = workspace_dir
-                .parent()
-                .context("hit root dir while looking for the workspace!")?;
-            workspace_dir = parent.to_path_buf();
-        }
-        Ok(workspace_dir)
-    }
-
-    /// Create the infrastructure store, backed by a local file.
-    pub fn create_store<Cfg>(
-        store_path: impl AsRef<std::path::Path>,
-        backup_store_path: impl AsRef<std::path::Path>,
-        cfg: Cfg,
-        apply: bool,
-    ) -> anyhow::Result<Store<Cfg>> {
-        let store: Store<Cfg> = if store_path.as_ref().exists() {
-            log::debug!(
-                "found store file - exists at: {}",
-                store_path.as_ref().display()
-            );
-            Store::from_path(apply, cfg, store_path.as_ref()).context("cannot open store json")?
-        } else {
-            log::debug!("creating a new empty store");
-            Store::empty(apply, cfg)
-        };
-        if apply {
-            log::debug!("backing up to {}", backup_store_path.as_ref().display());
-            store.save(backup_store_path.as_ref())?;
-        }
-        Ok(store)
-    }
-
-    /// Display any resources that should be pruned.
-    /// Return whether there are resources to prune.
-    pub fn display_prunes<Cfg>(store: &Store<Cfg>) -> bool {
-        let unused_resources = store.get_prunes();
-        if !unused_resources.is_empty() {
-            log::warn!(
-                "will prune unused resources: \n{}",
-                unused_resources
-                    .iter()
-                    .map(|n| format!("  {n}"))
-                    .collect::<Vec<_>>()
-                    .join("\n")
-            );
-            true
-        } else {
-            log::info!("no resources to prune/delete");
-            false
-        }
-    }
-
-    /// Get confirmation to perform a delete command
-    pub fn confirm_delete(confirmation_message: &str) -> anyhow::Result<bool> {
-        log::info!("confirm deletion by entering \"{confirmation_message}\":");
-        let mut typing = String::new();
-        std::io::stdin().read_line(&mut typing)?;
-        if typing != format!("{confirmation_message}\n") {
-            log::info!("got \"{typing}\", so we're bailing");
-            return Ok(false);
-        }
-        Ok(true)
-    }
-
-    /// Returns the sha256 digest of the file at the given path *if it exists*.
-    /// If the file does _not_ exist it returns `Ok(None)`.
-    pub fn sha256_digest(path: impl AsRef<std::path::Path>) -> anyhow::Result<Option<String>> {
-        log::trace!("determining sha256 of {}", path.as_ref().display());
-        if !path.as_ref().exists() {
-            return Ok(None);
-        }
-
-        fn sha256<R: Read>(mut reader: R) -> anyhow::Result<ring::digest::Digest> {
-            let mut context = ring::digest::Context::new(&ring::digest::SHA256);
-            let mut buffer = [0; 1024];
-
-            loop {
-                let count = reader.read(&mut buffer)?;
-                if count == 0 {
-                    break;
-                }
-                context.update(&buffer[..count]);
-            }
-
-            Ok(context.finish())
-        }
-
-        let input = std::fs::File::open(path)?;
-        let reader = std::io::BufReader::new(input);
-        let digest = sha256(reader)?;
-        Ok(Some(data_encoding::HEXUPPER.encode(digest.as_ref())))
-    }
-}
@@ -0,0 +1,193 @@
+//! Lint rules on resource declarations.
+//!
+//! Lints are checked against every resource as it is synced, before anything
+//! is changed, and report [`Diagnostic`]s, eg that a bucket is public or that
+//! a resource name contains spaces. Warnings are logged and collected in
+//! [`Store::diagnostics`], and errors stop the sync:
+//!
+//! ```ignore
+//! store.add_lint(lint::ResourceNames);
+//! store.add_lint(tele::aws::s3::public_buckets());
+//! store.add_lint(lint::for_type::<Lambda>("L001", Severity::Warning, |lambda| {
+//!     lambda.timeout.is_none().then(|| "lambda has no timeout".to_string())
+//! }));
+//! ```
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::{Store, TeleSync};
+
+/// How bad a [`Diagnostic`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    /// Stops the sync of the resource.
+    Error,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// A problem with a resource declaration found by a [`Lint`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// A short code identifying the rule, eg "T001".
+    pub code: &'static str,
+    /// Name of the resource.
+    pub resource: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}[{}] {}: {}",
+            self.severity, self.code, self.resource, self.message
+        )
+    }
+}
+
+/// A lint rule.
+pub trait Lint: Send + Sync {
+    /// Check the resource with the given name, Rust type name and serialized
+    /// declaration.
+    fn check(&self, name: &str, type_is: &str, data: &Value) -> Vec<Diagnostic>;
+}
+
+impl<F> Lint for F
+where
+    F: Fn(&str, &str, &Value) -> Vec<Diagnostic> + Send + Sync,
+{
+    fn check(&self, name: &str, type_is: &str, data: &Value) -> Vec<Diagnostic> {
+        self(name, type_is, data)
+    }
+}
+
+/// Warns about resource names that contain whitespace, which are easy to
+/// misspell and awkward to target from the command line.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ResourceNames;
+
+impl Lint for ResourceNames {
+    fn check(&self, name: &str, _: &str, _: &Value) -> Vec<Diagnostic> {
+        if name.chars().any(char::is_whitespace) {
+            vec![Diagnostic {
+                severity: Severity::Warning,
+                code: "T001",
+                resource: name.to_string(),
+                message: "resource name contains whitespace".to_string(),
+            }]
+        } else {
+            vec![]
+        }
+    }
+}
+
+/// Returns a lint of the resources of type `Data`, which reports a diagnostic
+/// with the message returned by `f`, if any.
+pub fn for_type<Data: TeleSync>(
+    code: &'static str,
+    severity: Severity,
+    f: impl Fn(&Data) -> Option<String> + Send + Sync,
+) -> impl Lint {
+    move |name: &str, type_is: &str, data: &Value| {
+        if type_is != std::any::type_name::<Data>() {
+            return vec![];
+        }
+        Data::deserialize(data)
+            .ok()
+            .and_then(|data| f(&data))
+            .map(|message| Diagnostic {
+                severity,
+                code,
+                resource: name.to_string(),
+                message,
+            })
+            .into_iter()
+            .collect()
+    }
+}
+
+/// The lints of a store and the diagnostics they reported.
+#[derive(Default)]
+pub(crate) struct Lints {
+    lints: Vec<Arc<dyn Lint>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Lints {
+    /// Returns the same lints, without any diagnostics.
+    pub(crate) fn share(&self) -> Self {
+        Lints {
+            lints: self.lints.clone(),
+            diagnostics: vec![],
+        }
+    }
+
+    /// Check the resource with the given name against the store's lints.
+    ///
+    /// Errs if any lint reports an error.
+    pub(crate) fn check<Data: TeleSync>(&mut self, name: &str, data: &Data) -> anyhow::Result<()> {
+        if self.lints.is_empty() {
+            return Ok(());
+        }
+        let type_is = std::any::type_name::<Data>();
+        let value = serde_json::to_value(data)?;
+        let diagnostics = self
+            .lints
+            .iter()
+            .flat_map(|lint| lint.check(name, type_is, &value))
+            .collect::<Vec<_>>();
+        let errors = diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        for diagnostic in diagnostics.iter() {
+            match diagnostic.severity {
+                Severity::Info => log::info!("{diagnostic}"),
+                Severity::Warning => log::warn!("{diagnostic}"),
+                Severity::Error => log::error!("{diagnostic}"),
+            }
+        }
+        self.diagnostics.extend(diagnostics);
+        anyhow::ensure!(
+            errors.is_empty(),
+            "{name} failed linting:\n{}",
+            errors.join("\n")
+        );
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for Lints {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Lints")
+            .field("lints", &self.lints.len())
+            .field("diagnostics", &self.diagnostics)
+            .finish()
+    }
+}
+
+impl<Config> Store<Config> {
+    /// Check every synced resource with the given lint.
+    pub fn add_lint(&mut self, lint: impl Lint + 'static) {
+        self.lints.lints.push(Arc::new(lint));
+    }
+
+    /// Returns the diagnostics reported by lints so far.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.lints.diagnostics
+    }
+}
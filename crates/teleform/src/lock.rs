@@ -0,0 +1,110 @@
+//! A PID lock file that keeps two processes from using the same store at once.
+use std::{io::Write, path::PathBuf};
+
+use anyhow::Context;
+
+use crate::{Error, Store};
+
+/// A held lock on a store, released on drop.
+#[derive(Debug)]
+pub(crate) struct StoreLock {
+    path: PathBuf,
+}
+
+/// Returns whether the process with the given pid is running, if that can be
+/// determined.
+///
+/// On unix this sends the process the null signal. Elsewhere it can't be
+/// determined, so lock files are never considered stale and have to be
+/// removed by hand (see [`Error::StoreLocked`]).
+#[cfg(unix)]
+fn is_running(pid: u32) -> Option<bool> {
+    let pid = libc::pid_t::try_from(pid).ok()?;
+    // SAFETY: the null signal only checks that the process exists and that we
+    // may signal it, without affecting it
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        return Some(true);
+    }
+    match std::io::Error::last_os_error().raw_os_error() {
+        Some(libc::ESRCH) => Some(false),
+        // the process exists, but belongs to another user
+        Some(libc::EPERM) => Some(true),
+        _ => None,
+    }
+}
+
+#[cfg(not(unix))]
+fn is_running(_pid: u32) -> Option<bool> {
+    None
+}
+
+impl StoreLock {
+    /// Acquire the lock file at the given path.
+    ///
+    /// Stale lock files, left behind by processes that are no longer running,
+    /// are replaced.
+    pub(crate) fn acquire(path: PathBuf) -> anyhow::Result<Self> {
+        let pid = std::process::id();
+        for _ in 0..2 {
+            match std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(mut file) => {
+                    write!(file, "{pid}")?;
+                    log::trace!("acquired lock {}", path.display());
+                    return Ok(StoreLock { path });
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    let contents = std::fs::read_to_string(&path).unwrap_or_default();
+                    let holder = contents.trim().parse::<u32>().ok();
+                    match holder {
+                        Some(holder) if holder != pid && is_running(holder) == Some(false) => {
+                            log::warn!(
+                                "removing stale lock {} held by pid {holder}",
+                                path.display()
+                            );
+                            std::fs::remove_file(&path)?;
+                        }
+                        _ => return Err(Error::StoreLocked { path, pid: holder }.into()),
+                    }
+                }
+                Err(e) => {
+                    return Err(e)
+                        .with_context(|| format!("could not create lock {}", path.display()))
+                }
+            }
+        }
+        anyhow::bail!("could not acquire lock {}", path.display())
+    }
+}
+
+impl Drop for StoreLock {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            log::error!("could not remove lock {}: {e}", self.path.display());
+        }
+    }
+}
+
+impl<Config> Store<Config> {
+    /// Returns the path of this store's lock file.
+    pub fn lock_path(&self) -> PathBuf {
+        let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+        name.push(".lock");
+        self.path.with_file_name(name)
+    }
+
+    /// Lock this store for the lifetime of the store, if it isn't already.
+    ///
+    /// This happens automatically before the first resource is synced,
+    /// pruned or deleted. Errs with [`Error::StoreLocked`] if another process
+    /// holds the lock.
+    pub fn lock(&mut self) -> anyhow::Result<()> {
+        if self.lock.is_none() {
+            self.lock = Some(StoreLock::acquire(self.lock_path())?);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+//! Values looked up from a provider during apply.
+use std::future::Future;
+
+use anyhow::Context;
+
+use crate::{diff, Remote, Store};
+
+/// The type name that looked up values are stored with.
+pub(crate) const LOOKUP_TYPE: &str = "tele::lookup";
+
+impl<Config> Store<Config> {
+    /// Look up a value from the provider with `f`, eg the latest AMI, storing
+    /// it under the given name.
+    ///
+    /// This is a middle ground between a hard-coded [`Local`](crate::Local)
+    /// and a data source resource. When applying, the value is looked up
+    /// again and the store is updated. When planning, the value stored by the
+    /// last apply is returned, or [`Remote::Unknown`] before the first apply.
+    ///
+    /// The stored value can be depended on like any resource:
+    ///
+    /// ```ignore
+    /// let ami = store
+    ///     .resolve_with("latest-ami", |aws: &Aws| latest_ami(aws))
+    ///     .await?;
+    /// store.sync_by_name("server", Server { ami, ..Default::default() }).await?;
+    /// store.depends_on_by_name("server", ["latest-ami"])?;
+    /// ```
+    ///
+    /// Values that are no longer looked up are dropped from the store by the
+    /// next prune.
+    pub async fn resolve_with<T, F, Fut>(
+        &mut self,
+        name: impl Into<String>,
+        f: F,
+    ) -> anyhow::Result<Remote<T>>
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned,
+        F: FnOnce(&Config) -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        self.lock()?;
+        let name = name.into();
+        let cached = match self.rez.get_mut(&name) {
+            Some(rez) => {
+                anyhow::ensure!(
+                    rez.type_is.as_deref() == Some(LOOKUP_TYPE),
+                    "cannot look up {name} - a resource of that name is already stored"
+                );
+                rez.use_count += 1;
+                Some(rez.data.clone())
+            }
+            None => None,
+        };
+        if !self.apply || !self.is_targeted(&name) {
+            return Ok(match cached {
+                Some(cached) => Remote::Remote(T::deserialize(cached)?),
+                None => Remote::Unknown,
+            });
+        }
+        let value = f(&self.cfg)
+            .await
+            .with_context(|| format!("could not look up {name}"))?;
+        let data = serde_json::to_value(&value)?;
+        match cached.as_ref() {
+            None => log::info!("looked up {name}"),
+            Some(cached) if cached != &data => log::info!(
+                "looked up {name}, which changed:\n{}",
+                diff::render(&diff::diff(cached, &data), true)
+            ),
+            Some(_) => log::debug!("looked up {name}, which is unchanged"),
+        }
+        let rez = self.rez.entry(name).or_default();
+        rez.type_is = Some(LOOKUP_TYPE.to_string());
+        rez.data = data;
+        if cached.is_none() {
+            rez.use_count = 1;
+        }
+        self.save(&self.path)?;
+        Ok(Remote::Remote(value))
+    }
+
+    /// Drop the looked up values that are no longer looked up, see
+    /// [`Store::resolve_with`].
+    pub(crate) fn prune_lookups(&mut self) -> anyhow::Result<()> {
+        let mut pruned = false;
+        for name in self.get_prunes() {
+            if self
+                .rez
+                .get(&name)
+                .is_some_and(|rez| rez.type_is.as_deref() == Some(LOOKUP_TYPE))
+            {
+                log::warn!("forgetting looked up value {name}");
+                self.rez.remove(&name);
+                pruned = true;
+            }
+        }
+        if pruned && self.apply {
+            self.save(&self.path)?;
+        }
+        Ok(())
+    }
+}
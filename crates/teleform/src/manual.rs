@@ -0,0 +1,59 @@
+//! Manual steps that an operator completes by hand during apply.
+//!
+//! Some infrastructure can't be fully automated, eg approving an ACM
+//! validation email. A manual step pauses apply, prints instructions and
+//! polls a check until the operator has completed the step.
+use std::{future::Future, time::Duration};
+
+use crate::{Error, Store};
+
+/// How often the check of a manual step is polled.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long to wait for an operator to complete a manual step.
+pub const TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+impl<Config> Store<Config> {
+    /// Declare a manual step with the given id.
+    ///
+    /// When applying, `check` is polled until it returns `true`. If the step
+    /// isn't complete already, `description` is printed as instructions for
+    /// the operator. Errs with [`Error::Manual`] if the step isn't completed
+    /// within [`TIMEOUT`].
+    ///
+    /// When not applying the step is only logged.
+    pub async fn manual<F, Fut>(
+        &mut self,
+        id: impl Into<String>,
+        description: impl Into<String>,
+        mut check: F,
+    ) -> anyhow::Result<()>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = anyhow::Result<bool>>,
+    {
+        let id = id.into();
+        let description = description.into();
+        if !self.apply || !self.is_targeted(&id) {
+            log::info!("manual step {id}: {description}");
+            return Ok(());
+        }
+        if check().await? {
+            log::debug!("manual step {id} is already complete");
+            return Ok(());
+        }
+        eprintln!("manual step {id} - waiting for you to:\n  {description}");
+        let start = std::time::Instant::now();
+        loop {
+            crate::rt::sleep(POLL_INTERVAL).await;
+            if check().await? {
+                log::info!("...completed manual step {id}");
+                return Ok(());
+            }
+            if start.elapsed() >= TIMEOUT {
+                return Err(Error::Manual { id, description }.into());
+            }
+            log::debug!("still waiting on manual step {id}");
+        }
+    }
+}
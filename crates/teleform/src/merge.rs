@@ -0,0 +1,107 @@
+//! Merging independently constructed stores.
+use serde_json::Value;
+
+use crate::{diff, Rez, Store, TeleSync};
+
+impl<Config> Store<Config> {
+    /// Merge the resources of another store into this one.
+    ///
+    /// This lets large codebases split their infrastructure declarations
+    /// across crates, each building its own store, and then apply and prune
+    /// them together. Resources keep their recorded dependencies, so prunes
+    /// are ordered across the merged stores.
+    ///
+    /// Errs if both stores contain a resource of the same name with different
+    /// types or data, showing how they differ.
+    pub fn merge(&mut self, other: Store<Config>) -> anyhow::Result<()> {
+        for (name, rez) in other.rez.iter() {
+            if let Some(existing) = self.rez.get(name) {
+                anyhow::ensure!(
+                    existing.type_is == rez.type_is,
+                    "cannot merge stores - both contain {name}, as {} and {}",
+                    existing.type_is.as_deref().unwrap_or("an unknown type"),
+                    rez.type_is.as_deref().unwrap_or("an unknown type"),
+                );
+                anyhow::ensure!(
+                    existing.data == rez.data,
+                    "cannot merge stores - both contain a different {name}:\n{}",
+                    diff::unified(&existing.data, &rez.data)
+                );
+            }
+        }
+        for (name, rez) in other.rez {
+            match self.rez.get_mut(&name) {
+                Some(existing) => {
+                    existing.use_count += rez.use_count;
+                    existing.depends_on.extend(rez.depends_on);
+                    existing.references.extend(rez.references);
+                    existing.tags.extend(rez.tags);
+                    existing.last_applied = existing.last_applied.max(rez.last_applied);
+                }
+                None => {
+                    self.rez.insert(name, rez);
+                }
+            }
+        }
+        self.registry.extend(other.registry);
+        if self.apply {
+            self.save(&self.path)?;
+        }
+        Ok(())
+    }
+
+    /// Insert an IaC resource into the store, keeping the values already
+    /// stored.
+    ///
+    /// Unlike [`Store::insert`], which replaces the stored resource, this only
+    /// fills in the fields that are missing (or null) in the stored resource.
+    /// This resolves conflicts between a resource created outside of teleform
+    /// and its stored values without clobbering either.
+    pub fn insert_merge<Data>(&mut self, name: impl Into<String>, data: Data) -> anyhow::Result<()>
+    where
+        Config: AsRef<<Data as TeleSync>::Provider>,
+        Data: std::any::Any + TeleSync,
+    {
+        let name = name.into();
+        let provided = serde_json::to_value(&data)?;
+        let Some(existing) = self.rez.get_mut(&name) else {
+            return self.insert(name, data);
+        };
+        let mut merged = existing.data.clone();
+        fill_missing(&mut merged, provided);
+        let changes = diff::diff(&existing.data, &merged);
+        if !changes.is_empty() {
+            log::info!(
+                "filling in missing fields of {name}:\n{}",
+                diff::render(&changes, true)
+            );
+        }
+        let merged = Rez {
+            data: merged,
+            use_count: existing.use_count + 1,
+            ..existing.clone()
+        };
+        merged.deserialize_as::<Data>(&name)?;
+        *existing = merged;
+        Ok(())
+    }
+}
+
+/// Fill in the fields of `stored` that are missing or null with those of
+/// `provided`.
+fn fill_missing(stored: &mut Value, provided: Value) {
+    match (stored, provided) {
+        (Value::Object(stored), Value::Object(provided)) => {
+            for (key, value) in provided {
+                match stored.get_mut(&key) {
+                    Some(existing) => fill_missing(existing, value),
+                    None => {
+                        stored.insert(key, value);
+                    }
+                }
+            }
+        }
+        (stored @ Value::Null, provided) => *stored = provided,
+        _ => {}
+    }
+}
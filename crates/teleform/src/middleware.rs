@@ -0,0 +1,67 @@
+//! Middleware around resource actions.
+//!
+//! Middleware wraps the future of every create, read, update and delete, which
+//! is handy for rate limiting, logging, metrics, chaos testing or dry-run
+//! short-circuiting without modifying every resource implementation. See
+//! [`Store::with_middleware`].
+use std::{future::Future, pin::Pin};
+
+use crate::{Action, Store};
+
+/// A boxed action future.
+pub type BoxFuture<'a> = Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>>;
+
+/// What a middleware is wrapping.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActionContext {
+    /// Name of the resource.
+    pub name: String,
+    /// Rust type name of the resource.
+    pub type_is: &'static str,
+    pub action: Action,
+    /// Whether the store is applying.
+    pub apply: bool,
+}
+
+/// Wraps resource actions.
+pub trait Middleware: Send + Sync {
+    /// Wrap the future of an action.
+    ///
+    /// Implementations usually await `next` at some point, but may also
+    /// short-circuit it by returning without awaiting it.
+    fn wrap<'a>(&'a self, ctx: &ActionContext, next: BoxFuture<'a>) -> BoxFuture<'a>;
+}
+
+/// Middleware, outermost first.
+#[derive(Default)]
+pub(crate) struct Stack(Vec<Box<dyn Middleware>>);
+
+impl std::fmt::Debug for Stack {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Stack({} middleware)", self.0.len())
+    }
+}
+
+impl Stack {
+    /// Wrap the future of an action in all middleware.
+    pub(crate) fn wrap<'a>(
+        &'a self,
+        ctx: &ActionContext,
+        mut next: BoxFuture<'a>,
+    ) -> BoxFuture<'a> {
+        for middleware in self.0.iter().rev() {
+            next = middleware.wrap(ctx, next);
+        }
+        next
+    }
+}
+
+impl<Config> Store<Config> {
+    /// Wrap every resource action in the given middleware.
+    ///
+    /// Middleware added first is outermost.
+    pub fn with_middleware(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.runner.middleware.0.push(Box::new(middleware));
+        self
+    }
+}
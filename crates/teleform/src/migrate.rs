@@ -0,0 +1,60 @@
+//! Migrating stored resources between incompatible definitions.
+use anyhow::Context;
+
+use crate::{diff, Store, TeleSync};
+
+impl<Config> Store<Config> {
+    /// Rewrite the stored resource with the given name from an old definition
+    /// of its type to a new one.
+    ///
+    /// When the fields of a resource's type change shape, the resource stored
+    /// before the change no longer deserializes. Keep a copy of the old type,
+    /// then map the stored resource before syncing it:
+    ///
+    /// ```ignore
+    /// store.migrate_local::<LambdaV1, Lambda>("my-lambda", |old| Lambda {
+    ///     memory_size: old.memory.into(),
+    ///     ..Default::default()
+    /// })?;
+    /// ```
+    ///
+    /// A resource that already deserializes as the [schema
+    /// version](TeleSync::SCHEMA_VERSION) of `New` is left alone, so the call
+    /// can stay until every store has been migrated. The store file is
+    /// only written when applying.
+    pub fn migrate_local<Old, New>(
+        &mut self,
+        name: impl Into<String>,
+        f: impl FnOnce(Old) -> New,
+    ) -> anyhow::Result<()>
+    where
+        Old: serde::de::DeserializeOwned,
+        New: TeleSync,
+    {
+        let name = name.into();
+        let rez = self
+            .rez
+            .get_mut(&name)
+            .with_context(|| format!("no resource {name} in the store"))?;
+        if rez.schema_version == New::SCHEMA_VERSION && New::deserialize(&rez.data).is_ok() {
+            log::debug!("{name} is already a {}", std::any::type_name::<New>());
+            return Ok(());
+        }
+        let old: Old = rez
+            .deserialize_as(&name)
+            .with_context(|| format!("could not migrate {name}"))?;
+        let data = serde_json::to_value(f(old))?;
+        log::info!(
+            "migrating {name} to {}:\n{}",
+            std::any::type_name::<New>(),
+            diff::render(&diff::diff(&rez.data, &data), true)
+        );
+        rez.data = data;
+        rez.type_is = Some(std::any::type_name::<New>().to_string());
+        rez.schema_version = New::SCHEMA_VERSION;
+        if self.apply {
+            self.save(&self.path)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,119 @@
+//! Notifications of apply results.
+//!
+//! Notifiers are told when an apply completes or fails, eg to post deploy
+//! notifications to a chat channel. Add them to a command line program with
+//! [`AppConfig::with_notifier`](crate::cli::AppConfig::with_notifier).
+use crate::{
+    cli::{RunOutcome, RunSummary},
+    middleware::BoxFuture,
+};
+
+/// The result of an apply.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Notification {
+    /// The apply completed.
+    Applied {
+        program: String,
+        summary: RunSummary,
+    },
+    /// The apply failed.
+    Failed { program: String, error: String },
+    /// The infrastructure drifted from its IaC definition.
+    ///
+    /// See [`crate::daemon`].
+    Drifted {
+        program: String,
+        summary: RunSummary,
+    },
+}
+
+impl Notification {
+    /// Returns the notification of the given outcome of an apply by the named
+    /// program.
+    pub fn new(program: impl Into<String>, outcome: &RunOutcome) -> Self {
+        let program = program.into();
+        match outcome {
+            RunOutcome::NoChanges => Notification::Applied {
+                program,
+                summary: RunSummary::default(),
+            },
+            RunOutcome::ChangesPlanned(summary) | RunOutcome::Applied(summary) => {
+                Notification::Applied {
+                    program,
+                    summary: *summary,
+                }
+            }
+            RunOutcome::Error(e) => Notification::Failed {
+                program,
+                error: format!("{e:#}"),
+            },
+        }
+    }
+
+    /// Returns whether the apply failed.
+    pub fn is_failure(&self) -> bool {
+        matches!(self, Notification::Failed { .. })
+    }
+
+    /// Returns a one line description of the notification.
+    pub fn text(&self) -> String {
+        match self {
+            Notification::Applied { program, summary } => {
+                format!("{program}: apply complete - {summary}")
+            }
+            Notification::Failed { program, error } => format!("{program}: apply failed - {error}"),
+            Notification::Drifted { program, summary } => {
+                format!("{program}: drift detected - {summary}")
+            }
+        }
+    }
+}
+
+/// Told the result of every apply.
+pub trait Notifier: Send + Sync {
+    fn notify<'a>(&'a self, notification: &'a Notification) -> BoxFuture<'a>;
+}
+
+/// Posts notifications to a webhook, as Slack-compatible JSON with the
+/// notification's [text](Notification::text), ie `{"text": "..."}`.
+#[cfg(feature = "http")]
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    url: String,
+    client: reqwest::Client,
+}
+
+#[cfg(feature = "http")]
+impl Webhook {
+    pub fn new(url: impl Into<String>) -> Self {
+        Webhook {
+            url: url.into(),
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[cfg(feature = "http")]
+impl Notifier for Webhook {
+    fn notify<'a>(&'a self, notification: &'a Notification) -> BoxFuture<'a> {
+        Box::pin(async move {
+            let body = serde_json::json!({ "text": notification.text() });
+            self.client
+                .post(&self.url)
+                .json(&body)
+                .send()
+                .await?
+                .error_for_status()?;
+            Ok(())
+        })
+    }
+}
+
+/// Tell each notifier about the notification, logging any that fail.
+pub(crate) async fn notify_all(notifiers: &[Box<dyn Notifier>], notification: &Notification) {
+    for notifier in notifiers {
+        if let Err(e) = notifier.notify(notification).await {
+            log::error!("could not send notification: {e:#}");
+        }
+    }
+}
@@ -0,0 +1,150 @@
+//! Options of individual resources.
+//!
+//! Declare a resource's options with [`Store::resource_with_opts`] before
+//! syncing (or pruning) it:
+//!
+//! ```ignore
+//! let table = store.resource_with_opts::<Table>(
+//!     "users",
+//!     ResourceOptions::new()
+//!         .prevent_destroy()
+//!         .ignore_changes(["billing_mode"])
+//!         .retries(3)
+//!         .timeout(Duration::from_secs(300)),
+//! );
+//! store.sync(&table, Table { .. }).await?;
+//! ```
+//!
+//! Options aren't stored, so they only apply to runs that declare them.
+use std::{collections::BTreeSet, time::Duration};
+
+use serde_json::Value;
+
+use crate::{id::ResourceId, Action, Error, Store, TeleSync};
+
+/// How long to wait before retrying a failed action.
+pub const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Error codes providers report for throttled requests and transient
+/// failures, which succeed when sent again.
+const RETRYABLE_CODES: &[&str] = &[
+    "Throttling",
+    "ThrottlingException",
+    "ThrottledException",
+    "TooManyRequestsException",
+    "RequestLimitExceeded",
+    "ProvisionedThroughputExceededException",
+    "SlowDown",
+    "ServiceUnavailable",
+    "InternalFailure",
+    "InternalError",
+    "InternalServerError",
+    "PriorRequestNotComplete",
+];
+
+/// Returns whether the action that failed with the given error may be
+/// retried.
+///
+/// Throttled requests and transient failures of the provider are retryable,
+/// as are timed out actions - except creations, which may have created the
+/// resource before timing out. Teleform's own errors (eg an action that isn't
+/// implemented) and every other error of the provider (eg a validation error)
+/// are not.
+pub fn is_retryable(action: Action, error: &anyhow::Error) -> bool {
+    match error.downcast_ref::<Error>() {
+        Some(Error::Timeout { .. }) => action != Action::Create,
+        Some(_) => false,
+        None => {
+            // SDK errors only mention their code in their debug representation
+            let debug = format!("{error:?}");
+            RETRYABLE_CODES.iter().any(|code| debug.contains(code))
+        }
+    }
+}
+
+/// Options of one resource.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ResourceOptions {
+    /// How many times a failed action is retried, if its error is
+    /// [retryable](is_retryable).
+    pub retries: u32,
+    /// How long each attempt of an action may take.
+    pub timeout: Option<Duration>,
+    /// Err instead of deleting the resource, whether it is recreated, pruned
+    /// or destroyed.
+    pub prevent_destroy: bool,
+    /// Top-level fields whose changes are ignored - the stored values are
+    /// kept instead.
+    pub ignore_changes: BTreeSet<String>,
+}
+
+impl ResourceOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Retry actions that fail with a [retryable](is_retryable) error the
+    /// given number of times.
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Fail attempts of actions that take longer than the given duration.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Never delete the resource.
+    pub fn prevent_destroy(mut self) -> Self {
+        self.prevent_destroy = true;
+        self
+    }
+
+    /// Ignore changes to the given top-level fields.
+    pub fn ignore_changes(mut self, fields: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.ignore_changes
+            .extend(fields.into_iter().map(Into::into));
+        self
+    }
+
+    /// Returns `data` with the stored values of its ignored fields.
+    pub(crate) fn keep_ignored<Data: TeleSync>(
+        &self,
+        stored: &Value,
+        data: Data,
+    ) -> anyhow::Result<Data> {
+        if self.ignore_changes.is_empty() {
+            return Ok(data);
+        }
+        let mut value = serde_json::to_value(&data)?;
+        if let (Value::Object(fields), Value::Object(stored)) = (&mut value, stored) {
+            for field in self.ignore_changes.iter() {
+                if let Some(stored) = stored.get(field) {
+                    fields.insert(field.clone(), stored.clone());
+                }
+            }
+        }
+        Ok(Data::deserialize(value)?)
+    }
+}
+
+impl<Config> Store<Config> {
+    /// Returns the handle of the resource of type `Data` with the given name,
+    /// setting its options.
+    pub fn resource_with_opts<Data: TeleSync>(
+        &mut self,
+        name: impl Into<String>,
+        options: ResourceOptions,
+    ) -> ResourceId<Data> {
+        let name = name.into();
+        self.runner.options.insert(name.clone(), options);
+        ResourceId::new(name)
+    }
+
+    /// Returns the options of the resource with the given name.
+    pub fn options(&self, name: &str) -> Option<&ResourceOptions> {
+        self.runner.options.get(name)
+    }
+}
@@ -0,0 +1,204 @@
+//! What happens to orphans - stored resources that are no longer declared.
+//!
+//! By default orphans are deleted by the next prune, so removing a resource
+//! from the IaC definition removes it from the infrastructure. That makes
+//! it easy to destroy production by accidentally deleting a line of code, so
+//! the [`OrphanPolicy`] can be changed for the whole store, or for types
+//! whose names start with a given prefix:
+//!
+//! ```ignore
+//! store.orphan_policy(OrphanPolicy::Warn);
+//! store.orphan_policy_for("tele::aws::route53::", OrphanPolicy::Delete);
+//! ```
+//!
+//! Resources that are explicitly destroyed, eg with
+//! [`Store::destroy_cascade`], are always deleted.
+//!
+//! Declared resources sometimes need the outputs of a resource the next prune
+//! will delete, eg to detach from it first. [`Store::destroy_context`] returns
+//! its stored data while it still exists:
+//!
+//! ```ignore
+//! if let Some(dying) = store.destroy_context::<Bucket>("old-bucket")? {
+//!     let arn = dying.data.arn.maybe_ref();
+//!     // ...
+//! }
+//! ```
+//!
+//! Programs that prune by type can list the types once, dependents first,
+//! with [`prune_order!`](crate::prune_order).
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::Store;
+
+/// What to do with a stored resource that is no longer declared.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OrphanPolicy {
+    /// Delete it with the next prune.
+    #[default]
+    Delete,
+    /// Keep it, and warn about it when pruning.
+    Warn,
+    /// Keep it, and fail when pruning.
+    Error,
+    /// Keep it.
+    Ignore,
+}
+
+/// A stored resource that will be deleted by the next prune, with the data
+/// (including remote values) it was last stored with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DestroyContext<Data> {
+    pub name: String,
+    pub data: Data,
+    /// Names of the stored resources that depend on it.
+    pub dependents: Vec<String>,
+}
+
+/// The orphan policies of a store.
+#[derive(Debug, Default)]
+pub(crate) struct Orphans {
+    default: OrphanPolicy,
+    by_type: BTreeMap<String, OrphanPolicy>,
+    /// Names of resources scheduled for destruction, which are always deleted.
+    pub(crate) destroying: BTreeSet<String>,
+}
+
+impl Orphans {
+    /// Returns the policy for the resource with the given name and type,
+    /// using the policy of the longest matching type prefix.
+    pub(crate) fn policy(&self, name: &str, type_is: Option<&str>) -> OrphanPolicy {
+        if self.destroying.contains(name) {
+            return OrphanPolicy::Delete;
+        }
+        type_is
+            .and_then(|type_is| {
+                self.by_type
+                    .iter()
+                    .filter(|(prefix, _)| type_is.starts_with(prefix.as_str()))
+                    .max_by_key(|(prefix, _)| prefix.len())
+            })
+            .map(|(_, policy)| *policy)
+            .unwrap_or(self.default)
+    }
+
+    /// Returns the same policies, without any scheduled destruction.
+    pub(crate) fn policies(&self) -> Self {
+        Orphans {
+            default: self.default,
+            by_type: self.by_type.clone(),
+            destroying: Default::default(),
+        }
+    }
+}
+
+impl<Config> Store<Config> {
+    /// Set what happens to stored resources that are no longer declared.
+    pub fn orphan_policy(&mut self, policy: OrphanPolicy) {
+        self.orphans.default = policy;
+    }
+
+    /// Set what happens to stored resources that are no longer declared and
+    /// whose type names start with the given prefix, eg
+    /// `"tele::aws::dynamodb::"`.
+    ///
+    /// This takes precedence over the store's policy, and longer prefixes take
+    /// precedence over shorter ones.
+    pub fn orphan_policy_for(&mut self, type_prefix: impl Into<String>, policy: OrphanPolicy) {
+        self.orphans.by_type.insert(type_prefix.into(), policy);
+    }
+
+    /// Returns whether the unused resource with the given name should be
+    /// deleted.
+    pub(crate) fn is_deletable_orphan(&self, name: &str) -> bool {
+        let type_is = self.rez.get(name).and_then(|rez| rez.type_is.as_deref());
+        self.orphans.policy(name, type_is) == OrphanPolicy::Delete
+    }
+
+    /// Returns the stored resource with the given name if the next prune will
+    /// delete it, ie if it hasn't been declared so far and its orphan policy
+    /// allows deleting it.
+    ///
+    /// Call this after the resource would have been declared, or it will
+    /// look like an orphan.
+    pub fn destroy_context<Data: serde::de::DeserializeOwned>(
+        &self,
+        name: &str,
+    ) -> anyhow::Result<Option<DestroyContext<Data>>> {
+        let Some(rez) = self.rez.get(name) else {
+            return Ok(None);
+        };
+        if rez.use_count > 0 || !self.is_targeted(name) || !self.is_deletable_orphan(name) {
+            return Ok(None);
+        }
+        Ok(Some(DestroyContext {
+            name: name.to_string(),
+            data: rez.deserialize_as(name)?,
+            dependents: self.dependents(name),
+        }))
+    }
+
+    /// Warn about, or err on, the orphans of type `Data` that are kept.
+    pub(crate) fn check_orphans<Data>(&self) -> anyhow::Result<()> {
+        let type_is = std::any::type_name::<Data>();
+        let mut errors = vec![];
+        for (name, _) in self.rez.iter().filter(|(name, rez)| {
+            rez.use_count == 0 && self.is_targeted(name) && rez.type_is.as_deref() == Some(type_is)
+        }) {
+            match self.orphans.policy(name, Some(type_is)) {
+                OrphanPolicy::Warn => log::warn!("{name} is no longer declared - keeping it"),
+                OrphanPolicy::Error => errors.push(name.clone()),
+                OrphanPolicy::Delete | OrphanPolicy::Ignore => {}
+            }
+        }
+        anyhow::ensure!(
+            errors.is_empty(),
+            "resources are no longer declared: {errors:?}.\nDeclare them again, or destroy them \
+             explicitly (eg with `Store::destroy_cascade`)."
+        );
+        Ok(())
+    }
+}
+
+/// The output of [`prune_order!`], named so that it has a type even when
+/// every type is configured out.
+#[doc(hidden)]
+pub type PruneResult = anyhow::Result<()>;
+
+/// Prune the orphans of the given types, in the given order, until no more
+/// can be pruned.
+///
+/// Expands to a future of `anyhow::Result<()>`. Each pass prunes the types
+/// in order, and passes repeat while they make progress, so an orphan that
+/// was deferred because its dependents (of a later type) were still pending
+/// is pruned by the next pass. List dependents before the resources they
+/// depend on to prune everything in one pass:
+///
+/// ```ignore
+/// tele::prune_order!(&mut store, [Route, Stage, Integration, Api]).await?;
+/// ```
+///
+/// Types can be given attributes, eg to only prune them with a feature:
+///
+/// ```ignore
+/// tele::prune_order!(&mut store, [#[cfg(feature = "api")] Route, Table]).await?;
+/// ```
+#[macro_export]
+macro_rules! prune_order {
+    ($store:expr, [$($(#[$meta:meta])* $ty:ty),+ $(,)?]) => {
+        async {
+            let store: &mut $crate::Store<_> = $store;
+            loop {
+                let before = store.get_prunes().len();
+                $(
+                    $(#[$meta])*
+                    store.prune::<$ty>().await?;
+                )+
+                let after = store.get_prunes().len();
+                if after == 0 || after == before {
+                    return $crate::orphans::PruneResult::Ok(());
+                }
+            }
+        }
+    };
+}
@@ -0,0 +1,176 @@
+//! Terminal progress reporting during apply.
+//!
+//! Enabled with the `progress` feature.
+//!
+//! ```ignore
+//! let mut store = tele::cli::create_store(&store_path, cfg, apply)?;
+//! tele::progress::Progress::new().attach(&mut store);
+//! ```
+use std::{
+    collections::BTreeMap,
+    io::{IsTerminal, Write},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use colored::Colorize;
+
+use crate::{events::Event, Store};
+
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+#[derive(Debug)]
+struct State {
+    started: Instant,
+    in_flight: BTreeMap<String, (String, Instant)>,
+    finished: usize,
+    failed: usize,
+    frame: usize,
+    /// Number of in-flight lines currently drawn below the summary line.
+    drawn: usize,
+}
+
+impl State {
+    fn clear(&mut self, out: &mut impl Write) {
+        if self.drawn > 0 {
+            // move up over the lines we drew and clear to the end of the screen
+            let _ = write!(out, "\x1b[{}A\x1b[J", self.drawn);
+        }
+        self.drawn = 0;
+    }
+
+    fn draw(&mut self, out: &mut impl Write) {
+        self.clear(out);
+        self.frame = (self.frame + 1) % SPINNER.len();
+        let _ = writeln!(
+            out,
+            "{} {} done, {} failed, {} in flight [{}]",
+            "apply".bold(),
+            self.finished,
+            self.failed,
+            self.in_flight.len(),
+            elapsed(self.started.elapsed())
+        );
+        self.drawn += 1;
+        for (name, (action, started)) in self.in_flight.iter() {
+            let _ = writeln!(
+                out,
+                "  {} {action} {name} [{}]",
+                SPINNER[self.frame],
+                elapsed(started.elapsed())
+            );
+            self.drawn += 1;
+        }
+        let _ = out.flush();
+    }
+}
+
+fn elapsed(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{:02}:{:02}", secs / 60, secs % 60)
+}
+
+/// Renders a summary line for the apply and a spinner for each in-flight
+/// resource action on stderr, along with elapsed times.
+///
+/// When stderr is not a terminal a single line is printed as each action
+/// finishes instead.
+#[derive(Debug, Clone)]
+pub struct Progress {
+    state: Arc<Mutex<State>>,
+    is_terminal: bool,
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Progress {
+    pub fn new() -> Self {
+        Progress {
+            state: Arc::new(Mutex::new(State {
+                started: Instant::now(),
+                in_flight: Default::default(),
+                finished: 0,
+                failed: 0,
+                frame: 0,
+                drawn: 0,
+            })),
+            is_terminal: std::io::stderr().is_terminal(),
+        }
+    }
+
+    /// Render progress for the actions taken by the given store.
+    ///
+    /// With the `tokio` feature, when called within a tokio runtime, spinners
+    /// tick while actions are in flight.
+    pub fn attach<Config>(self, store: &mut Store<Config>) {
+        #[cfg(feature = "tokio")]
+        if self.is_terminal {
+            if let Ok(handle) = tokio::runtime::Handle::try_current() {
+                let state = Arc::downgrade(&self.state);
+                handle.spawn(async move {
+                    let mut interval = tokio::time::interval(Duration::from_millis(100));
+                    loop {
+                        interval.tick().await;
+                        let Some(state) = state.upgrade() else {
+                            break;
+                        };
+                        let mut state = state.lock().unwrap();
+                        if !state.in_flight.is_empty() {
+                            state.draw(&mut std::io::stderr());
+                        }
+                    }
+                });
+            }
+        }
+        store.on_event(move |event| self.handle(event));
+    }
+
+    /// Update the display with the given event.
+    pub fn handle(&self, event: &Event) {
+        let mut state = self.state.lock().unwrap();
+        let mut stderr = std::io::stderr();
+        match event {
+            Event::Started { name, action } => {
+                state
+                    .in_flight
+                    .insert(name.clone(), (action.to_string(), Instant::now()));
+            }
+            Event::Finished {
+                name,
+                action,
+                duration,
+            } => {
+                state.in_flight.remove(name);
+                state.finished += 1;
+                if self.is_terminal {
+                    state.clear(&mut stderr);
+                }
+                let _ = writeln!(
+                    stderr,
+                    "{} {action} {name} [{}]",
+                    "✓".green(),
+                    elapsed(*duration)
+                );
+            }
+            Event::Failed {
+                name,
+                action,
+                error,
+            } => {
+                state.in_flight.remove(name);
+                state.failed += 1;
+                if self.is_terminal {
+                    state.clear(&mut stderr);
+                }
+                let _ = writeln!(stderr, "{} {action} {name}: {error}", "✗".red());
+            }
+        }
+        if self.is_terminal {
+            state.draw(&mut stderr);
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! Providers of infrastructure, eg [`Aws`](crate::aws::Aws).
+//!
+//! Every [`TeleSync`](crate::TeleSync) resource names the provider it is
+//! created with. Providers can hook into the lifecycle of a run, most usefully
+//! to check their credentials before anything is changed, instead of an apply
+//! failing halfway through:
+//!
+//! ```ignore
+//! let mut store = Store::load(apply, aws, "store.json").await?;
+//! store.init().await?;
+//! store.healthcheck().await?;
+//! // declare resources...
+//! store.shutdown().await?;
+//! ```
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+
+use crate::{middleware::BoxFuture, Store};
+
+/// A provider of infrastructure.
+///
+/// All of the hooks do nothing by default.
+pub trait Provider: Send + Sync {
+    /// A human-readable name of the provider, used in error messages.
+    fn name(&self) -> String {
+        std::any::type_name::<Self>().to_string()
+    }
+
+    /// Prepare the provider before any resources are declared.
+    fn init(&self) -> BoxFuture<'_> {
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Check that the provider is usable, eg that its credentials are valid.
+    fn healthcheck(&self) -> BoxFuture<'_> {
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Clean up after all resources are declared.
+    fn shutdown(&self) -> BoxFuture<'_> {
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Returns the settings particular to the resource with the given name,
+    /// eg its region, to store along with it.
+    fn settings_of(&self, _name: &str) -> BTreeMap<String, String> {
+        BTreeMap::new()
+    }
+
+    /// Fall back to the stored settings of the resource with the given name,
+    /// before it is deleted or refreshed, in case it is no longer declared.
+    ///
+    /// Settings declared during the run should take precedence.
+    fn restore_settings(&self, _name: &str, _settings: &BTreeMap<String, String>) {}
+}
+
+impl<Config: Provider> Store<Config> {
+    /// Run the [`init`](Provider::init) hook of the store's provider.
+    pub async fn init(&self) -> anyhow::Result<()> {
+        self.cfg
+            .init()
+            .await
+            .with_context(|| format!("could not initialize {}", self.cfg.name()))
+    }
+
+    /// Run the [`healthcheck`](Provider::healthcheck) of the store's
+    /// provider.
+    pub async fn healthcheck(&self) -> anyhow::Result<()> {
+        self.cfg
+            .healthcheck()
+            .await
+            .with_context(|| format!("{} is unhealthy", self.cfg.name()))
+    }
+
+    /// Run the [`shutdown`](Provider::shutdown) hook of the store's provider.
+    pub async fn shutdown(&self) -> anyhow::Result<()> {
+        self.cfg
+            .shutdown()
+            .await
+            .with_context(|| format!("could not shut down {}", self.cfg.name()))
+    }
+}
@@ -0,0 +1,116 @@
+//! Refreshing stored resources from the provider.
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+
+use crate::{diff, Action, Provider, Store, TeleSync};
+
+impl<Config> Store<Config> {
+    /// Re-read the resource with the given name from the provider, updating
+    /// the store to match.
+    ///
+    /// A resource that no longer [exists](TeleSync::exists) is dropped from
+    /// the store, so it is created again by the next sync.
+    ///
+    /// The store file is only written when applying.
+    pub async fn refresh<Data>(&mut self, name: impl Into<String>) -> anyhow::Result<()>
+    where
+        Config: AsRef<Data::Provider>,
+        Data: TeleSync,
+    {
+        self.refresh_many::<Data>(vec![name.into()]).await
+    }
+
+    /// Re-read the resources of one type with the given names from the
+    /// provider with a single [`TeleSync::read_many`], updating the store to
+    /// match.
+    pub(crate) async fn refresh_many<Data>(&mut self, names: Vec<String>) -> anyhow::Result<()>
+    where
+        Config: AsRef<Data::Provider>,
+        Data: TeleSync,
+    {
+        let mut stored = BTreeMap::new();
+        let mut resources = BTreeMap::new();
+        for name in names {
+            let rez = self
+                .rez
+                .get(&name)
+                .with_context(|| format!("no resource {name} in the store"))?;
+            rez.check_schema_version::<Data>(&name)?;
+            resources.insert(name.clone(), rez.deserialize_as::<Data>(&name)?);
+            stored.insert(name, rez.data.clone());
+        }
+        let label = stored.keys().cloned().collect::<Vec<_>>().join(", ");
+        let provider: &Data::Provider = self.cfg.as_ref();
+        for name in stored.keys() {
+            provider.restore_settings(name, &self.rez[name].provider_settings);
+        }
+        self.runner
+            .run(
+                self.apply,
+                Action::Read,
+                &label,
+                Some(&serde_json::to_value(&stored)?),
+                &mut resources,
+                |resources, _| Data::read_many(resources, provider),
+            )
+            .await
+            .with_context(|| format!("could not read {label}"))?;
+
+        let mut changed = false;
+        for name in stored.into_keys() {
+            let Some(data) = resources.get(&name) else {
+                log::warn!("{name} was deleted outside of teleform - dropping it from the store");
+                self.rez.remove(&name);
+                changed = true;
+                continue;
+            };
+            let data = serde_json::to_value(data)?;
+            // UNWRAP: safe because we checked above
+            let rez = self.rez.get_mut(&name).unwrap();
+            let changes = diff::diff(&rez.data, &data);
+            if changes.is_empty() {
+                log::info!("{name} is up to date");
+            } else {
+                log::info!("refreshed {name}:\n{}", diff::render(&changes, true));
+                rez.data = data;
+                changed = true;
+            }
+        }
+        if changed && self.apply {
+            self.save(&self.path)?;
+        }
+        Ok(())
+    }
+
+    /// Re-read only the resources with the given names from the provider,
+    /// updating the store to match.
+    ///
+    /// This is a quick resync after tinkering with a few resources by hand,
+    /// without refreshing everything. The resources' types must be registered
+    /// (see [`Store::register`]), and must support reading. Resources of the
+    /// same type are read together with one [`TeleSync::read_many`].
+    pub async fn refresh_ids(
+        &mut self,
+        names: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> anyhow::Result<()> {
+        let mut by_type = BTreeMap::<String, (_, Vec<String>)>::new();
+        for name in names {
+            let name = name.as_ref().to_string();
+            let rez = self
+                .rez
+                .get(&name)
+                .with_context(|| format!("no resource {name} in the store"))?;
+            let refresh = self.registry.entry_of(rez, &name)?.refresh;
+            by_type
+                .entry(rez.type_is.clone().unwrap_or_default())
+                .or_insert_with(|| (refresh, vec![]))
+                .1
+                .push(name);
+        }
+        for (refresh, names) in by_type.into_values() {
+            refresh(self, names).await?;
+        }
+        Ok(())
+    }
+}
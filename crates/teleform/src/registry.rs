@@ -0,0 +1,120 @@
+//! A registry of resource types, for deleting resources by name alone.
+//!
+//! Deleting a resource requires knowing its Rust type, which may not be
+//! obvious (or even declared anywhere) once the resource is no longer part of
+//! the IaC definition. Types are registered automatically when resources of
+//! that type are synced, and can be registered explicitly with
+//! [`Store::register`].
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+
+use crate::{runner::ActionFuture, Store, TeleSync};
+
+type Erased<Config> = for<'a> fn(&'a mut Store<Config>, String) -> ActionFuture<'a>;
+type ErasedMany<Config> = for<'a> fn(&'a mut Store<Config>, Vec<String>) -> ActionFuture<'a>;
+
+fn delete_erased<Config, Data>(store: &mut Store<Config>, name: String) -> ActionFuture<'_>
+where
+    Config: AsRef<Data::Provider> + Send + Sync,
+    Data: TeleSync + 'static,
+{
+    Box::pin(store._delete::<Data>(name))
+}
+
+fn refresh_erased<Config, Data>(store: &mut Store<Config>, names: Vec<String>) -> ActionFuture<'_>
+where
+    Config: AsRef<Data::Provider> + Send + Sync,
+    Data: TeleSync + 'static,
+{
+    Box::pin(store.refresh_many::<Data>(names))
+}
+
+/// Type-erased actions on a registered type.
+pub(crate) struct Entry<Config> {
+    pub(crate) delete: Erased<Config>,
+    pub(crate) refresh: ErasedMany<Config>,
+}
+
+/// Type-erased actions, keyed by type name.
+pub(crate) struct Registry<Config>(BTreeMap<&'static str, Entry<Config>>);
+
+impl<Config> Default for Registry<Config> {
+    fn default() -> Self {
+        Registry(BTreeMap::new())
+    }
+}
+
+impl<Config> std::fmt::Debug for Registry<Config> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.0.keys()).finish()
+    }
+}
+
+impl<Config> Registry<Config> {
+    pub(crate) fn register<Data>(&mut self)
+    where
+        Config: AsRef<Data::Provider> + Send + Sync,
+        Data: TeleSync + 'static,
+    {
+        self.0
+            .entry(std::any::type_name::<Data>())
+            .or_insert(Entry {
+                delete: delete_erased::<Config, Data>,
+                refresh: refresh_erased::<Config, Data>,
+            });
+    }
+
+    /// Add the types registered with another registry.
+    pub(crate) fn extend(&mut self, other: Registry<Config>) {
+        for (type_is, entry) in other.0 {
+            self.0.entry(type_is).or_insert(entry);
+        }
+    }
+
+    /// Returns the entry of the stored type of the resource with the given name.
+    pub(crate) fn entry_of(&self, rez: &crate::Rez, name: &str) -> anyhow::Result<&Entry<Config>> {
+        let type_is = rez
+            .type_is
+            .as_deref()
+            .with_context(|| format!("the type of {name} is unknown"))?;
+        self.0.get(type_is).with_context(|| {
+            format!("type {type_is} of {name} is not registered - see Store::register")
+        })
+    }
+}
+
+impl<Config> Store<Config> {
+    /// Register a resource type, so resources of that type can be deleted with
+    /// [`Store::destroy_by_id`] and refreshed with [`Store::refresh_ids`].
+    pub fn register<Data>(&mut self)
+    where
+        Config: AsRef<Data::Provider> + Send + Sync,
+        Data: TeleSync + 'static,
+    {
+        self.registry.register::<Data>();
+    }
+
+    /// Returns the names of the registered resource types.
+    pub fn registered_types(&self) -> Vec<&'static str> {
+        self.registry.0.keys().copied().collect()
+    }
+
+    /// Delete the resource with the given name, looking up how to delete it
+    /// from its stored type.
+    ///
+    /// Errs if the resource's type hasn't been registered.
+    pub async fn destroy_by_id(&mut self, name: impl Into<String>) -> anyhow::Result<()> {
+        let name = name.into();
+        let rez = self
+            .rez
+            .get(&name)
+            .with_context(|| format!("no resource {name} in the store"))?;
+        let delete = self.registry.entry_of(rez, &name)?.delete;
+        delete(self, name).await?;
+        if self.apply {
+            self.save(&self.path)?;
+        }
+        Ok(())
+    }
+}
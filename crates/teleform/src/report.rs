@@ -0,0 +1,297 @@
+//! Metrics and timing of the actions taken by a store.
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::Duration,
+};
+
+use crate::{diff::Change, Action, Store};
+
+/// Metrics about one action taken on a resource.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ActionReport {
+    /// Name of the resource.
+    pub name: String,
+    /// Rust type name of the resource.
+    pub type_is: String,
+    pub action: Action,
+    /// Wall-clock time the action took.
+    pub duration: Duration,
+    /// Number of times the action was retried before it succeeded.
+    pub retries: u32,
+    /// Size of the resource's serialized data after the action.
+    pub bytes: usize,
+}
+
+/// Metrics about all the actions taken by a store.
+///
+/// Use [`Store::report`](crate::Store::report) to see which resources dominate
+/// your applies.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ApplyReport {
+    pub actions: Vec<ActionReport>,
+    /// Why each recreated or updated resource was touched, by resource name.
+    pub reasons: BTreeMap<String, Vec<String>>,
+    /// The changes made to each recreated or updated resource's definition,
+    /// by resource name.
+    #[serde(default)]
+    pub changes: BTreeMap<String, Vec<Change>>,
+    /// Summaries of the changes made to recreated or updated resources whose
+    /// types describe their own changes, by resource name.
+    ///
+    /// See [`TeleSync::describe_change`](crate::TeleSync::describe_change).
+    #[serde(default)]
+    pub descriptions: BTreeMap<String, String>,
+}
+
+impl ApplyReport {
+    pub(crate) fn record(&mut self, report: ActionReport) {
+        self.actions.push(report);
+    }
+
+    pub(crate) fn explain(
+        &mut self,
+        name: &str,
+        reasons: Vec<String>,
+        changes: Vec<Change>,
+        description: Option<String>,
+    ) {
+        self.reasons.insert(name.to_string(), reasons);
+        self.changes.insert(name.to_string(), changes);
+        if let Some(description) = description {
+            self.descriptions.insert(name.to_string(), description);
+        }
+    }
+
+    /// Returns why the resource with the given name was touched, if it was
+    /// recreated or updated.
+    pub fn reasons(&self, name: &str) -> &[String] {
+        self.reasons
+            .get(name)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Render why each recreated or updated resource was touched, one line
+    /// per resource, eg "lambda: field `zip_file_hash` changed".
+    pub fn render_reasons(&self) -> String {
+        self.reasons
+            .iter()
+            .filter(|(_, reasons)| !reasons.is_empty())
+            .map(|(name, reasons)| format!("{name}: {}", reasons.join(", ")))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Returns the changes made to the definition of the resource with the
+    /// given name, if it was recreated or updated.
+    pub fn changes(&self, name: &str) -> &[Change] {
+        self.changes
+            .get(name)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Returns the summary of the changes made to the resource with the given
+    /// name, if it was recreated or updated and its type describes its own
+    /// changes.
+    pub fn description(&self, name: &str) -> Option<&str> {
+        self.descriptions.get(name).map(String::as_str)
+    }
+
+    /// Total time spent in actions.
+    pub fn total_duration(&self) -> Duration {
+        self.actions.iter().map(|a| a.duration).sum()
+    }
+
+    /// The `n` slowest actions, slowest first.
+    pub fn slowest(&self, n: usize) -> Vec<&ActionReport> {
+        let mut actions = self.actions.iter().collect::<Vec<_>>();
+        actions.sort_by_key(|a| std::cmp::Reverse(a.duration));
+        actions.truncate(n);
+        actions
+    }
+
+    /// Number of actions of the given kind.
+    pub fn count(&self, action: Action) -> usize {
+        self.actions.iter().filter(|a| a.action == action).count()
+    }
+
+    /// Render the report in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let mut lines = vec![
+            "# HELP teleform_action_duration_seconds Time taken by a resource action.".to_string(),
+            "# TYPE teleform_action_duration_seconds gauge".to_string(),
+        ];
+        for a in self.actions.iter() {
+            lines.push(format!(
+                "teleform_action_duration_seconds{{{}}} {}",
+                labels(a),
+                a.duration.as_secs_f64()
+            ));
+        }
+        lines.push("# HELP teleform_action_retries Retries taken by a resource action.".into());
+        lines.push("# TYPE teleform_action_retries gauge".into());
+        for a in self.actions.iter() {
+            lines.push(format!(
+                "teleform_action_retries{{{}}} {}",
+                labels(a),
+                a.retries
+            ));
+        }
+        lines.push("# HELP teleform_resource_bytes Size of a resource's stored data.".into());
+        lines.push("# TYPE teleform_resource_bytes gauge".into());
+        for a in self.actions.iter() {
+            lines.push(format!(
+                "teleform_resource_bytes{{{}}} {}",
+                labels(a),
+                a.bytes
+            ));
+        }
+        lines.push(String::new());
+        lines.join("\n")
+    }
+}
+
+/// What a run did (or plans to do) to one resource.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResourceStatus {
+    Created,
+    Recreated,
+    Updated,
+    /// Deleted, or pending a prune.
+    Destroyed,
+    Unchanged,
+}
+
+impl ResourceStatus {
+    fn symbol(&self) -> &'static str {
+        match self {
+            ResourceStatus::Created => "+",
+            ResourceStatus::Recreated => "-/+",
+            ResourceStatus::Updated => "~",
+            ResourceStatus::Destroyed => "-",
+            ResourceStatus::Unchanged => "=",
+        }
+    }
+}
+
+/// The resources whose names share a prefix, eg all resources named
+/// `"api/..."`, and what a run did to them.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize)]
+pub struct ResourceGroup {
+    /// The shared prefix, without its trailing separator. Empty for resources
+    /// whose names have no separator.
+    pub prefix: String,
+    pub resources: BTreeMap<String, ResourceStatus>,
+}
+
+impl ResourceGroup {
+    /// Number of resources in the group with the given status.
+    pub fn count(&self, status: ResourceStatus) -> usize {
+        self.resources.values().filter(|s| **s == status).count()
+    }
+
+    /// Returns a summary of the group, eg "2 created, 1 updated, 10
+    /// unchanged", leaving out statuses no resource has.
+    pub fn summary(&self) -> String {
+        [
+            (ResourceStatus::Created, "created"),
+            (ResourceStatus::Recreated, "recreated"),
+            (ResourceStatus::Updated, "updated"),
+            (ResourceStatus::Destroyed, "destroyed"),
+            (ResourceStatus::Unchanged, "unchanged"),
+        ]
+        .into_iter()
+        .filter_map(|(status, label)| {
+            let n = self.count(status);
+            (n > 0).then(|| format!("{n} {label}"))
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+    }
+}
+
+impl<Config> Store<Config> {
+    /// Returns what this run did (or plans to do) to each resource, grouped
+    /// by the prefix of their names up to the last `separator`, eg "/".
+    pub fn grouped_report(&self, separator: &str) -> Vec<ResourceGroup> {
+        let prunes = self.get_prunes().into_iter().collect::<BTreeSet<_>>();
+        let names = self
+            .rez
+            .keys()
+            .chain(self.runner.report.actions.iter().map(|a| &a.name))
+            .collect::<BTreeSet<_>>();
+        let mut groups = BTreeMap::<String, ResourceGroup>::new();
+        for name in names {
+            let actions = self
+                .runner
+                .report
+                .actions
+                .iter()
+                .filter(|a| &a.name == name)
+                .map(|a| a.action)
+                .collect::<BTreeSet<_>>();
+            let status = if actions.contains(&Action::Delete) && actions.contains(&Action::Create) {
+                ResourceStatus::Recreated
+            } else if actions.contains(&Action::Create) {
+                ResourceStatus::Created
+            } else if actions.contains(&Action::Update) {
+                ResourceStatus::Updated
+            } else if actions.contains(&Action::Delete) || prunes.contains(name) {
+                ResourceStatus::Destroyed
+            } else {
+                ResourceStatus::Unchanged
+            };
+            let prefix = name
+                .rsplit_once(separator)
+                .map(|(prefix, _)| prefix.to_string())
+                .unwrap_or_default();
+            groups
+                .entry(prefix.clone())
+                .or_insert_with(|| ResourceGroup {
+                    prefix,
+                    ..Default::default()
+                })
+                .resources
+                .insert(name.clone(), status);
+        }
+        groups.into_values().collect()
+    }
+
+    /// Render [`Store::grouped_report`] for review, one line per resource
+    /// under a summary of each group.
+    ///
+    /// With `collapse_unchanged`, unchanged resources are left out, so only
+    /// the summaries of groups without changes are shown.
+    pub fn render_grouped_report(&self, separator: &str, collapse_unchanged: bool) -> String {
+        let mut lines = vec![];
+        for group in self.grouped_report(separator) {
+            let prefix = if group.prefix.is_empty() {
+                "(no prefix)"
+            } else {
+                group.prefix.as_str()
+            };
+            lines.push(format!("{prefix}: {}", group.summary()));
+            for (name, status) in group.resources.iter() {
+                if collapse_unchanged && *status == ResourceStatus::Unchanged {
+                    continue;
+                }
+                lines.push(format!("  {:>3} {name}", status.symbol()));
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+fn labels(a: &ActionReport) -> String {
+    fn escape(s: &str) -> String {
+        s.replace('\\', "\\\\").replace('"', "\\\"")
+    }
+    format!(
+        "resource=\"{}\",type=\"{}\",action=\"{}\"",
+        escape(&a.name),
+        escape(&a.type_is),
+        a.action
+    )
+}
@@ -0,0 +1,221 @@
+//! The async runtime touchpoints of teleform: sleeping, reading files and
+//! running blocking work.
+//!
+//! With the `tokio` feature (the default) these use tokio. Without it they
+//! are runtime-agnostic, so teleform can be embedded in programs that run
+//! another async runtime, or none at all.
+use std::{path::Path, time::Duration};
+
+/// Sleep for the given duration.
+#[cfg(feature = "tokio")]
+pub(crate) async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await
+}
+
+/// Sleep for the given duration, on the shared timer thread.
+#[cfg(not(feature = "tokio"))]
+pub(crate) async fn sleep(duration: Duration) {
+    Sleep::new(duration).await
+}
+
+/// Read the file at the given path.
+#[cfg(feature = "tokio")]
+pub(crate) async fn read(path: impl AsRef<Path>) -> std::io::Result<Vec<u8>> {
+    tokio::fs::read(path).await
+}
+
+/// Read the file at the given path, blocking.
+#[cfg(not(feature = "tokio"))]
+pub(crate) async fn read(path: impl AsRef<Path>) -> std::io::Result<Vec<u8>> {
+    std::fs::read(path)
+}
+
+/// Run blocking work without blocking the runtime.
+#[cfg(feature = "tokio")]
+pub(crate) async fn spawn_blocking<T: Send + 'static>(
+    f: impl FnOnce() -> T + Send + 'static,
+) -> anyhow::Result<T> {
+    Ok(tokio::task::spawn_blocking(f).await?)
+}
+
+/// Run blocking work in place.
+#[cfg(not(feature = "tokio"))]
+pub(crate) async fn spawn_blocking<T: Send + 'static>(
+    f: impl FnOnce() -> T + Send + 'static,
+) -> anyhow::Result<T> {
+    Ok(f())
+}
+
+/// A future that completes once its deadline passes on the [`Timer`] thread.
+#[cfg(not(feature = "tokio"))]
+struct Sleep {
+    duration: Duration,
+    /// The key of the sleep in the timer, once registered.
+    key: Option<(std::time::Instant, u64)>,
+    state: std::sync::Arc<std::sync::Mutex<SleepState>>,
+}
+
+#[cfg(not(feature = "tokio"))]
+#[derive(Default)]
+struct SleepState {
+    done: bool,
+    waker: Option<std::task::Waker>,
+}
+
+#[cfg(not(feature = "tokio"))]
+impl Sleep {
+    fn new(duration: Duration) -> Self {
+        Sleep {
+            duration,
+            key: None,
+            state: Default::default(),
+        }
+    }
+}
+
+#[cfg(not(feature = "tokio"))]
+impl std::future::Future for Sleep {
+    type Output = ();
+
+    fn poll(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<()> {
+        if self.key.is_none() {
+            let deadline = std::time::Instant::now() + self.duration;
+            self.key = Some(Timer::get().register(deadline, self.state.clone()));
+        }
+        // UNWRAP: safe because we never panic while holding the lock
+        let mut state = self.state.lock().unwrap();
+        if state.done {
+            std::task::Poll::Ready(())
+        } else {
+            state.waker = Some(cx.waker().clone());
+            std::task::Poll::Pending
+        }
+    }
+}
+
+#[cfg(not(feature = "tokio"))]
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some(key) = self.key {
+            Timer::get().cancel(key);
+        }
+    }
+}
+
+/// The one timer thread shared by every [`Sleep`], so that timeouts don't
+/// each hold a thread for their full duration.
+#[cfg(not(feature = "tokio"))]
+#[derive(Default)]
+struct Timer {
+    sleeps: std::sync::Mutex<Sleeps>,
+    changed: std::sync::Condvar,
+}
+
+/// Pending sleeps by deadline, and a counter that tells apart those with the
+/// same deadline.
+#[cfg(not(feature = "tokio"))]
+#[derive(Default)]
+struct Sleeps {
+    next_id: u64,
+    by_deadline: std::collections::BTreeMap<
+        (std::time::Instant, u64),
+        std::sync::Arc<std::sync::Mutex<SleepState>>,
+    >,
+}
+
+#[cfg(not(feature = "tokio"))]
+impl Timer {
+    /// Returns the timer, starting its thread on first use.
+    fn get() -> &'static Timer {
+        static TIMER: std::sync::OnceLock<Timer> = std::sync::OnceLock::new();
+        TIMER.get_or_init(|| {
+            // the thread waits for the timer to be initialized before running
+            std::thread::spawn(|| Timer::get().run());
+            Timer::default()
+        })
+    }
+
+    fn register(
+        &self,
+        deadline: std::time::Instant,
+        state: std::sync::Arc<std::sync::Mutex<SleepState>>,
+    ) -> (std::time::Instant, u64) {
+        // UNWRAP: safe because we never panic while holding the lock
+        let mut sleeps = self.sleeps.lock().unwrap();
+        let key = (deadline, sleeps.next_id);
+        sleeps.next_id += 1;
+        sleeps.by_deadline.insert(key, state);
+        self.changed.notify_one();
+        key
+    }
+
+    fn cancel(&self, key: (std::time::Instant, u64)) {
+        // UNWRAP: safe because we never panic while holding the lock
+        self.sleeps.lock().unwrap().by_deadline.remove(&key);
+    }
+
+    /// Wake each sleep once its deadline passes, forever.
+    fn run(&self) {
+        // UNWRAP: safe because we never panic while holding the lock
+        let mut sleeps = self.sleeps.lock().unwrap();
+        loop {
+            let now = std::time::Instant::now();
+            while let Some(entry) = sleeps.by_deadline.first_entry() {
+                if entry.key().0 > now {
+                    break;
+                }
+                let state = entry.remove();
+                // UNWRAP: safe because we never panic while holding the lock
+                let mut state = state.lock().unwrap();
+                state.done = true;
+                if let Some(waker) = state.waker.take() {
+                    waker.wake();
+                }
+            }
+            // UNWRAP: safe because we never panic while holding the lock
+            sleeps = match sleeps.by_deadline.keys().next() {
+                Some((deadline, _)) => {
+                    let wait = deadline.saturating_duration_since(now);
+                    self.changed.wait_timeout(sleeps, wait).unwrap().0
+                }
+                None => self.changed.wait(sleeps).unwrap(),
+            };
+        }
+    }
+}
+
+/// Run the future until it completes or the given duration passes, returning
+/// `None` if it timed out.
+#[cfg(feature = "tokio")]
+pub(crate) async fn timeout<F: std::future::Future>(
+    duration: Duration,
+    future: F,
+) -> Option<F::Output> {
+    tokio::time::timeout(duration, future).await.ok()
+}
+
+/// Run the future until it completes or the given duration passes, returning
+/// `None` if it timed out.
+#[cfg(not(feature = "tokio"))]
+pub(crate) async fn timeout<F: std::future::Future>(
+    duration: Duration,
+    future: F,
+) -> Option<F::Output> {
+    use std::future::Future;
+
+    let mut future = std::pin::pin!(future);
+    let mut sleep = std::pin::pin!(Sleep::new(duration));
+    std::future::poll_fn(|cx| {
+        if let std::task::Poll::Ready(output) = future.as_mut().poll(cx) {
+            std::task::Poll::Ready(Some(output))
+        } else if sleep.as_mut().poll(cx).is_ready() {
+            std::task::Poll::Ready(None)
+        } else {
+            std::task::Poll::Pending
+        }
+    })
+    .await
+}
@@ -0,0 +1,227 @@
+//! Bookkeeping done around every resource action.
+use tracing::Instrument;
+
+use crate::{
+    approval::{Approval, Callback, Interactive, PendingAction},
+    audit::AuditEntry,
+    credentials::{self, Refresh},
+    debug::{Recorder, TranscriptEntry},
+    events::{Event, Listeners},
+    middleware::{ActionContext, Stack},
+    options::{self, ResourceOptions, RETRY_DELAY},
+    Action, ActionReport, ApplyReport, Error,
+};
+
+/// A boxed action future, as returned by the `TeleSync` methods.
+pub(crate) type ActionFuture<'a> = crate::middleware::BoxFuture<'a>;
+
+/// Witness that `'p` outlives `'b`.
+///
+/// Passing this to action closures lets them return futures that borrow
+/// from the provider (which lives for `'p`) without requiring `'p: 'static`.
+pub(crate) type Outlives<'b, 'p> = std::marker::PhantomData<&'b &'p ()>;
+
+/// Runs resource actions on behalf of a store.
+#[derive(Debug, Default)]
+pub(crate) struct Runner {
+    pub(crate) report: ApplyReport,
+    pub(crate) audit_log: Option<std::path::PathBuf>,
+    pub(crate) changelog: Option<std::path::PathBuf>,
+    pub(crate) interactive: Interactive,
+    pub(crate) approval: Option<Callback>,
+    pub(crate) listeners: Listeners,
+    pub(crate) refresh: Option<Refresh>,
+    pub(crate) middleware: Stack,
+    /// Options of resources, by name.
+    pub(crate) options: std::collections::BTreeMap<String, ResourceOptions>,
+    pub(crate) transcript: Option<Recorder>,
+}
+
+impl Runner {
+    /// Ask for approval of an action on the resource with the given name.
+    ///
+    /// Returns whether the action should go ahead. Actions are always approved
+    /// when not applying. Without an approval callback only destructive
+    /// actions are put to the (possibly interactive) prompt.
+    pub(crate) async fn approve(
+        &mut self,
+        apply: bool,
+        action: &'static str,
+        name: &str,
+    ) -> anyhow::Result<bool> {
+        if !apply {
+            return Ok(true);
+        }
+        let pending = PendingAction {
+            name: name.to_string(),
+            action,
+        };
+        let approval = if let Some(callback) = self.approval.as_ref() {
+            callback.call(pending).await?
+        } else if pending.is_destructive() {
+            self.interactive.prompt(&format!("{action} {name}?"))?
+        } else {
+            Approval::Approve
+        };
+        match approval {
+            Approval::Approve => Ok(true),
+            Approval::Skip => {
+                log::warn!("skipping {action} of {name}");
+                Ok(false)
+            }
+            Approval::Abort => anyhow::bail!("aborted before {action} of {name}"),
+        }
+    }
+
+    /// Errs if the resource with the given name must not be deleted, see
+    /// [`ResourceOptions::prevent_destroy`].
+    pub(crate) fn check_prevent_destroy(&self, name: &str) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            !self.options.get(name).is_some_and(|o| o.prevent_destroy),
+            "{name} would be deleted, but prevents being destroyed.\nRemove `prevent_destroy` \
+             from its options to delete it."
+        );
+        Ok(())
+    }
+
+    /// Run one action on `data`, the resource stored as `stored` (if it is
+    /// stored yet).
+    ///
+    /// The action runs inside a `tracing` span that records the resource name,
+    /// its type, the action and (once the action completes) its duration in
+    /// milliseconds, wrapped in the store's middleware. Successful actions are
+    /// recorded in the report, and when applying, in the audit log.
+    ///
+    /// Actions that fail because their credentials expired are retried, see
+    /// [`crate::credentials`], as are actions of resources with
+    /// [retries](ResourceOptions::retries) that fail with a
+    /// [retryable](options::is_retryable) error.
+    pub(crate) async fn run<'p, Data: serde::Serialize>(
+        &mut self,
+        apply: bool,
+        action: Action,
+        name: &str,
+        stored: Option<&serde_json::Value>,
+        data: &mut Data,
+        mut f: impl for<'b> FnMut(&'b mut Data, Outlives<'b, 'p>) -> ActionFuture<'b>,
+    ) -> anyhow::Result<()> {
+        let type_is = std::any::type_name::<Data>();
+        let input_hash = crate::sha256_hex(&serde_json::to_vec(data)?);
+        let old_hash = match stored {
+            Some(stored) => crate::sha256_hex(&serde_json::to_vec(stored)?),
+            None => String::new(),
+        };
+        let span = tracing::info_span!(
+            "action",
+            resource = name,
+            type_is,
+            action = action.as_str(),
+            duration_ms = tracing::field::Empty,
+        );
+        self.listeners.emit(Event::Started {
+            name: name.to_string(),
+            action,
+        });
+        let ctx = ActionContext {
+            name: name.to_string(),
+            type_is,
+            action,
+            apply,
+        };
+        let start = std::time::Instant::now();
+        let options = self.options.get(name).cloned().unwrap_or_default();
+        let mut retries = 0;
+        let mut failures = 0;
+        let result = loop {
+            let attempt = self
+                .middleware
+                .wrap(&ctx, f(data, Default::default()))
+                .instrument(span.clone());
+            let result = match options.timeout {
+                Some(timeout) => crate::rt::timeout(timeout, attempt)
+                    .await
+                    .unwrap_or_else(|| {
+                        Err(Error::Timeout {
+                            what: format!("{action} of {name}"),
+                            timeout,
+                        }
+                        .into())
+                    }),
+                None => attempt.await,
+            };
+            match result {
+                Err(e) if retries < credentials::MAX_RETRIES && credentials::is_expired(&e) => {
+                    retries += 1;
+                    log::warn!("credentials expired during {action} of {name} - retrying");
+                    if let Some(refresh) = self.refresh.as_mut() {
+                        (refresh.0)().await?;
+                    }
+                }
+                Err(e) if failures < options.retries && options::is_retryable(action, &e) => {
+                    retries += 1;
+                    failures += 1;
+                    log::warn!("{action} of {name} failed - retrying: {e:#}");
+                    crate::rt::sleep(RETRY_DELAY).await;
+                }
+                result => break result,
+            }
+        };
+        let duration = start.elapsed();
+        span.record("duration_ms", duration.as_millis() as u64);
+        if let Some(transcript) = self.transcript.as_mut() {
+            let entry = TranscriptEntry {
+                seq: 0,
+                timestamp: crate::now_secs(),
+                resource: name.to_string(),
+                type_is: type_is.to_string(),
+                action,
+                apply,
+                duration,
+                retries,
+                input_hash,
+                output_hash: match result.as_ref() {
+                    Ok(()) => Some(crate::sha256_hex(&serde_json::to_vec(data)?)),
+                    Err(_) => None,
+                },
+                error: result.as_ref().err().map(|e| format!("{e:#}")),
+            };
+            if let Err(e) = transcript.record(entry) {
+                log::error!("could not write to the transcript: {e}");
+            }
+        }
+        if let Err(e) = result.as_ref() {
+            span.in_scope(|| tracing::error!("{action} {name} failed: {e}"));
+            self.listeners.emit(Event::Failed {
+                name: name.to_string(),
+                action,
+                error: e.to_string(),
+            });
+        }
+        result?;
+        self.listeners.emit(Event::Finished {
+            name: name.to_string(),
+            action,
+            duration,
+        });
+
+        let bytes = serde_json::to_vec(data)?;
+        if apply {
+            if let Some(path) = self.audit_log.as_ref() {
+                let entry =
+                    AuditEntry::new(name, type_is, action, old_hash, crate::sha256_hex(&bytes));
+                if let Err(e) = entry.append(path) {
+                    log::error!("could not write to audit log {}: {e}", path.display());
+                }
+            }
+        }
+        self.report.record(ActionReport {
+            name: name.to_string(),
+            type_is: type_is.to_string(),
+            action,
+            duration,
+            retries,
+            bytes: bytes.len(),
+        });
+        Ok(())
+    }
+}
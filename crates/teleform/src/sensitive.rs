@@ -0,0 +1,136 @@
+//! Secret values that are kept out of human output.
+//!
+//! Wrap a value in [`Sensitive`] - or mark a remote value with
+//! [`Remote::redact`] - and it shows as `***` in logs, plan diffs, reports
+//! and `Debug` output, while it is stored and used by resources as usual:
+//!
+//! ```ignore
+//! pub struct Database {
+//!     pub connection_string: Remote<Sensitive<String>>,
+//! }
+//!
+//! let url = db.connection_string.maybe_ref().context("unknown connection")?;
+//! lambda.environment.insert("DATABASE_URL".into(), url.as_str().into());
+//! ```
+//!
+//! Sensitive values are stored as `{"$sensitive": value}`, which is how
+//! [`redact`] finds them in a resource's JSON. Plain values stored before a
+//! field became sensitive are still read.
+use std::ops::Deref;
+
+use serde_json::Value;
+
+use crate::{Remote, TeleEither};
+
+/// The key sensitive values are stored under.
+pub const MARKER: &str = "$sensitive";
+
+/// What sensitive values are shown as.
+pub const REDACTED: &str = "***";
+
+/// A secret value, shown as `***`.
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub struct Sensitive<T>(pub T);
+
+impl<T> Sensitive<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Sensitive<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> From<T> for Sensitive<T> {
+    fn from(value: T) -> Self {
+        Sensitive(value)
+    }
+}
+
+/// The `TeleEither` implementation for `Sensitive` picks like its value.
+impl<T: TeleEither> TeleEither for Sensitive<T> {
+    fn either(self, other: Self) -> Self {
+        Sensitive(self.0.either(other.0))
+    }
+}
+
+impl<T> std::fmt::Debug for Sensitive<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Sensitive({REDACTED})")
+    }
+}
+
+impl<T> std::fmt::Display for Sensitive<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl<T: serde::Serialize> serde::Serialize for Sensitive<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeMap;
+
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(MARKER, &self.0)?;
+        map.end()
+    }
+}
+
+impl<'de, T: serde::de::Deserialize<'de>> serde::de::Deserialize<'de> for Sensitive<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(serde::Deserialize)]
+        #[serde(untagged)]
+        enum Stored<T> {
+            Marked {
+                #[serde(rename = "$sensitive")]
+                value: T,
+            },
+            Plain(T),
+        }
+        Ok(Sensitive(match Stored::deserialize(deserializer)? {
+            Stored::Marked { value } | Stored::Plain(value) => value,
+        }))
+    }
+}
+
+impl<T> Remote<T> {
+    /// Mark the value as sensitive, see [`Sensitive`].
+    pub fn redact(self) -> Remote<Sensitive<T>> {
+        match self {
+            Remote::Unknown => Remote::Unknown,
+            Remote::Remote(value) => Remote::Remote(Sensitive(value)),
+        }
+    }
+}
+
+/// Returns whether the JSON is a stored [`Sensitive`] value.
+pub fn is_sensitive(value: &Value) -> bool {
+    matches!(value, Value::Object(fields) if fields.len() == 1 && fields.contains_key(MARKER))
+}
+
+/// Returns the JSON with its [`Sensitive`] values replaced by `"***"`, for
+/// human output.
+pub fn redact(value: &Value) -> Value {
+    match value {
+        value if is_sensitive(value) => Value::String(REDACTED.to_string()),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, value)| (key.clone(), redact(value)))
+                .collect(),
+        ),
+        Value::Array(values) => Value::Array(values.iter().map(redact).collect()),
+        value => value.clone(),
+    }
+}
@@ -0,0 +1,100 @@
+//! State history and snapshots, with rollback to a point in time.
+//!
+//! Before an applying store mutates anything it snapshots its store file into a
+//! `<store name>.snapshots` directory next to it, keeping the most recent
+//! generations. A bad apply's state changes can then be reverted with
+//! [`Store::restore_snapshot`].
+use anyhow::Context;
+
+use crate::Store;
+
+/// The default number of snapshot generations to keep.
+pub const DEFAULT_GENERATIONS: usize = 10;
+
+impl<Config> Store<Config> {
+    /// Returns the directory snapshots of this store are kept in.
+    pub fn snapshot_dir(&self) -> std::path::PathBuf {
+        let stem = self
+            .path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "store".to_string());
+        self.path.with_file_name(format!("{stem}.snapshots"))
+    }
+
+    /// Set the number of snapshot generations to keep.
+    pub fn set_snapshot_generations(&mut self, generations: usize) {
+        self.snapshot_generations = generations;
+    }
+
+    /// Snapshot the current state of the store, returning the snapshot's timestamp
+    /// in milliseconds since the UNIX epoch.
+    ///
+    /// Snapshots older than the configured number of generations are removed.
+    pub fn snapshot(&self) -> anyhow::Result<u128> {
+        let dir = self.snapshot_dir();
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("could not create snapshot dir {}", dir.display()))?;
+        let mut ts = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_millis();
+        while dir.join(format!("{ts}.json")).exists() {
+            ts += 1;
+        }
+        let path = dir.join(format!("{ts}.json"));
+        log::debug!("snapshotting store to {}", path.display());
+        self.save(&path)?;
+
+        let snapshots = self.snapshots()?;
+        let excess = snapshots.len().saturating_sub(self.snapshot_generations);
+        for old in snapshots.into_iter().take(excess) {
+            log::trace!("removing old snapshot {old}");
+            std::fs::remove_file(dir.join(format!("{old}.json")))?;
+        }
+        Ok(ts)
+    }
+
+    /// Returns the timestamps of all snapshots, oldest first.
+    pub fn snapshots(&self) -> anyhow::Result<Vec<u128>> {
+        let dir = self.snapshot_dir();
+        if !dir.is_dir() {
+            return Ok(vec![]);
+        }
+        let mut snapshots = vec![];
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .map(|ext| ext == "json")
+                .unwrap_or_default()
+            {
+                if let Some(ts) = path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .and_then(|stem| stem.parse::<u128>().ok())
+                {
+                    snapshots.push(ts);
+                }
+            }
+        }
+        snapshots.sort();
+        Ok(snapshots)
+    }
+
+    /// Restore the store to the snapshot with the given timestamp.
+    ///
+    /// This only reverts the store's state, it does not touch any infrastructure.
+    /// The store file is rewritten only if the store is applying.
+    pub fn restore_snapshot(&mut self, ts: u128) -> anyhow::Result<()> {
+        let path = self.snapshot_dir().join(format!("{ts}.json"));
+        let bytes =
+            std::fs::read(&path).with_context(|| format!("no such snapshot {}", path.display()))?;
+        let rez = crate::file::decode(&path, &bytes)?;
+        log::warn!("restoring store to snapshot {ts}");
+        self.rez = rez;
+        if self.apply {
+            self.save(&self.path)?;
+        }
+        Ok(())
+    }
+}
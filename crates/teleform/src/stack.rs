@@ -0,0 +1,76 @@
+//! Stacks partition one program's resources into separately stored groups.
+//!
+//! Each stack is a store of its own, saved next to the parent store (eg
+//! `store.network.json` for the "network" stack of `store.json`). Stacks
+//! are planned and applied independently - only the stacks that are synced
+//! are touched - so slow-moving base infrastructure isn't replanned on every
+//! application deploy. Stacks can read each other's resources with
+//! [`Store::stack_output`].
+use std::path::PathBuf;
+
+use anyhow::Context;
+
+use crate::{file, Store, TeleSync};
+
+impl<Config> Store<Config> {
+    /// Returns the path of the store file of the stack with the given name.
+    pub fn stack_path(&self, name: &str) -> PathBuf {
+        let stem = self
+            .path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "store".to_string());
+        self.path.with_file_name(format!("{stem}.{name}.json"))
+    }
+
+    /// Open the stack with the given name, creating it if need be.
+    ///
+    /// The stack shares this store's config, settings, targets, orphan
+    /// policies, resource options, lints and approval (the approval callback
+    /// or interactive prompt), and applies if this store applies. It is held
+    /// to this store's change budget, counted separately from this store's
+    /// changes.
+    ///
+    /// It is locked and saved independently. The stack has no backend of its
+    /// own - its state is published along with this store's by
+    /// [`Store::push`].
+    pub fn stack(&self, name: &str) -> anyhow::Result<Store<Config>>
+    where
+        Config: Clone,
+    {
+        let path = self.stack_path(name);
+        let mut stack = if path.is_file() {
+            Store::from_path(self.apply, self.cfg.clone(), &path)
+                .with_context(|| format!("could not open stack {name}"))?
+        } else {
+            Store::new(self.apply, self.cfg.clone(), &path)
+        };
+        stack.settings = self.settings.clone();
+        stack.targets = self.targets.clone();
+        stack.orphans = self.orphans.policies();
+        stack.snapshot_generations = self.snapshot_generations;
+        stack.budget = self.budget.fresh();
+        stack.lints = self.lints.share();
+        stack.runner.options = self.runner.options.clone();
+        stack.runner.interactive = self.runner.interactive.like();
+        stack.runner.approval = self.runner.approval.clone();
+        Ok(stack)
+    }
+
+    /// Read the resource with the given name from the stack with the given
+    /// name, eg to reference the outputs of a base stack while syncing an
+    /// application stack.
+    ///
+    /// The stack's store file is read as it is on disk, without locking it.
+    pub fn stack_output<Data: TeleSync>(&self, stack: &str, name: &str) -> anyhow::Result<Data> {
+        let path = self.stack_path(stack);
+        let bytes = std::fs::read(&path)
+            .with_context(|| format!("could not read stack {stack} at {}", path.display()))?;
+        let rez = file::decode(&path, &bytes)?;
+        let rez = rez
+            .get(name)
+            .with_context(|| format!("no resource {name} in stack {stack}"))?;
+        rez.deserialize_as(name)
+            .with_context(|| format!("could not read {name} of stack {stack}"))
+    }
+}
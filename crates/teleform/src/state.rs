@@ -0,0 +1,154 @@
+//! Comparing stored states, eg staging against production, or the snapshots
+//! taken before and after an apply.
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use colored::Colorize;
+use serde_json::Value;
+
+use crate::{diff::Change, file, sensitive::redact, Rez};
+
+/// How one resource differs between two states.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ResourceDiff {
+    /// The resource is only in the second state.
+    Added { data: Value },
+    /// The resource is only in the first state.
+    Removed { data: Value },
+    /// The resource is in both states, with these differences.
+    Changed { changes: Vec<Change> },
+}
+
+/// The differences between two states, keyed by resource name.
+///
+/// Resources of directories of stores are named `<store file>/<resource>`.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize)]
+pub struct StateDiff {
+    pub resources: BTreeMap<String, ResourceDiff>,
+}
+
+impl StateDiff {
+    /// Returns whether the states are the same.
+    pub fn is_empty(&self) -> bool {
+        self.resources.is_empty()
+    }
+
+    /// Render the differences for humans, optionally in color.
+    pub fn render(&self, color: bool) -> String {
+        let paint = |s: String, f: fn(&str) -> colored::ColoredString| {
+            if color {
+                f(&s).to_string()
+            } else {
+                s
+            }
+        };
+        self.resources
+            .iter()
+            .map(|(name, diff)| match diff {
+                ResourceDiff::Added { .. } => paint(format!("+ {name}"), |s| s.green()),
+                ResourceDiff::Removed { .. } => paint(format!("- {name}"), |s| s.red()),
+                ResourceDiff::Changed { changes } => {
+                    let changes = crate::diff::render(changes, color)
+                        .lines()
+                        .map(|line| format!("    {line}"))
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    format!("~ {name}\n{changes}")
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Render the differences as pretty JSON.
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+impl std::fmt::Display for StateDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.render(false))
+    }
+}
+
+/// Read the store file at the given path, or every store file in the given
+/// directory, keyed by resource name.
+fn read_state(path: &Path) -> anyhow::Result<BTreeMap<String, Rez>> {
+    if path.is_file() {
+        let bytes = std::fs::read(path)?;
+        return file::decode(path, &bytes);
+    }
+    let mut files = std::fs::read_dir(path)
+        .with_context(|| format!("could not read {}", path.display()))?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<anyhow::Result<Vec<PathBuf>>>()?;
+    files.sort();
+    let mut state = BTreeMap::new();
+    for file_path in files {
+        if file_path.extension().is_none_or(|ext| ext != "json") || !file_path.is_file() {
+            continue;
+        }
+        let bytes = std::fs::read(&file_path)?;
+        let rez = match file::decode(&file_path, &bytes) {
+            Ok(rez) => rez,
+            Err(e) => {
+                log::debug!("skipping {} - not a store: {e}", file_path.display());
+                continue;
+            }
+        };
+        let stem = file_path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        state.extend(
+            rez.into_iter()
+                .map(|(name, rez)| (format!("{stem}/{name}"), rez)),
+        );
+    }
+    Ok(state)
+}
+
+/// Compare the states at two paths, each either a store file or a directory
+/// of store files.
+pub fn diff(a: impl AsRef<Path>, b: impl AsRef<Path>) -> anyhow::Result<StateDiff> {
+    let a = read_state(a.as_ref())?;
+    let mut b = read_state(b.as_ref())?;
+    let mut resources = BTreeMap::new();
+    for (name, rez_a) in a {
+        let diff = match b.remove(&name) {
+            None => ResourceDiff::Removed {
+                data: redact(&rez_a.data),
+            },
+            Some(rez_b) => {
+                let mut changes = vec![];
+                if rez_a.type_is != rez_b.type_is {
+                    changes.push(Change::Changed {
+                        path: "type_is".to_string(),
+                        old: rez_a.type_is.into(),
+                        new: rez_b.type_is.into(),
+                    });
+                }
+                changes.extend(crate::diff::diff(&rez_a.data, &rez_b.data));
+                if changes.is_empty() {
+                    continue;
+                }
+                ResourceDiff::Changed { changes }
+            }
+        };
+        resources.insert(name, diff);
+    }
+    resources.extend(b.into_iter().map(|(name, rez)| {
+        (
+            name,
+            ResourceDiff::Added {
+                data: redact(&rez.data),
+            },
+        )
+    }));
+    Ok(StateDiff { resources })
+}
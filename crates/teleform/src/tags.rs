@@ -0,0 +1,52 @@
+//! Tags on stored resources.
+//!
+//! Tags are free-form key/value pairs kept in the store file, eg to record
+//! which account each resource of a multi-account setup belongs to. Synced
+//! resources are also given the
+//! [`default_tags`](crate::config::Settings::default_tags) of the store's
+//! settings.
+use crate::Store;
+
+/// The tag key of the account a resource belongs to.
+pub const ACCOUNT_TAG: &str = "account";
+
+impl<Config> Store<Config> {
+    /// Tag the resource with the given name.
+    ///
+    /// Call this after syncing the resource.
+    pub fn tag(
+        &mut self,
+        name: &str,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) -> anyhow::Result<()> {
+        let Some(rez) = self.rez.get_mut(name) else {
+            log::debug!("not tagging {name} - it is not in the store");
+            return Ok(());
+        };
+        rez.tags.insert(key.into(), value.into());
+        if self.apply {
+            self.save(&self.path)?;
+        }
+        Ok(())
+    }
+
+    /// Tag the resource with the given name with the account it belongs to.
+    pub fn set_account(&mut self, name: &str, account: impl Into<String>) -> anyhow::Result<()> {
+        self.tag(name, ACCOUNT_TAG, account)
+    }
+
+    /// Returns the tags of the resource with the given name.
+    pub fn tags(&self, name: &str) -> Option<&std::collections::BTreeMap<String, String>> {
+        self.rez.get(name).map(|rez| &rez.tags)
+    }
+
+    /// Returns the names of the resources with the given tag.
+    pub fn tagged(&self, key: &str, value: &str) -> Vec<String> {
+        self.rez
+            .iter()
+            .filter(|(_, rez)| rez.tags.get(key).map(String::as_str) == Some(value))
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+}
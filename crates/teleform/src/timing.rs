@@ -0,0 +1,101 @@
+//! Estimates of how long a run will take, from the durations of past applies.
+//!
+//! Applying records how long each created or updated resource's actions took
+//! in its [`last_duration_ms`](crate::Rez::last_duration_ms). With a time
+//! budget set by [`Store::with_time_budget`], planning adds up the recorded
+//! durations of the resources it would create or update and warns once the
+//! estimate exceeds the budget, so that slow resources (eg CloudFront
+//! distributions or RDS instances) can be scheduled deliberately instead of
+//! discovered mid-apply.
+//!
+//! Resources without a recorded duration are estimated by the average of the
+//! other resources of their type.
+use std::time::Duration;
+
+use crate::Store;
+
+/// Tracks the estimated duration of the changes planned by a run.
+#[derive(Debug, Default)]
+pub(crate) struct Timing {
+    budget: Option<Duration>,
+    estimate: Duration,
+    warned: bool,
+}
+
+impl<Config> Store<Config> {
+    /// Warn when planning changes that are estimated to take longer than the
+    /// given budget to apply.
+    pub fn with_time_budget(mut self, budget: Duration) -> Self {
+        self.timing.budget = Some(budget);
+        self
+    }
+
+    /// Returns how long the changes planned so far are estimated to take to
+    /// apply, from the durations of past applies.
+    ///
+    /// This is always zero when applying.
+    pub fn estimated_duration(&self) -> Duration {
+        self.timing.estimate
+    }
+
+    /// Returns how long the actions of the resource with the given name took
+    /// when it was last created or updated, or if that was never recorded, the
+    /// average of the other resources of its type.
+    pub fn historical_duration(&self, name: &str) -> Option<Duration> {
+        let rez = self.rez.get(name)?;
+        if let Some(ms) = rez.last_duration_ms {
+            return Some(Duration::from_millis(ms));
+        }
+        let durations = self
+            .rez
+            .values()
+            .filter(|other| other.type_is == rez.type_is)
+            .filter_map(|other| other.last_duration_ms)
+            .collect::<Vec<_>>();
+        if durations.is_empty() {
+            return None;
+        }
+        let average = durations.iter().sum::<u64>() / durations.len() as u64;
+        Some(Duration::from_millis(average))
+    }
+
+    /// Account for the actions just taken on the resource with the given name.
+    ///
+    /// When applying this records their duration, otherwise it adds the
+    /// resource's historical duration to the estimate of the run.
+    pub(crate) fn time_actions(&mut self, name: &str) {
+        if self.apply {
+            let ms = self
+                .runner
+                .report
+                .actions
+                .iter()
+                .filter(|report| report.name == name)
+                .map(|report| report.duration.as_millis() as u64)
+                .sum();
+            if let Some(rez) = self.rez.get_mut(name) {
+                rez.last_duration_ms = Some(ms);
+            }
+            return;
+        }
+        let Some(duration) = self.historical_duration(name) else {
+            return;
+        };
+        self.timing.estimate += duration;
+        if duration >= Duration::from_secs(60) {
+            log::info!("{name} took {}s to apply last time", duration.as_secs());
+        }
+        match self.timing.budget {
+            Some(budget) if self.timing.estimate > budget && !self.timing.warned => {
+                self.timing.warned = true;
+                log::warn!(
+                    "the planned changes are estimated to take {}s to apply, which exceeds the \
+                     time budget of {}s",
+                    self.timing.estimate.as_secs(),
+                    budget.as_secs()
+                );
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,70 @@
+//! Waiting for resources to become ready.
+//!
+//! Many resources aren't usable as soon as the call that creates or updates
+//! them returns, eg a DynamoDB table is `CREATING` for a while. Resources wait
+//! for them in their `create_finalize` and `update_finalize` actions by
+//! polling with [`Wait::until`].
+use std::{future::Future, time::Duration};
+
+use crate::Error;
+
+/// How long to wait for a resource to become ready, and how often to check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Wait {
+    /// How long to wait before giving up.
+    pub timeout: Duration,
+    /// How long to sleep between checks.
+    pub poll_interval: Duration,
+}
+
+impl Default for Wait {
+    fn default() -> Self {
+        Wait {
+            timeout: Duration::from_secs(60 * 5),
+            poll_interval: Duration::from_secs(3),
+        }
+    }
+}
+
+impl Wait {
+    /// Wait with the given timeout.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Wait { timeout, ..self }
+    }
+
+    /// Check with the given poll interval.
+    pub fn with_poll_interval(self, poll_interval: Duration) -> Self {
+        Wait {
+            poll_interval,
+            ..self
+        }
+    }
+
+    /// Poll `is_ready` until it returns `true`.
+    ///
+    /// `what` describes what is being waited on, for logging. Errs with
+    /// [`Error::Timeout`] if `is_ready` doesn't return `true` within the
+    /// timeout, or with the first error `is_ready` returns.
+    pub async fn until<F, Fut>(&self, what: &str, mut is_ready: F) -> anyhow::Result<()>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = anyhow::Result<bool>>,
+    {
+        log::info!("awaiting {what}");
+        let start = std::time::Instant::now();
+        loop {
+            if is_ready().await? {
+                log::info!("...{what} is ready");
+                return Ok(());
+            }
+            if start.elapsed() >= self.timeout {
+                return Err(Error::Timeout {
+                    what: what.to_string(),
+                    timeout: self.timeout,
+                }
+                .into());
+            }
+            crate::rt::sleep(self.poll_interval).await;
+        }
+    }
+}
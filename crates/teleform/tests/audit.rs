@@ -0,0 +1,26 @@
+mod common;
+
+use common::{Cloud, Thing};
+use tele::{Action, Store};
+
+#[tokio::test]
+async fn audit_entries_hash_the_stored_data() {
+    let cloud = Cloud::default();
+    let path = common::store_path("audit_entries_hash_the_stored_data");
+    let mut store = Store::new(true, cloud.clone(), &path);
+    store.sync_by_name("a", Thing::new(1)).await.unwrap();
+    store.sync_by_name("a", Thing::new(2)).await.unwrap();
+
+    let entries = tele::audit::read(store.audit_log().unwrap()).unwrap();
+    let created = entries
+        .iter()
+        .find(|entry| entry.action == Action::Create)
+        .unwrap();
+    let updated = entries
+        .iter()
+        .find(|entry| entry.action == Action::Update)
+        .unwrap();
+    assert_eq!("", created.old_hash);
+    assert_eq!(created.new_hash, updated.old_hash);
+    assert_ne!(updated.old_hash, updated.new_hash);
+}
@@ -0,0 +1,49 @@
+#![cfg(feature = "aws-iam")]
+mod common;
+
+use std::{
+    io::{Read, Write},
+    net::TcpListener,
+    sync::mpsc,
+    time::Duration,
+};
+
+use tele::{
+    aws::{iam::Role, Overrides},
+    Local, Store,
+};
+
+/// Listen on a local port, reporting each request and answering it with an
+/// error. Returns the URL of the listener.
+fn listen() -> (String, mpsc::Receiver<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let _ = tx.send(());
+            let mut buffer = [0; 4096];
+            let _ = stream.read(&mut buffer);
+            let _ = stream.write_all(
+                b"HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            );
+        }
+    });
+    (url, rx)
+}
+
+#[tokio::test]
+async fn iam_uses_the_overrides_of_the_resource() {
+    let (url, requests) = listen();
+    let aws = tele::aws::localstack("http://127.0.0.1:1").await;
+    aws.set_overrides_of("role", Overrides::default().endpoint_url(url));
+    let path = common::store_path("iam_uses_the_overrides_of_the_resource");
+    let mut store = Store::new(true, aws, &path);
+    let role = Role {
+        document: Local(serde_json::json!({})),
+        ..Default::default()
+    };
+    assert!(store.sync_by_name("role", role).await.is_err());
+    assert!(requests.recv_timeout(Duration::from_secs(5)).is_ok());
+}
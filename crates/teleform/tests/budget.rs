@@ -0,0 +1,69 @@
+mod common;
+
+use common::{Cloud, Thing};
+use tele::{
+    approval::Approval,
+    budget::ChangeBudget,
+    cli::{AppConfig, Args, Command},
+    Store,
+};
+
+#[tokio::test]
+async fn declined_actions_are_not_charged() {
+    let cloud = Cloud::default();
+    let path = common::store_path("declined_actions_are_not_charged");
+    common::apply_things(&cloud, &path, &[("a", 1), ("b", 1)]).await;
+
+    let mut store = Store::load(true, cloud.clone(), &path)
+        .await
+        .unwrap()
+        .with_change_budget(ChangeBudget {
+            max_updates: Some(1),
+            ..Default::default()
+        });
+    store.set_approval(|pending| async move {
+        Ok(if pending.name == "a" {
+            Approval::Skip
+        } else {
+            Approval::Approve
+        })
+    });
+    store.sync_by_name("a", Thing::new(2)).await.unwrap();
+    store.sync_by_name("b", Thing::new(2)).await.unwrap();
+    assert_eq!(Some(1), cloud.size_of("a"));
+    assert_eq!(Some(2), cloud.size_of("b"));
+}
+
+#[tokio::test]
+async fn apply_over_budget_changes_nothing() {
+    let cloud = Cloud::default();
+    let path = common::store_path("apply_over_budget_changes_nothing");
+    common::apply_things(&cloud, &path, &[("a", 1), ("b", 1)]).await;
+
+    let app = AppConfig::new(
+        "test",
+        cloud.clone(),
+        |store| {
+            Box::pin(async move {
+                store.sync_by_name("a", Thing::new(2)).await?;
+                store.sync_by_name("b", Thing::new(2)).await?;
+                Ok(())
+            })
+        },
+        |store| Box::pin(store.prune::<Thing>()),
+    )
+    .with_change_budget(ChangeBudget {
+        max_updates: Some(1),
+        ..Default::default()
+    });
+    let args = Args {
+        command: Command::Apply,
+        targets: vec![],
+        auto_approve: true,
+        store_path: Some(path),
+        ignore_change_budget: false,
+    };
+    assert!(tele::cli::run_with_args(app, args).await.is_err());
+    assert_eq!(Some(1), cloud.size_of("a"));
+    assert_eq!(Some(1), cloud.size_of("b"));
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use common::{Cloud, Thing};
+use tele::cli::{AppConfig, Args, Command, RunOutcome, RunSummary};
+
+fn args(command: Command, path: &std::path::Path) -> Args {
+    Args {
+        command,
+        targets: vec![],
+        auto_approve: true,
+        store_path: Some(path.to_path_buf()),
+        ignore_change_budget: false,
+    }
+}
+
+#[tokio::test]
+async fn plan_of_only_deletions_has_changes() {
+    let cloud = Cloud::default();
+    let path = common::store_path("plan_of_only_deletions_has_changes");
+    common::apply_things(&cloud, &path, &[("a", 1)]).await;
+
+    let app = AppConfig::new(
+        "test",
+        cloud.clone(),
+        |_store| Box::pin(async { Ok(()) }),
+        |store| Box::pin(store.prune::<Thing>()),
+    );
+    let result = tele::cli::run_with_args(app, args(Command::Plan, &path)).await;
+    let outcome = RunOutcome::new(false, result);
+    match outcome {
+        RunOutcome::ChangesPlanned(summary) => assert_eq!(
+            RunSummary {
+                destroyed: 1,
+                ..Default::default()
+            },
+            summary
+        ),
+        other => panic!("expected planned changes, got {other:?}"),
+    }
+    assert_eq!(Some(1), cloud.size_of("a"));
+}
+
+#[test]
+fn new_stores_use_the_given_path() {
+    let path = common::store_path("new_stores_use_the_given_path");
+    let store = tele::cli::create_store(&path, Cloud::default(), false).unwrap();
+    assert_eq!(&path, store.path());
+}
+
+#[test]
+fn parses_graph() {
+    let args = Args::parse(["graph".to_string()]).unwrap();
+    assert_eq!(Command::Graph, args.command);
+}
+
+#[tokio::test]
+async fn unhealthy_providers_change_nothing() {
+    let cloud = Cloud {
+        unhealthy: true,
+        ..Default::default()
+    };
+    let path = common::store_path("unhealthy_providers_change_nothing");
+    let app = AppConfig::new(
+        "test",
+        cloud.clone(),
+        |store| Box::pin(async move { store.sync_by_name("a", Thing::new(1)).await.map(|_| ()) }),
+        |store| Box::pin(store.prune::<Thing>()),
+    );
+    assert!(tele::cli::run_with_args(app, args(Command::Apply, &path))
+        .await
+        .is_err());
+    assert_eq!(None, cloud.size_of("a"));
+}
+
+#[tokio::test]
+async fn applies_with_git_one_after_another() {
+    let cloud = Cloud::default();
+    let path = common::store_path("applies_with_git_one_after_another");
+    let dir = path.parent().unwrap();
+    for args in [
+        &["init", "--quiet"][..],
+        &["config", "user.name", "test"],
+        &["config", "user.email", "test@example.com"],
+    ] {
+        let status = std::process::Command::new("git")
+            .current_dir(dir)
+            .args(args)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    for size in [1, 2] {
+        let app = AppConfig::new(
+            "test",
+            cloud.clone(),
+            move |store| {
+                Box::pin(async move { store.sync_by_name("a", Thing::new(size)).await.map(|_| ()) })
+            },
+            |store| Box::pin(store.prune::<Thing>()),
+        )
+        .with_git();
+        tele::cli::run_with_args(app, args(Command::Apply, &path))
+            .await
+            .unwrap();
+        assert_eq!(Some(size), cloud.size_of("a"));
+    }
+    let log = std::process::Command::new("git")
+        .current_dir(dir)
+        .args(["log", "--name-only", "--format="])
+        .output()
+        .unwrap();
+    let committed = String::from_utf8_lossy(&log.stdout);
+    assert!(!committed.contains("snapshots"), "{committed}");
+}
@@ -0,0 +1,178 @@
+//! A fake provider, whose resources live in memory.
+#![allow(dead_code)]
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use tele::{Local, TeleSync};
+
+/// The fake cloud, holding the size of each [`Thing`] by name.
+#[derive(Debug, Default, Clone)]
+pub struct Cloud {
+    pub things: Arc<Mutex<BTreeMap<String, u32>>>,
+    /// Errors that the next creations fail with, in order.
+    pub failures: Arc<Mutex<Vec<String>>>,
+    /// Whether the cloud fails its healthcheck.
+    pub unhealthy: bool,
+    /// How many times many things were read at once.
+    pub sweeps: Arc<Mutex<u32>>,
+    /// The zone of each thing that isn't in the default zone.
+    pub zones: Arc<Mutex<BTreeMap<String, String>>>,
+}
+
+impl Cloud {
+    /// Returns the size of the thing with the given name, if it exists.
+    pub fn size_of(&self, name: &str) -> Option<u32> {
+        self.things.lock().unwrap().get(name).copied()
+    }
+
+    /// Change a thing behind teleform's back.
+    pub fn set(&self, name: &str, size: u32) {
+        self.things.lock().unwrap().insert(name.to_string(), size);
+    }
+
+    /// Fail the next creation with the given error.
+    pub fn fail_next(&self, error: &str) {
+        self.failures.lock().unwrap().push(error.to_string());
+    }
+
+    /// Delete a thing behind teleform's back.
+    pub fn remove(&self, name: &str) {
+        self.things.lock().unwrap().remove(name);
+    }
+}
+
+impl tele::Provider for Cloud {
+    fn healthcheck(&self) -> tele::middleware::BoxFuture<'_> {
+        let unhealthy = self.unhealthy;
+        Box::pin(async move {
+            anyhow::ensure!(!unhealthy, "the cloud is down");
+            Ok(())
+        })
+    }
+
+    fn settings_of(&self, name: &str) -> BTreeMap<String, String> {
+        self.zones
+            .lock()
+            .unwrap()
+            .get(name)
+            .map(|zone| [("zone".to_string(), zone.clone())].into())
+            .unwrap_or_default()
+    }
+
+    fn restore_settings(&self, name: &str, settings: &BTreeMap<String, String>) {
+        if let Some(zone) = settings.get("zone") {
+            self.zones
+                .lock()
+                .unwrap()
+                .entry(name.to_string())
+                .or_insert_with(|| zone.clone());
+        }
+    }
+}
+
+impl AsRef<Cloud> for Cloud {
+    fn as_ref(&self) -> &Cloud {
+        self
+    }
+}
+
+#[derive(TeleSync, Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[tele(helper = Cloud)]
+#[tele(create = create_thing, update = update_thing, delete = delete_thing)]
+#[tele(read = read_thing, read_many = read_things, exists = thing_exists)]
+pub struct Thing {
+    pub size: Local<u32>,
+}
+
+impl Thing {
+    pub fn new(size: u32) -> Self {
+        Thing { size: Local(size) }
+    }
+}
+
+async fn create_thing(
+    thing: &mut Thing,
+    apply: bool,
+    cloud: &Cloud,
+    name: &str,
+) -> anyhow::Result<()> {
+    if apply {
+        let mut failures = cloud.failures.lock().unwrap();
+        if !failures.is_empty() {
+            anyhow::bail!(failures.remove(0));
+        }
+        drop(failures);
+        cloud.set(name, *thing.size);
+    }
+    Ok(())
+}
+
+async fn update_thing(
+    thing: &mut Thing,
+    apply: bool,
+    cloud: &Cloud,
+    name: &str,
+    _previous: &Thing,
+) -> anyhow::Result<()> {
+    if apply {
+        cloud.set(name, *thing.size);
+    }
+    Ok(())
+}
+
+async fn delete_thing(
+    _thing: &Thing,
+    apply: bool,
+    cloud: &Cloud,
+    name: &str,
+) -> anyhow::Result<()> {
+    if apply {
+        cloud.remove(name);
+    }
+    Ok(())
+}
+
+async fn read_thing(thing: &mut Thing, cloud: &Cloud, name: &str) -> anyhow::Result<()> {
+    let size = cloud
+        .size_of(name)
+        .ok_or_else(|| anyhow::anyhow!("no thing {name}"))?;
+    thing.size = Local(size);
+    Ok(())
+}
+
+async fn read_things(things: &mut BTreeMap<String, Thing>, cloud: &Cloud) -> anyhow::Result<()> {
+    *cloud.sweeps.lock().unwrap() += 1;
+    things.retain(|name, thing| match cloud.size_of(name) {
+        Some(size) => {
+            thing.size = Local(size);
+            true
+        }
+        None => false,
+    });
+    Ok(())
+}
+
+async fn thing_exists(_thing: &Thing, cloud: &Cloud, name: &str) -> anyhow::Result<bool> {
+    Ok(cloud.size_of(name).is_some())
+}
+
+/// Returns the path of a store file in a fresh temporary directory.
+pub fn store_path(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir()
+        .join("teleform-tests")
+        .join(format!("{test}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.join("store.json")
+}
+
+/// Create the things with the given names and sizes in a new store at `path`.
+pub async fn apply_things(cloud: &Cloud, path: &PathBuf, things: &[(&str, u32)]) {
+    let mut store = tele::Store::new(true, cloud.clone(), path);
+    for (name, size) in things {
+        store.sync_by_name(*name, Thing::new(*size)).await.unwrap();
+    }
+}
@@ -0,0 +1,29 @@
+mod common;
+
+use common::{Cloud, Thing};
+use tele::Store;
+
+#[tokio::test]
+async fn config_sets_the_default_tags_and_backend() {
+    let cloud = Cloud::default();
+    let path = common::store_path("config_sets_the_default_tags_and_backend");
+    let dir = path.parent().unwrap();
+    let config = dir.join("tele.toml");
+    std::fs::write(
+        &config,
+        "[backend]\nkind = \"local\"\ndir = \"published\"\n\n[default_tags]\nproject = \"test\"\n",
+    )
+    .unwrap();
+
+    let mut store = Store::from_config(true, cloud, &config).unwrap();
+    store.sync_by_name("a", Thing::new(1)).await.unwrap();
+    assert_eq!(
+        Some("test"),
+        store
+            .tags("a")
+            .and_then(|tags| tags.get("project"))
+            .map(String::as_str)
+    );
+    store.push().await.unwrap();
+    assert!(dir.join("published").join("store.json").is_file());
+}
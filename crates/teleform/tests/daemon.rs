@@ -0,0 +1,75 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{Cloud, Thing};
+use tele::{cli::RunSummary, daemon::Daemon, Store};
+
+#[tokio::test]
+async fn remediates_drift_that_is_only_in_the_provider() {
+    let cloud = Cloud::default();
+    let path = common::store_path("remediates_drift_that_is_only_in_the_provider");
+    common::apply_things(&cloud, &path, &[("a", 1)]).await;
+    // the store still says 1
+    cloud.set("a", 5);
+
+    let store_cloud = cloud.clone();
+    let mut daemon = Daemon::new(
+        "test",
+        Duration::from_secs(60),
+        move |apply| {
+            let mut store = Store::from_path(apply, store_cloud.clone(), &path)?;
+            store.register::<Thing>();
+            Ok(store)
+        },
+        |store| {
+            Box::pin(async move {
+                store.sync_by_name("a", Thing::new(1)).await?;
+                Ok(())
+            })
+        },
+    );
+    let updated = RunSummary {
+        updated: 1,
+        ..Default::default()
+    };
+    assert_eq!(updated, daemon.detect_drift().await.unwrap());
+    assert_eq!(updated, daemon.remediate().await.unwrap());
+    assert_eq!(Some(1), cloud.size_of("a"));
+    assert!(daemon.detect_drift().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn refreshes_resources_of_a_type_with_one_sweep() {
+    let cloud = Cloud::default();
+    let path = common::store_path("refreshes_resources_of_a_type_with_one_sweep");
+    common::apply_things(&cloud, &path, &[("a", 1), ("b", 2), ("c", 3)]).await;
+    cloud.set("a", 5);
+    cloud.remove("b");
+
+    let store_cloud = cloud.clone();
+    let mut daemon = Daemon::new(
+        "test",
+        Duration::from_secs(60),
+        move |apply| {
+            let mut store = Store::from_path(apply, store_cloud.clone(), &path)?;
+            store.register::<Thing>();
+            Ok(store)
+        },
+        |store| {
+            Box::pin(async move {
+                store.sync_by_name("a", Thing::new(1)).await?;
+                store.sync_by_name("b", Thing::new(2)).await?;
+                store.sync_by_name("c", Thing::new(3)).await?;
+                Ok(())
+            })
+        },
+    );
+    let drift = RunSummary {
+        created: 1,
+        updated: 1,
+        ..Default::default()
+    };
+    assert_eq!(drift, daemon.detect_drift().await.unwrap());
+    assert_eq!(1, *cloud.sweeps.lock().unwrap());
+}
@@ -0,0 +1,19 @@
+#![cfg(feature = "graph")]
+mod common;
+
+use common::{Cloud, Thing};
+use tele::Store;
+
+#[tokio::test]
+async fn graph_page_escapes_scripts_in_names() {
+    let cloud = Cloud::default();
+    let path = common::store_path("graph_page_escapes_scripts_in_names");
+    let mut store = Store::new(false, cloud, &path);
+    store
+        .sync_by_name("</script><script>alert(1)</script>", Thing::new(1))
+        .await
+        .unwrap();
+    let page = store.graph_page().unwrap();
+    assert!(!page.contains("<script>alert"));
+    assert_eq!(4, page.matches("</script>").count());
+}
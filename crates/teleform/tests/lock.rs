@@ -0,0 +1,35 @@
+#![cfg(unix)]
+mod common;
+
+use common::{Cloud, Thing};
+use tele::{Error, Store};
+
+#[tokio::test]
+async fn replaces_locks_of_processes_that_are_gone() {
+    let cloud = Cloud::default();
+    let path = common::store_path("replaces_locks_of_processes_that_are_gone");
+    let mut child = std::process::Command::new("true").spawn().unwrap();
+    let pid = child.id();
+    child.wait().unwrap();
+
+    let mut store = Store::new(true, cloud.clone(), &path);
+    std::fs::write(store.lock_path(), pid.to_string()).unwrap();
+    store.sync_by_name("a", Thing::new(1)).await.unwrap();
+    assert_eq!(Some(1), cloud.size_of("a"));
+}
+
+#[tokio::test]
+async fn keeps_locks_of_running_processes() {
+    let cloud = Cloud::default();
+    let path = common::store_path("keeps_locks_of_running_processes");
+    let mut store = Store::new(true, cloud.clone(), &path);
+    let lock_path = store.lock_path();
+    std::fs::write(&lock_path, std::os::unix::process::parent_id().to_string()).unwrap();
+
+    let e = store.sync_by_name("a", Thing::new(1)).await.unwrap_err();
+    assert!(matches!(
+        e.downcast_ref(),
+        Some(Error::StoreLocked { path, .. }) if *path == lock_path
+    ));
+    assert_eq!(None, cloud.size_of("a"));
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use common::{Cloud, Thing};
+use tele::Store;
+
+#[tokio::test]
+async fn prune_order_skips_types_that_are_configured_out() {
+    let cloud = Cloud::default();
+    let path = common::store_path("prune_order_skips_types_that_are_configured_out");
+    common::apply_things(&cloud, &path, &[("a", 1)]).await;
+
+    let mut store = Store::load(true, cloud.clone(), &path).await.unwrap();
+    tele::prune_order!(
+        &mut store,
+        [
+            #[cfg(any())]
+            Thing
+        ]
+    )
+    .await
+    .unwrap();
+    assert_eq!(Some(1), cloud.size_of("a"));
+    tele::prune_order!(
+        &mut store,
+        [
+            #[cfg(any())]
+            Thing,
+            Thing
+        ]
+    )
+    .await
+    .unwrap();
+    assert_eq!(None, cloud.size_of("a"));
+}
+
+#[tokio::test]
+async fn undeclared_resources_are_pruned_with_their_stored_settings() {
+    let cloud = Cloud::default();
+    let path = common::store_path("undeclared_resources_are_pruned_with_their_stored_settings");
+    cloud
+        .zones
+        .lock()
+        .unwrap()
+        .insert("a".to_string(), "east".to_string());
+    common::apply_things(&cloud, &path, &[("a", 1)]).await;
+
+    // a later run that no longer declares the thing, or its zone
+    let cloud = Cloud {
+        zones: Default::default(),
+        ..cloud
+    };
+    let mut store = Store::load(true, cloud.clone(), &path).await.unwrap();
+    store.prune::<Thing>().await.unwrap();
+    assert_eq!(None, cloud.size_of("a"));
+    assert_eq!(
+        Some("east"),
+        cloud.zones.lock().unwrap().get("a").map(String::as_str)
+    );
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{Cloud, Thing};
+use tele::{
+    options::{is_retryable, ResourceOptions},
+    Action, Error, Store,
+};
+
+#[tokio::test]
+async fn throttled_actions_are_retried() {
+    let cloud = Cloud::default();
+    let path = common::store_path("throttled_actions_are_retried");
+    let mut store = Store::new(true, cloud.clone(), &path);
+    let id = store.resource_with_opts::<Thing>("a", ResourceOptions::new().retries(1));
+    cloud.fail_next("ThrottlingException: Rate exceeded");
+    store.sync(&id, Thing::new(1)).await.unwrap();
+    assert_eq!(Some(1), cloud.size_of("a"));
+}
+
+#[tokio::test]
+async fn invalid_actions_are_not_retried() {
+    let cloud = Cloud::default();
+    let path = common::store_path("invalid_actions_are_not_retried");
+    let mut store = Store::new(true, cloud.clone(), &path);
+    let id = store.resource_with_opts::<Thing>("a", ResourceOptions::new().retries(1));
+    cloud.fail_next("ValidationException: size is too small");
+    assert!(store.sync(&id, Thing::new(1)).await.is_err());
+    assert_eq!(None, cloud.size_of("a"));
+}
+
+#[test]
+fn timed_out_creations_are_not_retried() {
+    let timeout = || {
+        anyhow::Error::from(Error::Timeout {
+            what: "create of a".to_string(),
+            timeout: Duration::from_secs(1),
+        })
+    };
+    assert!(!is_retryable(Action::Create, &timeout()));
+    assert!(is_retryable(Action::Update, &timeout()));
+    assert!(!is_retryable(
+        Action::Read,
+        &Error::not_implemented::<Thing>("read").into()
+    ));
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use common::Cloud;
+use tele::{
+    backend::LocalBackend,
+    config::{FromEnv, Settings},
+    Store,
+};
+
+fn secrets() -> Settings {
+    Settings {
+        vars: [("PASSWORD".to_string(), "hunter2".to_string())].into(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn secret_hashes_are_keyed_by_the_store() {
+    let a = Store::new(true, Cloud::default(), common::store_path("secrets_a"));
+    let b = Store::new(true, Cloud::default(), common::store_path("secrets_b"));
+    let first: FromEnv<String> = a.resolve_secret(&secrets(), "PASSWORD").unwrap();
+    let again: FromEnv<String> = a.resolve_secret(&secrets(), "PASSWORD").unwrap();
+    let other: FromEnv<String> = b.resolve_secret(&secrets(), "PASSWORD").unwrap();
+    assert!(a.secrets_key_path().is_file());
+    assert_eq!(first, again);
+    assert_ne!(first.hash, other.hash);
+    assert_eq!("hunter2", first.value().unwrap());
+}
+
+#[test]
+fn planning_creates_no_secrets_key() {
+    let store = Store::new(false, Cloud::default(), common::store_path("secrets_plan"));
+    let _: FromEnv<String> = store.resolve_secret(&secrets(), "PASSWORD").unwrap();
+    assert!(!store.secrets_key_path().exists());
+}
+
+#[tokio::test]
+async fn secrets_keys_are_not_pushed() {
+    let path = common::store_path("secrets_push");
+    let remote = path.with_file_name("remote");
+    let mut store = Store::new(true, Cloud::default(), &path);
+    let _: FromEnv<String> = store.resolve_secret(&secrets(), "PASSWORD").unwrap();
+    store.save(&path).unwrap();
+    store.set_backend(LocalBackend::new(&remote));
+    store.push().await.unwrap();
+    assert!(remote.join("store.json").is_file());
+    assert!(!remote.join("store.key").exists());
+}
@@ -0,0 +1,35 @@
+mod common;
+
+use common::{Cloud, Thing};
+use tele::{approval::Approval, budget::ChangeBudget, Store};
+
+#[tokio::test]
+async fn stacks_keep_the_change_budget() {
+    let cloud = Cloud::default();
+    let path = common::store_path("stacks_keep_the_change_budget");
+    let store = Store::new(true, cloud.clone(), &path);
+    let mut stack = store.stack("app").unwrap();
+    stack.sync_by_name("a", Thing::new(1)).await.unwrap();
+    stack.sync_by_name("b", Thing::new(1)).await.unwrap();
+    drop(stack);
+
+    let store = store.with_change_budget(ChangeBudget {
+        max_updates: Some(1),
+        ..Default::default()
+    });
+    let mut stack = store.stack("app").unwrap();
+    stack.sync_by_name("a", Thing::new(2)).await.unwrap();
+    assert!(stack.sync_by_name("b", Thing::new(2)).await.is_err());
+    assert_eq!(Some(1), cloud.size_of("b"));
+}
+
+#[tokio::test]
+async fn stacks_keep_the_approval() {
+    let cloud = Cloud::default();
+    let path = common::store_path("stacks_keep_the_approval");
+    let mut store = Store::new(true, cloud.clone(), &path);
+    store.set_approval(|_| async { Ok(Approval::Skip) });
+    let mut stack = store.stack("app").unwrap();
+    stack.sync_by_name("a", Thing::new(1)).await.unwrap();
+    assert_eq!(None, cloud.size_of("a"));
+}
@@ -0,0 +1,138 @@
+mod common;
+
+use common::{Cloud, Thing};
+use tele::{Local, Remote, Store, TeleSync};
+
+/// A resource whose deployment is only known once it is finalized.
+#[derive(TeleSync, Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[tele(helper = Cloud)]
+#[tele(create = create_deployed, create_finalize = finalize_deployed)]
+struct Deployed {
+    version: Local<u32>,
+    deployment: Remote<String>,
+}
+
+async fn create_deployed(
+    _deployed: &mut Deployed,
+    _apply: bool,
+    _cloud: &Cloud,
+    _name: &str,
+) -> anyhow::Result<()> {
+    Ok(())
+}
+
+async fn finalize_deployed(
+    deployed: &mut Deployed,
+    apply: bool,
+    _cloud: &Cloud,
+    _name: &str,
+) -> anyhow::Result<()> {
+    if apply {
+        deployed.deployment = Remote::Remote(format!("deployment-{}", *deployed.version));
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn finalized_data_is_stored() {
+    let cloud = Cloud::default();
+    let path = common::store_path("finalized_data_is_stored");
+    let definition = || Deployed {
+        version: Local(1),
+        deployment: Remote::Unknown,
+    };
+    let mut store = Store::new(true, cloud.clone(), &path);
+    let deployed = store.sync_by_name("d", definition()).await.unwrap();
+    assert_eq!(
+        Remote::Remote("deployment-1".to_string()),
+        deployed.deployment
+    );
+    drop(store);
+
+    let mut store = Store::load(false, cloud, &path).await.unwrap();
+    let deployed = store.sync_by_name("d", definition()).await.unwrap();
+    assert_eq!(
+        Remote::Remote("deployment-1".to_string()),
+        deployed.deployment
+    );
+    assert!(store.report().actions.is_empty());
+}
+
+#[tokio::test]
+async fn unchanged_resources_deleted_outside_are_created_again() {
+    let cloud = Cloud::default();
+    let path = common::store_path("unchanged_resources_deleted_outside_are_created_again");
+    common::apply_things(&cloud, &path, &[("a", 1)]).await;
+    cloud.remove("a");
+
+    let mut store = Store::load(true, cloud.clone(), &path).await.unwrap();
+    store.sync_by_name("a", Thing::new(1)).await.unwrap();
+    assert_eq!(Some(1), cloud.size_of("a"));
+}
+
+#[tokio::test]
+async fn plans_explain_their_updates() {
+    let cloud = Cloud::default();
+    let path = common::store_path("plans_explain_their_updates");
+    common::apply_things(&cloud, &path, &[("a", 1)]).await;
+
+    let mut store = Store::load(false, cloud, &path).await.unwrap();
+    store.sync_by_name("a", Thing::new(2)).await.unwrap();
+    assert_eq!("a: field `size` changed", store.report().render_reasons());
+}
+
+/// A resource that refers to the size of another.
+#[derive(TeleSync, Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[tele(helper = Cloud)]
+#[tele(create = create_link, references = linked_sizes)]
+struct Link {
+    size: Local<u32>,
+}
+
+async fn create_link(
+    _link: &mut Link,
+    _apply: bool,
+    _cloud: &Cloud,
+    _name: &str,
+) -> anyhow::Result<()> {
+    Ok(())
+}
+
+fn linked_sizes(link: &Link) -> Vec<serde_json::Value> {
+    vec![(*link.size).into()]
+}
+
+#[tokio::test]
+async fn referenced_values_are_recorded_as_dependencies() {
+    let cloud = Cloud::default();
+    let path = common::store_path("referenced_values_are_recorded_as_dependencies");
+    common::apply_things(&cloud, &path, &[("a", 1), ("b", 2)]).await;
+
+    let mut store = Store::load(true, cloud.clone(), &path).await.unwrap();
+    store
+        .sync_by_name("link", Link { size: Local(2) })
+        .await
+        .unwrap();
+    assert_eq!(vec!["link".to_string()], store.dependents("b"));
+    assert!(store.dependents("a").is_empty());
+    // recorded dependencies don't replace references
+    store.depends_on_by_name("link", ["a"]).unwrap();
+    assert_eq!(vec!["link".to_string()], store.dependents("b"));
+}
+
+#[tokio::test]
+async fn resources_are_managed_by_handle() {
+    let cloud = Cloud::default();
+    let path = common::store_path("resources_are_managed_by_handle");
+    let mut store = Store::new(true, cloud.clone(), &path);
+    let a = store.resource::<Thing>("a");
+    let link = store.resource::<Link>("link");
+    store.sync(&a, Thing::new(1)).await.unwrap();
+    store.sync(&link, Link { size: Local(5) }).await.unwrap();
+    store.depends_on(&link, &[&a]).unwrap();
+    assert_eq!(vec!["link".to_string()], store.dependents("a"));
+
+    store.destroy(&a).await.unwrap();
+    assert_eq!(None, cloud.size_of("a"));
+    assert_eq!(vec!["link"], store.stored_ids());
+}